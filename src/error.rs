@@ -1,11 +1,12 @@
 use miniscript::bitcoin::{
     self, amount, bip32, hashes, hex, key, network, script, taproot, witness_program,
+    witness_version,
 };
 use miniscript::policy::compiler::CompilerError;
 use miniscript::{descriptor, TranslateErr};
 
 use crate::parser::ast::{Ident, InfixOp};
-use crate::runtime::Value;
+use crate::runtime::{limits::Limit, Value};
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -83,15 +84,27 @@ pub enum RuntimeError {
     #[error("Expected a policy or pubkey, not {0:?}")]
     NotPolicyLike(Box<Value>),
 
+    #[error("Expected a miniscript, not {0:?}")]
+    NotMiniscript(Box<Value>),
+
     #[error("Expected a descriptor or pubkey, not {0:?}")]
     NotDescriptorLike(Box<Value>),
 
     #[error("Expected Script, not {0:?}")]
     NotScript(Box<Value>),
 
+    #[error("Expected a script context of \"legacy\", \"segwitv0\" or \"tapscript\", not {0:?}")]
+    InvalidScriptContext(Box<Value>),
+
     #[error("Expected a transaction as object, raw bytes or tagged list, not {0:?}")]
     NotTxLike(Box<Value>),
 
+    #[error("Expected an Elements transaction as object or tagged list, not {0:?}")]
+    NotElementsTxLike(Box<Value>),
+
+    #[error("Invalid Elements {0}, expected explicit or confidential (committed) bytes: {1:?}")]
+    InvalidConfidential(&'static str, Box<Value>),
+
     #[error("Expected raw Script or Bytes, not {0:?}. Perhaps you meant to use explicitScript()/scriptPubKey()?")]
     InvalidScriptConstructor(Box<Value>),
 
@@ -157,15 +170,74 @@ pub enum RuntimeError {
     #[error("Script probabilities are not supported in explicit tree structure")]
     InvalidScriptProb,
 
+    #[error("Expected a compilation target of \"wsh\", \"sh-wsh\", \"sh\" or \"tr\", not {0:?}")]
+    InvalidCompileTarget(String),
+
+    #[error("Expected a minimization goal of \"expected\", \"weight\" or \"size\", not {0:?}")]
+    InvalidCompileGoal(String),
+
+    #[error("Cannot combine miniscript fragments of the {0} and {1} script contexts")]
+    MiniscriptMixedContexts(&'static str, &'static str),
+
+    #[error("Policy has more than {0} spending paths")]
+    CompileTooManyPaths(usize),
+
+    #[error("Cannot satisfy the compiled descriptor using the spending path assets")]
+    CompileUnsatisfiablePath,
+
     #[error("sh() can only wrap wsh() or wpkh()")]
     InvalidShUse,
 
     #[error("Script cannot be represented as an address: {0}")]
     NotAddressable(bitcoin::ScriptBuf),
 
+    #[error("Elements addresses can only be generated for segwit scriptPubKeys, not {0}")]
+    ElementsAddressNotSegwit(bitcoin::ScriptBuf),
+
+    #[error("Unknown Elements network \"{0}\", expected \"liquid\", \"liquidtestnet\" or \"elementsregtest\"")]
+    InvalidElementsNetwork(String),
+
+    #[error("Expected a prevout for each of the {1} inputs, not {0}")]
+    ElementsPrevoutsMismatch(usize, usize),
+
     #[error("Number operation overflowed")]
     Overflow,
 
+    #[error("Transaction outputs total ({1}) exceeds the inputs total ({0})")]
+    TxOutputsExceedInputs(bitcoin::Amount, bitcoin::Amount),
+
+    #[error("TRUC (v3) transaction vsize of {0} vB exceeds the 10000 vB limit")]
+    TrucTooLarge(usize),
+
+    #[error("TRUC (v3) transaction vsize of {0} vB exceeds the 1000 vB limit for transactions with an unconfirmed parent")]
+    TrucChildTooLarge(usize),
+
+    #[error("TRUC (v3) transactions may only have a single unconfirmed parent, not {0}")]
+    TrucMultipleParents(usize),
+
+    #[error("TRUC (v3) transaction cannot spend from the unconfirmed non-TRUC parent {0}")]
+    TrucNonTrucParent(bitcoin::Txid),
+
+    #[error("Non-TRUC (v{0}) transaction cannot spend from the unconfirmed TRUC parent {1}")]
+    TrucNonTrucChild(i32, bitcoin::Txid),
+
+    #[error("Insufficient funds to pay for the {0} fee")]
+    InsufficientFundsForFee(bitcoin::Amount),
+
+    #[error("The amounts of all spent inputs are required")]
+    MissingSpentAmount,
+
+    #[error("Execution limit exceeded: {0}")]
+    LimitExceeded(Limit),
+
+    #[error("Execution limit not supported on this target: {0}")]
+    LimitUnsupported(Limit),
+
+    #[error(
+        "Maximum stack depth exceeded, recursive calls can only be this deep when in tail position"
+    )]
+    StackDepthExceeded,
+
     #[error("in {}(): {1}", .0.as_ref().unwrap_or(&"<anonymous>".into()))]
     CallError(Option<Ident>, #[source] Box<RuntimeError>),
 
@@ -188,6 +260,9 @@ pub enum RuntimeError {
     #[error("Invalid merkle root hash: {0}")]
     InvalidMerkleRoot(#[source] hashes::FromSliceError),
 
+    #[error("Invalid scalar, must be below the curve order")]
+    InvalidScalar,
+
     #[error("Invalid pubkey key length: {0} (expected 32 or 33)")]
     InvalidPubKeyLen(usize),
 
@@ -197,6 +272,15 @@ pub enum RuntimeError {
     #[error("Invalid Taproot unspendable key: {0}")]
     InvalidTrUnspendable(Box<Value>),
 
+    #[error("Taproot script tree depth of {0} exceeds the maximum of {1}")]
+    TaprootMaxDepthExceeded(usize, usize), // (actual, max)
+
+    #[error("Taproot annex must start with 0x50")]
+    InvalidAnnex,
+
+    #[error("Script leaf not found in the tr() descriptor: {0:?}")]
+    TaprootLeafNotFound(bitcoin::ScriptBuf),
+
     #[error("No viable taproot internal key found, provide one explicitly")]
     TaprootNoViableKey,
 
@@ -218,9 +302,48 @@ pub enum RuntimeError {
     #[error("Duplicated tag")]
     TagDuplicated,
 
+    #[error("Missing argument for parameter {0}")]
+    MissingArg(String),
+
+    #[error("Expected at most {1} arguments, not {0}")]
+    TooManyArgs(usize, usize), // (actual, max)
+
+    #[error("Unknown named argument: {0}")]
+    UnknownNamedArg(Ident),
+
+    #[error("Argument {0} was given both positionally and by name")]
+    DuplicatedArg(Ident),
+
+    #[error("Named arguments are only supported for user-defined functions")]
+    NamedArgsUnsupported,
+
+    #[error("Cannot destructure into an array pattern, expected an array and not {0:?}")]
+    PatternNotArray(Box<Value>),
+
+    #[error("Array pattern expected {1} elements, not {0}")]
+    PatternLength(usize, usize), // (actual, expected)
+
+    #[error("Array pattern expected at least {1} elements, not {0}")]
+    PatternMinLength(usize, usize), // (actual, min)
+
+    #[error("Tagged list pattern tag not found: \"{0}\"")]
+    PatternTagNotFound(String),
+
     #[error("Unknown tag")]
     TagUnknown,
 
+    #[error("Invalid format string: {0}")]
+    InvalidFormatString(String),
+
+    #[error("Missing format argument #{0}")]
+    FormatMissingArg(usize),
+
+    #[error("Invalid base64 encoding")]
+    InvalidBase64,
+
+    #[error("Unknown bech32 variant \"{0}\", expected \"bech32\" or \"bech32m\"")]
+    InvalidBech32Variant(String),
+
     // Generic error raised from user-land Minsc code
     #[error("Exception: {0}")]
     ScriptException(String),
@@ -280,6 +403,9 @@ pub enum RuntimeError {
     #[error("Witness program error: {0}")]
     WitnessProgError(#[from] witness_program::Error),
 
+    #[error("Witness version error: {0}")]
+    WitnessVersionError(#[from] witness_version::TryFromError),
+
     #[error("Push bytes error: {0}")]
     PushBytesError(#[from] script::PushBytesError),
 
@@ -292,11 +418,36 @@ pub enum RuntimeError {
     #[error("Invalid address: {0}")]
     AddressError(#[from] bitcoin::address::ParseError),
 
+    #[error("Invalid address: {0}")]
+    AddressNetworkError(#[from] bitcoin::address::Error),
+
     #[error("Encoding error: {0}")]
     EncodeError(#[from] bitcoin::consensus::encode::Error),
 
     #[error("Invalid Script: {0}")]
     InvalidScript(#[from] bitcoin::script::Error),
+
+    #[error("Invalid UTF-8: {0}")]
+    Utf8Error(#[from] std::string::FromUtf8Error),
+
+    #[error("Invalid hex: {0}")]
+    HexDecodeError(#[from] hex::HexToBytesError),
+
+    #[error("Invalid base58: {0}")]
+    Base58Error(#[from] bitcoin::base58::Error),
+
+    #[error("Invalid bech32: {0}")]
+    Bech32DecodeError(#[from] bitcoin::bech32::DecodeError),
+
+    #[error("Invalid bech32 hrp: {0}")]
+    Bech32HrpError(#[from] bitcoin::bech32::primitives::hrp::Error),
+}
+
+impl RuntimeError {
+    /// Resource exhaustion errors are propagated as-is, without accumulating context for every nested call
+    pub fn is_resource_exhaustion(&self) -> bool {
+        matches!(self, Self::LimitExceeded(_) | Self::StackDepthExceeded)
+    }
 }
 
 impl From<TranslateErr<RuntimeError>> for RuntimeError {
@@ -328,6 +479,12 @@ pub enum ParseError {
     #[error("Absolute by-blocktime timelock out of range, supported up to 2106")]
     InvalidDateTimeOutOfRange,
 
+    #[error("Positional arguments cannot follow named arguments")]
+    PositionalAfterNamedArg,
+
+    #[error("Invalid destructuring pattern, expected variables, arrays or tagged lists")]
+    InvalidPattern,
+
     #[error("{0}")]
     LalrError(String),
 }
@@ -22,7 +22,7 @@ use std::str::FromStr;
 
 pub use error::{Error, ParseError, RuntimeError};
 pub use parser::{ast, Expr, Ident, Library, Stmt, Stmts};
-pub use runtime::{Evaluate, Execute, Number, Scope, ScopeRef, Value};
+pub use runtime::{Evaluate, Execute, Limits, Number, Scope, ScopeRef, Value};
 pub use util::PrettyDisplay;
 
 use miniscript::{descriptor, policy};
@@ -40,6 +40,14 @@ where
     Ok(expr.try_into()?.eval(&Scope::root())?)
 }
 
+/// Evaluate the given expression in the default global scope, enforcing the execution limits
+pub fn eval_limited<T: TryInto<Expr>>(expr: T, limits: Limits) -> Result<Value, Error>
+where
+    Error: From<T::Error>,
+{
+    runtime::limits::with_limits(limits, || eval(expr))
+}
+
 /// Parse program code into an Expr AST
 pub fn parse(s: &str) -> Result<Expr, ParseError> {
     Expr::from_str(s)
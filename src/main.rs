@@ -1,10 +1,16 @@
-use minsc::{eval, parse, Error, PrettyDisplay};
+use miniscript::bitcoin::{hex::FromHex, ScriptBuf};
+use minsc::stdlib::script_decompiler::decompile_script;
+use minsc::{eval, parse, Error, ParseError, PrettyDisplay, Scope};
 use std::{env, fs, io};
 
 fn main_() -> Result<(), Error> {
     let mut args = env::args();
     let input = args.nth(1).unwrap_or_else(|| "-".into());
 
+    if input == "disasm" {
+        return disasm(args);
+    }
+
     let arg = args.next();
     let print_ast = arg == Some("--ast".into());
     let debug = arg == Some("--debug".into());
@@ -30,6 +36,34 @@ fn main_() -> Result<(), Error> {
     Ok(())
 }
 
+/// Decompile hex-encoded Script bytes into Minsc code, with `--lift` to lift Miniscript into a policy
+///
+/// Usage: minsc disasm [<hex>|-] [--lift]
+fn disasm(args: env::Args) -> Result<(), Error> {
+    let (mut hex, mut lift) = (None, false);
+    for arg in args {
+        match &*arg {
+            "--lift" => lift = true,
+            _ => hex = Some(arg),
+        }
+    }
+    let hex = match hex.as_deref() {
+        None | Some("-") => {
+            let mut hex = String::new();
+            io::Read::read_to_string(&mut io::stdin(), &mut hex)?;
+            hex
+        }
+        Some(hex) => hex.to_string(),
+    };
+    let bytes = Vec::<u8>::from_hex(hex.trim()).map_err(ParseError::from)?;
+    let script = ScriptBuf::from(bytes);
+    println!(
+        "{}",
+        decompile_script(&script, lift, Some(0), &Scope::root())?
+    );
+    Ok(())
+}
+
 fn main() {
     // Print errors using Display rather than Debug
     if let Err(e) = main_() {
@@ -1,6 +1,8 @@
 use bitcoin::address::{self, Address};
 use miniscript::{bitcoin, DescriptorPublicKey};
 
+use std::convert::TryInto;
+
 use crate::parser::ParseError;
 
 /// Expressions have no side-effects and produce a value
@@ -60,6 +62,8 @@ impl_from_variant!(Block, Expr);
 pub struct Call {
     pub func: Box<Expr>,
     pub args: Vec<Expr>,
+    /// Arguments passed by their parameter name, as `$name = value`
+    pub named_args: Vec<(Ident, Expr)>,
 }
 impl_from_variant!(Call, Expr);
 
@@ -138,7 +142,7 @@ impl_from_variant!(ScriptFrag, Expr);
 /// An anonymous function expression
 #[derive(Debug, Clone)]
 pub struct FnExpr {
-    pub signature: Vec<Ident>,
+    pub signature: Signature,
     pub body: Box<Expr>,
     pub dynamic_scoping: bool,
 }
@@ -206,12 +210,26 @@ impl_from_variant!(BtcAmount, Expr);
 #[derive(Debug, Clone)]
 pub struct FnDef {
     pub ident: Ident,
-    pub signature: Vec<Ident>,
+    pub signature: Signature,
     pub body: Expr,
     pub dynamic_scoping: bool,
 }
 impl_from_variant!(FnDef, Stmt);
 
+/// Function parameters, with an optional `...$rest` parameter collecting the remaining arguments
+#[derive(Debug, Clone, Default)]
+pub struct Signature {
+    pub params: Vec<Param>,
+    pub rest: Option<Ident>,
+}
+
+/// A function parameter with an optional default value, evaluated when no argument is given for it
+#[derive(Debug, Clone)]
+pub struct Param {
+    pub pattern: Pattern,
+    pub default: Option<Expr>,
+}
+
 /// An assignment statement
 #[derive(Debug, Clone)]
 pub struct Assign(pub Vec<Assignment>);
@@ -219,10 +237,22 @@ impl_from_variant!(Assign, Stmt);
 
 #[derive(Debug, Clone)]
 pub struct Assignment {
-    pub lhs: Ident,
+    pub lhs: Pattern,
     pub rhs: Expr,
 }
 
+/// A destructuring pattern, for assignments and function parameters
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    /// Bind the value to a variable, or discard it if the identifier is `_`
+    Ident(Ident),
+    /// Destructure an array, optionally collecting the remaining elements into a `...$rest` variable
+    Array(Vec<Pattern>, Option<Ident>),
+    /// Destructure a tagged list by its tag names
+    Tagged(Vec<(String, Pattern)>),
+}
+impl_from_variant!(Ident, Pattern);
+
 /// A call statement whose return value is discarded
 #[derive(Debug, Clone)]
 pub struct CallStmt(pub Call);
@@ -247,9 +277,36 @@ impl Expr {
         Ok(Expr::Bytes(Vec::from_hex(s)?))
     }
 
+    /// 0x-less hex strings can also be bech32 addresses that happen to only use hex characters,
+    /// which are told apart by their checksum
+    pub fn bytes_or_address_from_hex(s: &str) -> Result<Expr, ParseError> {
+        match s.parse() {
+            Ok(address) => Ok(Expr::Address(address)),
+            Err(_) => Self::bytes_from_hex(s),
+        }
+    }
+
+    /// Bech32 addresses shorter than 40 characters are lexed as identifiers, and are told apart by their checksum
+    pub fn ident_or_address(ident: Ident) -> Expr {
+        let prefix = ident.0.get(..5).unwrap_or(&ident.0).to_lowercase();
+        if ["bc1", "tb1", "bcrt1"]
+            .iter()
+            .any(|hrp| prefix.starts_with(hrp))
+        {
+            if let Ok(address) = ident.0.parse() {
+                return Expr::Address(address);
+            }
+        }
+        Expr::Ident(ident)
+    }
+
     /// Expand escape characters in string literals (\", \\, \n, \r and \t)
     pub fn string_from_escaped_str(s: &str) -> Expr {
-        Expr::String(if !s.contains('\\') {
+        Expr::String(Self::unescape_str(s))
+    }
+
+    pub fn unescape_str(s: &str) -> String {
+        if !s.contains('\\') {
             s.to_owned()
         } else {
             let mut iter = s.chars();
@@ -268,7 +325,7 @@ impl Expr {
                 s_new.push(ch);
             }
             s_new
-        })
+        }
     }
 
     pub fn as_ident(&self) -> Option<&Ident> {
@@ -278,3 +335,85 @@ impl Expr {
         }
     }
 }
+
+// Assignments are parsed with an expression on the LHS that is converted into a Pattern,
+// to avoid an ambiguity between array patterns and array expressions in the grammar.
+impl std::convert::TryFrom<Expr> for Pattern {
+    type Error = ParseError;
+    fn try_from(expr: Expr) -> Result<Self, ParseError> {
+        let is_tag = |el: &Expr| matches!(el, Expr::Infix(Infix { op: InfixOp::Colon, lhs, .. }) if matches!(**lhs, Expr::String(_)));
+        Ok(match expr {
+            Expr::Ident(ident) => Pattern::Ident(ident),
+            // ["tag1": $a, "tag2": $b]
+            Expr::Array(Array(elements)) if !elements.is_empty() && elements.iter().all(is_tag) => {
+                Pattern::Tagged(
+                    elements
+                        .into_iter()
+                        .map(|el| match el {
+                            Expr::Infix(Infix { lhs, rhs, .. }) => match *lhs {
+                                Expr::String(tag) => Ok((tag, (*rhs).try_into()?)),
+                                _ => unreachable!("checked by is_tag"),
+                            },
+                            _ => unreachable!("checked by is_tag"),
+                        })
+                        .collect::<Result<_, ParseError>>()?,
+                )
+            }
+            // [$a, $b]
+            Expr::Array(Array(elements)) => Pattern::Array(
+                elements
+                    .into_iter()
+                    .map(TryInto::try_into)
+                    .collect::<Result<_, _>>()?,
+                None,
+            ),
+            // $a:$b, as an element within an array pattern
+            Expr::Infix(Infix {
+                op: InfixOp::Colon,
+                lhs,
+                rhs,
+            }) => Pattern::Array(vec![(*lhs).try_into()?, (*rhs).try_into()?], None),
+            _ => return Err(ParseError::InvalidPattern),
+        })
+    }
+}
+
+impl std::fmt::Display for Signature {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for (i, param) in self.params.iter().enumerate() {
+            write!(f, "{}{}", iif!(i > 0, ", ", ""), param.pattern)?;
+            if param.default.is_some() {
+                write!(f, " (optional)")?;
+            }
+        }
+        if let Some(rest) = &self.rest {
+            write!(f, "{}...{}", iif!(self.params.is_empty(), "", ", "), rest)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for Pattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Pattern::Ident(ident) => write!(f, "{}", ident),
+            Pattern::Array(elements, rest) => {
+                write!(f, "[")?;
+                for (i, element) in elements.iter().enumerate() {
+                    write!(f, "{}{}", iif!(i > 0, ", ", ""), element)?;
+                }
+                if let Some(rest) = rest {
+                    write!(f, "{}...{}", iif!(elements.is_empty(), "", ", "), rest)?;
+                }
+                write!(f, "]")
+            }
+            Pattern::Tagged(tags) => {
+                write!(f, "[")?;
+                for (i, (tag, pattern)) in tags.iter().enumerate() {
+                    write!(f, "{}{:?}: {}", iif!(i > 0, ", ", ""), tag, pattern)?;
+                }
+                write!(f, "]")
+            }
+        }
+    }
+}
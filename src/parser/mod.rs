@@ -46,10 +46,35 @@ pub fn prepend<T>(mut list: Vec<T>, val: T) -> Vec<T> {
     list
 }
 
+/// A function call argument, passed by position or by name
+pub enum CallArg {
+    Positional(Expr),
+    Named(ast::Ident, Expr),
+}
+
+/// Positional and named call arguments
+pub type CallArgs = (Vec<Expr>, Vec<(ast::Ident, Expr)>);
+
+/// Split call arguments into the positional and named ones. Named arguments must come last.
+pub fn split_args(args: Vec<CallArg>) -> Result<CallArgs, ParseError> {
+    let (mut positional, mut named) = (vec![], vec![]);
+    for arg in args {
+        match arg {
+            CallArg::Positional(_) if !named.is_empty() => {
+                return Err(ParseError::PositionalAfterNamedArg)
+            }
+            CallArg::Positional(expr) => positional.push(expr),
+            CallArg::Named(ident, expr) => named.push((ident, expr)),
+        }
+    }
+    Ok((positional, named))
+}
+
 pub fn call(func: &str, args: Vec<Expr>) -> Expr {
     ast::Call {
         func: Expr::Ident(func.to_string().into()).into(),
         args,
+        named_args: vec![],
     }
     .into()
 }
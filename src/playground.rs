@@ -6,8 +6,11 @@ use wasm_bindgen::prelude::*;
 use miniscript::bitcoin::{Address, Network, ScriptBuf};
 use miniscript::{Descriptor, MiniscriptKey};
 
+use crate::runtime::limits::with_limits;
 use crate::util::DescriptorExt;
-use crate::{parse, Error, Evaluate, Execute, Library, PrettyDisplay, Scope, ScopeRef, Value};
+use crate::{
+    parse, Error, Evaluate, Execute, Library, Limits, PrettyDisplay, Scope, ScopeRef, Value,
+};
 
 #[derive(Serialize)]
 pub struct PlaygroundResult {
@@ -89,7 +92,17 @@ pub fn run_playground(code: &str, network: &str) -> std::result::Result<JsValue,
 }
 
 fn run(code: &str) -> Result<Value, Error> {
-    DEMO_SCOPE.with(|root| Ok(parse(code)?.eval(root)?))
+    // No wall-clock limit, which is not available on wasm32
+    let limits = Limits {
+        max_call_depth: Some(1_000),
+        max_steps: Some(10_000_000),
+        max_array_len: Some(1_000_000),
+        max_bytes_len: Some(10_000_000),
+        max_script_len: Some(10_000_000),
+        max_duration: None,
+    };
+    let expr = parse(code)?;
+    with_limits(limits, || DEMO_SCOPE.with(|root| Ok(expr.eval(root)?)))
 }
 
 fn script_asm(script: &ScriptBuf) -> String {
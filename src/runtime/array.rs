@@ -122,7 +122,7 @@ impl<T: FromValue> TryFrom<Array> for Vec<T> {
 }
 
 // Generic conversion from Array into tuples of any convertible type
-// Currently supports 1-tuples, 2-tuples and 3-tuples
+// Currently supports 1-tuples, 2-tuples, 3-tuples and 4-tuples
 impl<A: FromValue> TryFrom<Array> for (A,) {
     type Error = Error;
     fn try_from(arr: Array) -> Result<(A,)> {
@@ -147,6 +147,22 @@ impl<A: FromValue, B: FromValue, C: FromValue> TryFrom<Array> for (A, B, C) {
         Ok((iter.next_into()?, iter.next_into()?, iter.next_into()?))
     }
 }
+impl<A: FromValue, B: FromValue, C: FromValue, D: FromValue> TryFrom<Array> for (A, B, C, D) {
+    type Error = Error;
+    fn try_from(arr: Array) -> Result<(A, B, C, D)> {
+        let min_len = A::IS_REQUIRED as usize
+            + B::IS_REQUIRED as usize
+            + C::IS_REQUIRED as usize
+            + D::IS_REQUIRED as usize;
+        let mut iter = arr.check_varlen(min_len, 4)?.into_iter();
+        Ok((
+            iter.next_into()?,
+            iter.next_into()?,
+            iter.next_into()?,
+            iter.next_into()?,
+        ))
+    }
+}
 
 impl fmt::Display for Array {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -179,7 +195,7 @@ fn should_use_colon_syntax(elements: &Vec<Value>) -> bool {
     if elements.len() == 2 {
         match (&elements[0], &elements[1]) {
             // Never if the LHS is one of these (not typically used with colon tuple construction syntax)
-            (Array(_) | Function(_) | Transaction(_), _) => false,
+            (Array(_) | Function(_) | Transaction(_) | ElementsTransaction(_), _) => false,
 
             // If the LHS is a String or Script, only if they're short (used as tagged list keys and predicates)
             (String(lhs), _) => lhs.len() < 43,
@@ -188,7 +204,8 @@ fn should_use_colon_syntax(elements: &Vec<Value>) -> bool {
             // Otherwise, only if the LHS and RHS are of different types
             (
                 lhs @ (Bool(_) | Number(_) | Bytes(_) | Address(_) | PubKey(_) | Policy(_)
-                | Descriptor(_) | TapInfo(_) | WithProb(..) | Network(_) | Symbol(_)),
+                | Descriptor(_) | TapInfo(_) | WithProb(..) | TapLeaf(..) | Miniscript(_)
+                | Network(_) | Symbol(_)),
                 rhs,
             ) => mem::discriminant(lhs) != mem::discriminant(rhs),
         }
@@ -1,7 +1,11 @@
+use std::borrow::Cow;
 use std::fmt;
 
 use crate::parser::{ast, Expr, Ident};
-use crate::runtime::{Array, Error, Evaluate, Result, ScopeRef, Value};
+use crate::runtime::{
+    eval_exprs, eval_named_args, limits, Array, Error, Evaluate, Execute, Mutable, Result,
+    ScopeRef, Value,
+};
 use crate::stdlib::fns::throw as stdlib_throw;
 
 #[derive(Debug, Clone)]
@@ -14,7 +18,7 @@ pub enum Function {
 #[derive(Clone)]
 pub struct UserFunction {
     pub ident: Option<Ident>,
-    pub signature: Vec<Ident>,
+    pub signature: ast::Signature,
     pub body: Expr,
     pub scope: Option<ScopeRef>,
 }
@@ -29,6 +33,9 @@ pub struct NativeFunction {
 
 pub type NativeFunctionPt = fn(Array, &ScopeRef) -> Result<Value>;
 
+/// Arguments passed by their parameter name
+pub type NamedArgs = Vec<(Ident, Value)>;
+
 impl_from_variant!(NativeFunction, Function, Native);
 
 pub trait Call {
@@ -37,43 +44,222 @@ pub trait Call {
 
 impl Call for Function {
     fn call(&self, args: Vec<Value>, caller_scope: &ScopeRef) -> Result<Value> {
+        self.call_named(args, vec![], caller_scope)
+    }
+}
+
+impl Function {
+    /// Call the function with positional and named arguments
+    pub fn call_named(
+        &self,
+        args: Vec<Value>,
+        named_args: NamedArgs,
+        caller_scope: &ScopeRef,
+    ) -> Result<Value> {
+        let _guard = limits::enter_call()?;
         match self {
-            Function::User(f) => f.call(args, caller_scope),
-            Function::Native(f) => f.call(args, caller_scope), // wraps with CallError context internally
+            Function::User(f) => f.call_named(args, named_args, caller_scope),
+            Function::Native(f) => {
+                ensure!(
+                    named_args.is_empty(),
+                    Error::CallError(f.ident.clone(), Error::NamedArgsUnsupported.into())
+                );
+                f.call(args, caller_scope) // wraps with CallError context internally
+            }
         }
     }
 }
 
 impl Call for UserFunction {
     fn call(&self, args: Vec<Value>, caller_scope: &ScopeRef) -> Result<Value> {
-        let _call = || {
-            ensure!(
-                self.signature.len() == args.len(),
-                Error::InvalidArgumentsError(
-                    Error::InvalidLength(args.len(), self.signature.len()).into(),
+        self.call_named(args, vec![], caller_scope)
+    }
+}
+
+impl UserFunction {
+    pub fn call_named(
+        &self,
+        args: Vec<Value>,
+        named_args: NamedArgs,
+        caller_scope: &ScopeRef,
+    ) -> Result<Value> {
+        let mut func = Cow::Borrowed(self);
+        let (mut args, mut named_args) = (args, named_args);
+        let mut caller_scope = caller_scope.make_ref();
+        // Calls to user functions in tail position are returned unevaluated by eval_body() and
+        // evaluated here in a loop, so that tail recursion does not grow the native stack.
+        loop {
+            let wrap_err = |e: Error, func: &UserFunction| match e {
+                e if e.is_resource_exhaustion() => e,
+                e => Error::CallError(func.ident.clone(), e.into()),
+            };
+            match func.eval_body(args, named_args, &caller_scope) {
+                Ok(TailEval::Value(value)) => return Ok(value),
+                Ok(TailEval::Call(
+                    Function::User(mut next_func),
+                    next_args,
+                    caller_ident,
+                    scope,
+                )) => {
+                    if next_func.ident.is_none() {
+                        next_func.ident = caller_ident;
+                    }
+                    func = Cow::Owned(next_func);
+                    (args, named_args) = next_args;
+                    caller_scope = scope;
+                }
+                Ok(TailEval::Call(native_func, (args, named_args), caller_ident, scope)) => {
+                    return call_as(
+                        &native_func,
+                        args,
+                        named_args,
+                        &scope,
+                        caller_ident.as_ref(),
+                    )
+                    .map_err(|e| wrap_err(e, &func));
+                }
+                Err(e) => return Err(wrap_err(e, &func)),
+            }
+        }
+    }
+}
+
+impl UserFunction {
+    fn eval_body(
+        &self,
+        args: Vec<Value>,
+        named_args: NamedArgs,
+        caller_scope: &ScopeRef,
+    ) -> Result<TailEval> {
+        // For lexically-scoped functions, create a child scope of the scope where the function was defined.
+        // For dynamically-scoped function, create a child of the caller scope.
+        let scope = self.scope.as_ref().unwrap_or(caller_scope).child();
+        self.bind_args(args, named_args, &scope)?;
+        eval_tail(&self.body, &scope.into_readonly())
+    }
+
+    /// Bind the arguments to the function parameters, filling in default values for missing ones
+    fn bind_args(
+        &self,
+        args: Vec<Value>,
+        named_args: NamedArgs,
+        scope: &ScopeRef<Mutable>,
+    ) -> Result<()> {
+        let invalid_args = |e: Error| Error::InvalidArgumentsError(e.into());
+        let params = &self.signature.params;
+        ensure!(
+            args.len() <= params.len() || self.signature.rest.is_some(),
+            invalid_args(Error::TooManyArgs(args.len(), params.len()))
+        );
+        let mut args = args.into_iter();
+        let mut param_values: Vec<Option<Value>> =
+            args.by_ref().take(params.len()).map(Some).collect();
+        param_values.resize(params.len(), None);
+        let rest_values: Vec<Value> = args.collect();
+
+        for (name, value) in named_args {
+            let index = params
+                .iter()
+                .position(
+                    |param| matches!(&param.pattern, ast::Pattern::Ident(ident) if *ident == name),
                 )
+                .ok_or_else(|| invalid_args(Error::UnknownNamedArg(name.clone())))?;
+            ensure!(
+                param_values[index].is_none(),
+                invalid_args(Error::DuplicatedArg(name))
             );
-            // For lexically-scoped functions, create a child scope of the scope where the function was defined.
-            // For dynamically-scoped function, create a child of the caller scope.
-            let scope = self.scope.as_ref().unwrap_or(caller_scope).child();
-            {
-                let mut scope = scope.borrow_mut();
-                for (index, value) in args.into_iter().enumerate() {
-                    let ident = self.signature.get(index).unwrap();
-                    scope.set(ident.clone(), value)?;
-                }
+            param_values[index] = Some(value);
+        }
+
+        for (index, (param, value)) in params.iter().zip(param_values).enumerate() {
+            // Default values are evaluated in the function scope, and may refer to the preceding parameters
+            let value = match (value, &param.default) {
+                (Some(value), _) => value,
+                (None, Some(default)) => default
+                    .eval(&scope.as_readonly())
+                    .map_err(|e| Error::NthContext(index + 1, e.into()))?,
+                (None, None) => bail!(invalid_args(Error::MissingArg(param.pattern.to_string()))),
+            };
+            param
+                .pattern
+                .bind(value, &mut scope.borrow_mut())
+                .map_err(|e| Error::NthContext(index + 1, e.into()))?;
+        }
+        if let Some(rest) = &self.signature.rest {
+            scope.borrow_mut().set(rest.clone(), rest_values)?;
+        }
+        Ok(())
+    }
+}
+
+/// The result of evaluating an expression, with the call in tail position (if any) left unevaluated
+enum TailEval {
+    Value(Value),
+    /// The function to call with its positional and named arguments, the identifier used to call it and the caller scope
+    Call(Function, (Vec<Value>, NamedArgs), Option<Ident>, ScopeRef),
+}
+
+/// Evaluate the expression, following if-else branches and block return values to find calls in tail position
+fn eval_tail(expr: &Expr, scope: &ScopeRef) -> Result<TailEval> {
+    Ok(match expr {
+        Expr::Call(call) => {
+            limits::step()?;
+            let func = call.func.eval(scope)?.into_fn()?;
+            let args = eval_exprs(scope, &call.args)?;
+            let named_args = eval_named_args(scope, &call.named_args)?;
+            let caller_ident = call.func.as_ident().cloned();
+            TailEval::Call(func, (args, named_args), caller_ident, scope.make_ref())
+        }
+        Expr::If(if_expr) => {
+            limits::step()?;
+            if if_expr.condition.eval(scope)?.into_bool()? {
+                eval_tail(&if_expr.then_val, scope)?
+            } else {
+                eval_tail(&if_expr.else_val, scope)?
             }
-            self.body.eval(&scope.into_readonly())
-        };
-        _call().map_err(|e| Error::CallError(self.ident.clone(), e.into()))
+        }
+        Expr::Block(ast::Block {
+            stmts,
+            return_value: Some(return_value),
+        }) => {
+            limits::step()?;
+            let scope = scope.child();
+            for stmt in stmts {
+                stmt.exec(&scope)?;
+            }
+            eval_tail(return_value, &scope.into_readonly())?
+        }
+        other => TailEval::Value(other.eval(scope)?),
+    })
+}
+
+/// Call the function, using the identifier it was called by to name anonymous functions in errors
+pub fn call_as(
+    func: &Function,
+    args: Vec<Value>,
+    named_args: NamedArgs,
+    caller_scope: &ScopeRef,
+    caller_ident: Option<&Ident>,
+) -> Result<Value> {
+    match (
+        func.call_named(args, named_args, caller_scope),
+        caller_ident,
+    ) {
+        (Err(Error::CallError(None, e)), Some(caller_ident)) => {
+            // If the function originating the error is unnamed but the caller called it using an identifier,
+            // use the caller name for the CallError message. A name may not be available on the caller side
+            // either if the function was not accessed through a simple identifier, for example `([a].0)()`
+            Err(Error::CallError(Some(caller_ident.clone()), e))
+        }
+        (other, _) => other,
     }
 }
 
 impl Call for NativeFunction {
     fn call(&self, args: Vec<Value>, caller_scope: &ScopeRef) -> Result<Value> {
         (self.pt)(Array(args), caller_scope).map_err(|e| {
-            if self.pt == stdlib_throw {
-                e // Don't include the `throw()` function in the CallError stack context.
+            if self.pt == stdlib_throw || e.is_resource_exhaustion() {
+                e // Don't include the `throw()` function or resource errors in the CallError stack context.
             } else {
                 Error::CallError(self.ident.clone(), e.into())
             }
@@ -170,13 +356,7 @@ impl fmt::Display for UserFunction {
             write!(f, "{}", ident)?;
         }
         write!(f, "(")?;
-        for (i, arg_name) in self.signature.iter().enumerate() {
-            if i > 0 {
-                write!(f, ", ")?;
-            }
-            write!(f, "{}", arg_name)?;
-        }
-        write!(f, ")")
+        write!(f, "{})", self.signature)
     }
 }
 impl fmt::Display for NativeFunction {
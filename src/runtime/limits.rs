@@ -0,0 +1,225 @@
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::runtime::{Error, Result};
+
+/// Resource limits for evaluating untrusted Minsc code. Unset limits are not enforced.
+#[derive(Debug, Clone, Default)]
+pub struct Limits {
+    /// Maximum nesting depth of function calls
+    pub max_call_depth: Option<usize>,
+    /// Maximum total number of expressions evaluated
+    pub max_steps: Option<usize>,
+    /// Maximum number of elements in constructed arrays
+    pub max_array_len: Option<usize>,
+    /// Maximum length of constructed Bytes and Strings, in bytes
+    pub max_bytes_len: Option<usize>,
+    /// Maximum length of constructed Scripts, in bytes
+    pub max_script_len: Option<usize>,
+    /// Maximum wall-clock evaluation time. Evaluation fails with an error if set on wasm32 targets
+    /// without WASI, which have no clock.
+    pub max_duration: Option<Duration>,
+}
+
+/// The limit that was exceeded, with its configured value
+#[derive(Debug, Clone, PartialEq)]
+pub enum Limit {
+    CallDepth(usize),
+    Steps(usize),
+    ArrayLen(usize),
+    BytesLen(usize),
+    ScriptLen(usize),
+    Duration(Duration),
+}
+
+struct State {
+    limits: Limits,
+    call_depth: usize,
+    steps: usize,
+    started: Option<Instant>,
+}
+
+/// Native stack space kept in reserve below the deepest allowed function call, for the evaluation
+/// happening between calls (and for the code handling the error)
+const STACK_RESERVE: usize = 256 * 1024;
+
+/// The native stack space nested calls may use when the thread's stack bounds cannot be determined,
+/// measured from the first call. Kept under the default stack size of wasm (1 MiB).
+const FALLBACK_STACK_USAGE: usize = 512 * 1024;
+
+thread_local! {
+    static STATE: RefCell<Option<State>> = const { RefCell::new(None) };
+    // The lowest stack address function calls may be entered at, determined on the first call
+    static STACK_LIMIT: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Run the closure with the limits enforced for all evaluation within it (on the current thread)
+pub fn with_limits<T>(limits: Limits, f: impl FnOnce() -> T) -> T {
+    let state = State {
+        started: limits.max_duration.and_then(|_| now()),
+        limits,
+        call_depth: 0,
+        steps: 0,
+    };
+    let _guard = StateGuard(STATE.with(|s| s.replace(Some(state))));
+    f()
+}
+
+/// Restores the previous state when dropped, including when unwinding from a panic
+struct StateGuard(Option<State>);
+
+impl Drop for StateGuard {
+    fn drop(&mut self) {
+        let prev_state = self.0.take();
+        STATE.with(|s| s.replace(prev_state));
+    }
+}
+
+/// The current time, if available. `Instant::now()` panics on wasm32 without WASI.
+fn now() -> Option<Instant> {
+    #[cfg(all(target_arch = "wasm32", not(target_os = "wasi")))]
+    return None;
+    #[cfg(not(all(target_arch = "wasm32", not(target_os = "wasi"))))]
+    Some(Instant::now())
+}
+
+/// Account for a single evaluation step, checking the step count and elapsed time
+pub fn step() -> Result<()> {
+    with_state(|state| {
+        state.steps += 1;
+        check(state.limits.max_steps, state.steps, Limit::Steps)?;
+        if let Some(max_duration) = state.limits.max_duration {
+            let started = state
+                .started
+                .ok_or(Error::LimitUnsupported(Limit::Duration(max_duration)))?;
+            ensure!(
+                started.elapsed() <= max_duration,
+                Error::LimitExceeded(Limit::Duration(max_duration))
+            );
+        }
+        Ok(())
+    })
+}
+
+/// Enter a function call, returning a guard that exits it when dropped
+///
+/// Checks the call depth limit (if set) and the remaining native stack space, which is always
+/// checked to turn deep non-tail recursion into an error rather than a stack overflow.
+pub fn enter_call() -> Result<CallGuard> {
+    // The address of a local variable approximates the current stack pointer
+    let marker = 0u8;
+    let stack_pointer = &marker as *const u8 as usize;
+    ensure!(
+        stack_pointer > stack_limit(stack_pointer),
+        Error::StackDepthExceeded
+    );
+
+    with_state(|state| {
+        check(
+            state.limits.max_call_depth,
+            state.call_depth + 1,
+            Limit::CallDepth,
+        )?;
+        state.call_depth += 1;
+        Ok(())
+    })?;
+    Ok(CallGuard)
+}
+
+pub struct CallGuard;
+
+impl Drop for CallGuard {
+    fn drop(&mut self) {
+        with_state(|state| {
+            state.call_depth = state.call_depth.saturating_sub(1);
+            Ok(())
+        })
+        .ok();
+    }
+}
+
+fn stack_limit(stack_pointer: usize) -> usize {
+    STACK_LIMIT.with(|limit| {
+        if limit.get() == 0 {
+            limit.set(match stack_bounds() {
+                Some((low, size)) => low + STACK_RESERVE.min(size / 4),
+                None => stack_pointer.saturating_sub(FALLBACK_STACK_USAGE),
+            });
+        }
+        limit.get()
+    })
+}
+
+/// The current thread's lowest stack address and stack size
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn stack_bounds() -> Option<(usize, usize)> {
+    use std::mem::MaybeUninit;
+    unsafe {
+        let mut attr = MaybeUninit::<libc::pthread_attr_t>::uninit();
+        if libc::pthread_getattr_np(libc::pthread_self(), attr.as_mut_ptr()) != 0 {
+            return None;
+        }
+        let (mut addr, mut size) = (std::ptr::null_mut(), 0);
+        let res = libc::pthread_attr_getstack(attr.as_ptr(), &mut addr, &mut size);
+        libc::pthread_attr_destroy(attr.as_mut_ptr());
+        (res == 0).then_some((addr as usize, size))
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn stack_bounds() -> Option<(usize, usize)> {
+    unsafe {
+        let thread = libc::pthread_self();
+        let top = libc::pthread_get_stackaddr_np(thread) as usize;
+        let size = libc::pthread_get_stacksize_np(thread);
+        Some((top - size, size))
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "macos")))]
+fn stack_bounds() -> Option<(usize, usize)> {
+    None
+}
+
+/// Check that an array of the given length may be constructed
+pub fn check_array_len(len: usize) -> Result<()> {
+    with_state(|state| check(state.limits.max_array_len, len, Limit::ArrayLen))
+}
+
+/// Check that Bytes (or a String) of the given length may be constructed
+pub fn check_bytes_len(len: usize) -> Result<()> {
+    with_state(|state| check(state.limits.max_bytes_len, len, Limit::BytesLen))
+}
+
+/// Check that a Script of the given length may be constructed
+pub fn check_script_len(len: usize) -> Result<()> {
+    with_state(|state| check(state.limits.max_script_len, len, Limit::ScriptLen))
+}
+
+fn with_state(f: impl FnOnce(&mut State) -> Result<()>) -> Result<()> {
+    STATE.with(|s| match &mut *s.borrow_mut() {
+        Some(state) => f(state),
+        None => Ok(()),
+    })
+}
+
+fn check(max: Option<usize>, actual: usize, limit: fn(usize) -> Limit) -> Result<()> {
+    match max {
+        Some(max) if actual > max => Err(Error::LimitExceeded(limit(max))),
+        _ => Ok(()),
+    }
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Limit::CallDepth(max) => write!(f, "maximum call depth of {}", max),
+            Limit::Steps(max) => write!(f, "maximum of {} evaluation steps", max),
+            Limit::ArrayLen(max) => write!(f, "maximum array length of {}", max),
+            Limit::BytesLen(max) => write!(f, "maximum bytes length of {}", max),
+            Limit::ScriptLen(max) => write!(f, "maximum script length of {}", max),
+            Limit::Duration(max) => write!(f, "maximum evaluation time of {:?}", max),
+        }
+    }
+}
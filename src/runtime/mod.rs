@@ -9,11 +9,13 @@ pub type Result<T> = std::result::Result<T, Error>;
 
 pub mod array;
 pub mod function;
+pub mod limits;
 pub mod scope;
 pub mod value;
 
 pub use array::Array;
 pub use function::{Call, Function};
+pub use limits::Limits;
 pub use scope::{Mutable, Scope, ScopeRef};
 pub use value::{FromValue, Number, Number::*, Symbol, Value};
 
@@ -36,12 +38,63 @@ impl Execute for ast::Assign {
         let readonly = scope.as_readonly();
         for assignment in &self.0 {
             let value = assignment.rhs.eval(&readonly)?;
-            scope.borrow_mut().set(assignment.lhs.clone(), value)?;
+            assignment.lhs.bind(value, &mut scope.borrow_mut())?;
         }
         Ok(())
     }
 }
 
+impl ast::Pattern {
+    /// Bind the value to the pattern variables, destructuring it as necessary
+    pub fn bind(&self, value: Value, scope: &mut Scope) -> Result<()> {
+        match self {
+            ast::Pattern::Ident(ident) if ident.0 == "_" => Ok(()),
+            ast::Pattern::Ident(ident) => scope.set(ident.clone(), value),
+            ast::Pattern::Array(patterns, rest) => {
+                let mut elements = match value {
+                    Value::Array(array) => array.into_inner(),
+                    other => bail!(Error::PatternNotArray(other.into())),
+                };
+                if rest.is_some() {
+                    ensure!(
+                        elements.len() >= patterns.len(),
+                        Error::PatternMinLength(elements.len(), patterns.len())
+                    );
+                } else {
+                    ensure!(
+                        elements.len() == patterns.len(),
+                        Error::PatternLength(elements.len(), patterns.len())
+                    );
+                }
+                let rest_elements = elements.split_off(patterns.len());
+                for (index, (pattern, element)) in patterns.iter().zip(elements).enumerate() {
+                    pattern
+                        .bind(element, scope)
+                        .map_err(|e| Error::NthContext(index + 1, e.into()))?;
+                }
+                if let Some(rest) = rest {
+                    ast::Pattern::Ident(rest.clone()).bind(rest_elements.into(), scope)?;
+                }
+                Ok(())
+            }
+            ast::Pattern::Tagged(tag_patterns) => {
+                let mut tags = value.into_tags()?;
+                for (tag, pattern) in tag_patterns {
+                    let tag_index = tags
+                        .iter()
+                        .position(|(val_tag, _)| val_tag == tag)
+                        .ok_or_else(|| Error::PatternTagNotFound(tag.clone()))?;
+                    let (_, tag_value) = tags.remove(tag_index);
+                    pattern
+                        .bind(tag_value, scope)
+                        .map_err(|e| Error::TagError(tag.clone(), e.into()))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
 impl Execute for ast::FnDef {
     fn exec(&self, scope: &ScopeRef<Mutable>) -> Result<()> {
         let func = Function::from_def(self.clone(), scope.as_readonly());
@@ -96,16 +149,8 @@ impl Evaluate for ast::Call {
     fn eval(&self, scope: &ScopeRef) -> Result<Value> {
         let func = self.func.eval(scope)?.into_fn()?;
         let args = eval_exprs(scope, &self.args)?;
-
-        match (func.call(args, scope), self.func.as_ident()) {
-            (Err(Error::CallError(None, e)), Some(caller_ident)) => {
-                // If the function originating the error is unnamed but the caller called it using an identifier,
-                // use the caller name for the CallError message. A name may not be available on the caller side
-                // either if the function was not accessed through a simple identifier, for example `([a].0)()`
-                Err(Error::CallError(Some(caller_ident.clone()), e.into()))
-            }
-            (other, _) => other,
-        }
+        let named_args = eval_named_args(scope, &self.named_args)?;
+        function::call_as(&func, args, named_args, scope, self.func.as_ident())
     }
 }
 
@@ -229,14 +274,19 @@ impl Evaluate for ast::Infix {
     fn eval(&self, scope: &ScopeRef) -> Result<Value> {
         self.op
             .apply(self.lhs.eval(scope)?, self.rhs.eval(scope)?, scope)
-            .map_err(|e| Error::InfixOpError(self.op, e.into()))
+            .map_err(|e| match e {
+                e if e.is_resource_exhaustion() => e,
+                e => Error::InfixOpError(self.op, e.into()),
+            })
     }
 }
 
 impl ast::InfixOp {
     fn apply(&self, lhs: Value, rhs: Value, scope: &ScopeRef) -> Result<Value> {
         use ast::InfixOp::*;
-        use Value::{Array, Bytes, Number as Num, Policy, PubKey, Script, String, WithProb};
+        use Value::{
+            Array, Bytes, Number as Num, Policy, PubKey, Script, String, TapLeaf, WithProb,
+        };
 
         Ok(match (self, lhs, rhs) {
             // == != for all types
@@ -264,25 +314,38 @@ impl ast::InfixOp {
             (Multiply, Num(Float(a)), Num(Float(b))) => (a * b).into(),
 
             // + for arrays, bytes and strings
-            (Add, Array(a), Array(b)) => [a.0, b.0].concat().into(),
-            (Add, Bytes(a), Bytes(b)) => [a, b].concat().into(),
-            (Add, String(a), String(b)) => [a, b].concat().into(),
+            (Add, Array(a), Array(b)) => {
+                limits::check_array_len(a.len() + b.len())?;
+                [a.0, b.0].concat().into()
+            }
+            (Add, Bytes(a), Bytes(b)) => {
+                limits::check_bytes_len(a.len() + b.len())?;
+                [a, b].concat().into()
+            }
+            (Add, String(a), String(b)) => {
+                limits::check_bytes_len(a.len() + b.len())?;
+                [a, b].concat().into()
+            }
             // + for string and number
-            (Add, String(a), Num(b)) => [a, b.to_string()].concat().into(),
+            (Add, String(a), Num(b)) => {
+                let b = b.to_string();
+                limits::check_bytes_len(a.len() + b.len())?;
+                [a, b].concat().into()
+            }
 
             // + for taproot construction (internal_key+script_tree)
             (Add, k @ PubKey(_), s)
             | (Add, k @ Bytes(_), s @ Script(_) | s @ Policy(_) | s @ PubKey(_) | s @ Array(_)) => {
-                stdlib::taproot::tr(k, Some(s), &scope.borrow())?
+                stdlib::taproot::tr(k, Some(s), scope)?
             }
 
             // * to repeat script fragments
             (Multiply, Script(s), Num(Int(n))) | (Multiply, Num(Int(n)), Script(s)) => {
-                stdlib::btc::repeat_script(s, n.try_into()?).into()
+                stdlib::btc::repeat_script(s, n.try_into()?)?.into()
             }
 
             // @ to assign execution probabilities (Script/Policy, or a PubKey coerced into a pk() Policy)
-            (Prob, Num(prob), v @ Policy(_) | v @ Script(_) | v @ PubKey(_)) => {
+            (Prob, Num(prob), v @ Policy(_) | v @ Script(_) | v @ PubKey(_) | v @ TapLeaf(..)) => {
                 WithProb(prob.into_usize()?, v.into())
             }
 
@@ -324,6 +387,7 @@ impl Evaluate for ast::Block {
 
 impl Evaluate for Expr {
     fn eval(&self, scope: &ScopeRef) -> Result<Value> {
+        limits::step()?;
         Ok(match self {
             Expr::Ident(x) => x.eval(scope)?, // dedicated error type
             Expr::Call(x) => x.eval(scope)?,  // dedicated error type
@@ -357,10 +421,24 @@ trait ResultExt<T> {
 }
 impl<T> ResultExt<T> for Result<T> {
     fn ctx(self, context_str: &'static str) -> Result<T> {
-        self.map_err(|e| Error::ContextStr(context_str, e.into()))
+        self.map_err(|e| match e {
+            e if e.is_resource_exhaustion() => e,
+            e => Error::ContextStr(context_str, e.into()),
+        })
     }
 }
 
+/// Evaluate named argument expressions to produce a list of named values
+pub fn eval_named_args(
+    scope: &ScopeRef,
+    named_args: &[(ast::Ident, Expr)],
+) -> Result<function::NamedArgs> {
+    named_args
+        .iter()
+        .map(|(name, expr)| Ok((name.clone(), expr.eval(scope)?)))
+        .collect()
+}
+
 /// Evaluate a list of expressions to produce a list of values
 pub fn eval_exprs<T: Borrow<Expr>>(scope: &ScopeRef, exprs: &[T]) -> Result<Vec<Value>> {
     exprs.iter().map(|arg| arg.borrow().eval(scope)).collect()
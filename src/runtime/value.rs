@@ -3,12 +3,17 @@ use std::fmt;
 use std::str::FromStr;
 
 use bitcoin::{
-    hashes, hashes::Hash, hex::DisplayHex, taproot::TaprootSpendInfo, Address, Network, ScriptBuf,
-    Transaction,
+    hashes,
+    hashes::Hash,
+    hex::DisplayHex,
+    taproot::{LeafVersion, TaprootSpendInfo},
+    Address, Network, ScriptBuf, Transaction,
 };
 use miniscript::{bitcoin, DescriptorPublicKey};
 
 use crate::parser::Expr;
+use crate::stdlib::elements::ElementsTransaction;
+use crate::stdlib::ms::AnyMiniscript;
 use crate::util::{fmt_quoted_str, PrettyDisplay};
 use crate::{error, DescriptorDpk as Descriptor, PolicyDpk as Policy};
 
@@ -35,6 +40,9 @@ pub enum Value {
     Descriptor(Descriptor),
     TapInfo(TaprootSpendInfo),
     WithProb(usize, Box<Value>), // Policy/Script with an associated execution probability (the `@` operator)
+    TapLeaf(LeafVersion, ScriptBuf), // Script with an explicit Taproot leaf version (the leaf() function)
+    Miniscript(AnyMiniscript), // Miniscript fragment tree with its type information and script context
+    ElementsTransaction(ElementsTransaction),
 
     // A unique Symbol
     Symbol(Symbol),
@@ -92,6 +100,7 @@ impl_from_variant!(Array, Value);
 impl_from_variant!(Symbol, Value);
 impl_from_variant!(Policy, Value);
 impl_from_variant!(Descriptor, Value);
+impl_from_variant!(AnyMiniscript, Value, Miniscript);
 impl_from_variant!(DescriptorPublicKey, Value, PubKey);
 impl_from_variant!(ScriptBuf, Value, Script);
 impl_from_variant!(Address, Value);
@@ -195,6 +204,7 @@ impl TryFrom<Value> for Vec<u8> {
             Value::String(string) => string.into_bytes(),
             Value::Script(script) => script.into_bytes(),
             Value::Transaction(tx) => bitcoin::consensus::serialize(&tx),
+            Value::ElementsTransaction(tx) => tx.serialize(true),
             v => bail!(Error::NotBytesLike(v.into())),
         })
     }
@@ -225,6 +235,9 @@ impl_hash_conv!(hashes::sha256d::Hash);
 impl_hash_conv!(hashes::ripemd160::Hash);
 impl_hash_conv!(hashes::hash160::Hash);
 impl_hash_conv!(miniscript::hash256::Hash);
+impl_hash_conv!(hashes::sha1::Hash);
+impl_hash_conv!(hashes::sha512::Hash);
+impl_hash_conv!(hashes::hmac::Hmac<hashes::sha512::Hash>);
 
 /// Generic conversion from a Value/Option<Value> into T/Option<T> of any TryFrom<Value> type.
 ///
@@ -308,10 +321,13 @@ impl Value {
             Value::String(_) => "string",
             Value::Policy(_) => "policy",
             Value::WithProb(_, _) => "withprob",
+            Value::TapLeaf(_, _) => "tapleaf",
+            Value::Miniscript(_) => "miniscript",
             Value::Descriptor(_) => "descriptor",
             Value::Address(_) => "address",
             Value::Script(_) => "script",
             Value::Transaction(_) => "transaction",
+            Value::ElementsTransaction(_) => "elementstx",
             Value::Function(_) => "function",
             Value::Network(_) => "network",
             Value::TapInfo(_) => "tapinfo",
@@ -391,6 +407,10 @@ impl fmt::Display for Value {
             Value::String(x) => fmt_quoted_str(f, x),
             Value::Policy(x) => write!(f, "{}", x),
             Value::WithProb(p, x) => write!(f, "{}@{}", p, x),
+            Value::TapLeaf(ver, script) => {
+                let ver = ver.to_consensus();
+                write!(f, "leaf(0x{:02x}, {})", ver, script.pretty(None))
+            }
             Value::Descriptor(x) => write!(f, "{:#}", x), // not round-trip-able for Sh/Wsh or Tr with script-paths (can be, if the compiled miniscript in it was)
             Value::Address(x) => write!(f, "{}", x),
             Value::Function(x) => write!(f, "{}", x), // not round-trip-able (cannot be)
@@ -401,6 +421,8 @@ impl fmt::Display for Value {
             Value::Transaction(x) => write!(f, "{}", x.pretty(None)),
             Value::Script(x) => write!(f, "{}", x.pretty(None)),
             Value::TapInfo(x) => write!(f, "{}", x.pretty(None)), // not round-trip-able for >2 scripts
+            Value::Miniscript(x) => write!(f, "{}", x.pretty(None)),
+            Value::ElementsTransaction(x) => write!(f, "{}", x.pretty(None)),
         }
     }
 }
@@ -424,6 +446,8 @@ impl PrettyDisplay for Value {
             Value::Script(x) => write!(f, "{}", x.pretty(indent)),
             Value::Transaction(x) => write!(f, "{}", x.pretty(indent)),
             Value::TapInfo(x) => write!(f, "{}", x.pretty(indent)),
+            Value::Miniscript(x) => write!(f, "{}", x.pretty(indent)),
+            Value::ElementsTransaction(x) => write!(f, "{}", x.pretty(indent)),
             // Use Display for types that don't implement PrettyDisplay
             other => write!(f, "{}", other),
         }
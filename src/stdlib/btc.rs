@@ -18,12 +18,14 @@ use miniscript::descriptor::{
 
 use super::script_marker::{Marker, MarkerItem, ScriptMarker};
 use crate::runtime::scope::{Mutable, ScopeRef};
-use crate::runtime::{eval_exprs, Array, Error, Evaluate, Float, Int, Result, Symbol, Value};
+use crate::runtime::{
+    eval_exprs, limits, Array, Error, Evaluate, Float, Int, Result, Symbol, Value,
+};
 use crate::util::{self, fmt_list, DeriveExt, DescriptorExt, PrettyDisplay, EC};
-use crate::{ast, time};
+use crate::{ast, time, DescriptorDpk};
 
 // XXX should this be randomized? is there a way this could be abused when viewing untrusted scripts?
-const SCRIPT_MARKER_MAGIC_BYTES: &[u8] = "SCRIPT MARKER MAGIC BYTES".as_bytes();
+pub const SCRIPT_MARKER_MAGIC_BYTES: &[u8] = "SCRIPT MARKER MAGIC BYTES".as_bytes();
 
 pub fn attach_stdlib(scope: &ScopeRef<Mutable>) {
     let mut scope = scope.borrow_mut();
@@ -38,14 +40,36 @@ pub fn attach_stdlib(scope: &ScopeRef<Mutable>) {
 
     // Functions
     scope.set_fn("address", fns::address).unwrap();
+    scope.set_fn("address::info", fns::addressInfo).unwrap();
+    scope
+        .set_fn("address::isValidFor", fns::addressIsValidFor)
+        .unwrap();
     scope.set_fn("transaction", fns::transaction).unwrap();
     scope.set_fn("script", fns::script).unwrap();
     scope.set_fn("pubkey", fns::pubkey).unwrap();
     scope.set_fn("scriptPubKey", fns::scriptPubKey).unwrap();
+    scope.set_fn("witnessProgram", fns::witnessProgram).unwrap();
     scope.set_fn("script::strip", fns::scriptStrip).unwrap();
     scope.set_fn("script::wiz", fns::scriptWiz).unwrap();
     scope.set_fn("script::bitide", fns::scriptBitIde).unwrap();
 
+    // Transaction inspection functions
+    scope.set_fn("tx::txid", fns::txid).unwrap();
+    scope.set_fn("tx::wtxid", fns::wtxid).unwrap();
+    scope.set_fn("tx::weight", fns::txWeight).unwrap();
+    scope.set_fn("tx::vsize", fns::txVsize).unwrap();
+    scope.set_fn("tx::fee", fns::txFee).unwrap();
+    scope.set_fn("tx::feerate", fns::txFeerate).unwrap();
+
+    // Fee planning for unsigned transactions
+    scope
+        .set_fn("tx::estimateVsize", fns::txEstimateVsize)
+        .unwrap();
+    scope.set_fn("tx::change", fns::txChange).unwrap();
+
+    // TRUC (v3) policy rules
+    scope.set_fn("tx::checkTruc", fns::txCheckTruc).unwrap();
+
     scope
         .set("SCRIPT_MARKER_MAGIC", SCRIPT_MARKER_MAGIC_BYTES.to_vec())
         .unwrap();
@@ -76,6 +100,7 @@ fn script_frag(value: Value) -> Result<ScriptBuf> {
     Ok(match value {
         // As script code
         Value::Script(script) => script,
+        Value::Miniscript(ms) => ms.encode()?,
 
         // As data pushes
         Value::Number(Int(n)) => push_int(n),
@@ -89,14 +114,12 @@ fn script_frag(value: Value) -> Result<ScriptBuf> {
 
         // Flatten arrays
         Value::Array(elements) => {
-            let scriptbytes = elements
+            let frags = elements
                 .into_iter()
                 .map(|val| Ok(script_frag(val)?.into_bytes()))
-                .collect::<Result<Vec<_>>>()?
-                .into_iter()
-                .flatten()
-                .collect::<Vec<u8>>();
-            ScriptBuf::from(scriptbytes)
+                .collect::<Result<Vec<_>>>()?;
+            limits::check_script_len(frags.iter().map(Vec::len).sum())?;
+            ScriptBuf::from(frags.concat())
         }
 
         Value::Number(Float(n)) => bail!(Error::InvalidScriptFragIntOnly(n)),
@@ -105,10 +128,11 @@ fn script_frag(value: Value) -> Result<ScriptBuf> {
     // XXX could reuse a single ScriptBuilder, if writing raw `ScriptBuf`s into it was possible
 }
 
-pub fn repeat_script(script: ScriptBuf, times: usize) -> ScriptBuf {
+pub fn repeat_script(script: ScriptBuf, times: usize) -> Result<ScriptBuf> {
+    limits::check_script_len(script.len().saturating_mul(times))?;
     let bytes = script.into_bytes();
     let bytes_n: Vec<u8> = (0..times).map(|_| bytes.clone()).flatten().collect();
-    ScriptBuf::from(bytes_n)
+    Ok(ScriptBuf::from(bytes_n))
 }
 
 impl Evaluate for ast::ChildDerive {
@@ -200,14 +224,86 @@ pub mod fns {
 
     /// Generate an address
     /// address(Script|Descriptor|PubKey|TapInfo|String|Address, Network=Signet) -> Address
+    ///
+    /// Addresses provided as an Address/String are validated against the Network when one is
+    /// given, or returned as-is otherwise.
     pub fn address(args: Array, _: &ScopeRef) -> Result<Value> {
         let (spk, network): (Value, Option<Network>) = args.args_into()?;
-        let spk = spk.into_spk()?;
-        let network = network.unwrap_or(Network::Signet);
+        Ok(match (spk, network) {
+            (spk @ Value::Address(_) | spk @ Value::String(_), None) => {
+                Address::try_from(spk)?.into()
+            }
+            (spk @ Value::Address(_) | spk @ Value::String(_), Some(network)) => {
+                let address = Address::try_from(spk)?.as_unchecked().clone();
+                let (_, payload) = address.require_network(network)?.into_parts();
+                Address::new(network, payload).into()
+            }
+            (spk, network) => {
+                let spk = spk.into_spk()?;
+                Address::from_script(&spk, network.unwrap_or(Network::Signet))
+                    .map_err(|_| Error::NotAddressable(spk.into()))?
+                    .into()
+            }
+        })
+    }
 
-        Ok(Address::from_script(&spk, network)
-            .map_err(|_| Error::NotAddressable(spk.into()))?
-            .into())
+    /// address::info(Address|String) -> Array<Tagged>
+    ///
+    /// Returns the network, type and witness version/program (for segwit addresses)
+    /// or hash (for p2pkh/p2sh addresses). The type is one of p2pkh, p2sh, p2wpkh,
+    /// p2wsh, p2tr or witness_unknown. Signet addresses are reported as testnet,
+    /// the two share the same encoding.
+    pub fn addressInfo(args: Array, _: &ScopeRef) -> Result<Value> {
+        let address: Address = args.arg_into()?;
+        let address_type = match address.address_type() {
+            Some(address_type) => address_type.to_string(),
+            None => "witness_unknown".into(),
+        };
+        let mut info = vec![
+            ("network".into(), (*address.network()).into()),
+            ("type".into(), address_type.into()),
+        ];
+        match address.payload() {
+            address::Payload::PubkeyHash(hash) => {
+                info.push(("hash".into(), hash.to_byte_array().to_vec().into()))
+            }
+            address::Payload::ScriptHash(hash) => {
+                info.push(("hash".into(), hash.to_byte_array().to_vec().into()))
+            }
+            address::Payload::WitnessProgram(program) => {
+                info.push((
+                    "witness_version".into(),
+                    (program.version().to_num() as i64).into(),
+                ));
+                info.push((
+                    "witness_program".into(),
+                    program.program().as_bytes().to_vec().into(),
+                ));
+            }
+            _ => {}
+        }
+        info.push(("script_pubkey".into(), address.script_pubkey().into()));
+        Ok(Value::array(
+            info.into_iter()
+                .map(|(tag, val): (String, Value)| Value::array(vec![tag.into(), val]))
+                .collect(),
+        ))
+    }
+
+    /// address::isValidFor(Address|String, Network) -> Bool
+    pub fn addressIsValidFor(args: Array, _: &ScopeRef) -> Result<Value> {
+        let (address, network): (Address, Network) = args.args_into()?;
+        Ok(address.as_unchecked().is_valid_for_network(network).into())
+    }
+
+    /// Construct a witness program scriptPubKey of any version, including ones with
+    /// no known semantics (like the P2A anchor output, `witnessProgram(1, 0x4e73)`)
+    /// witnessProgram(Number version, Bytes program) -> Script
+    pub fn witnessProgram(args: Array, _: &ScopeRef) -> Result<Value> {
+        let (version, program): (u32, Vec<u8>) = args.args_into()?;
+        let program =
+            WitnessProgram::new(WitnessVersion::try_from(u8::try_from(version)?)?, program)?;
+        Ok(ScriptBuf::new_witness_program(&program).into())
     }
 
     /// script(Bytes|Script) -> Script
@@ -271,6 +367,208 @@ pub mod fns {
         fmt_script(&mut bitide_str, &script, ScriptFmt::BitIde, Some(0))?;
         Ok(Symbol::new(Some(bitide_str)).into())
     }
+
+    /// tx::txid(Transaction) -> Bytes
+    /// Get the txid, in the reversed byte order it is typically displayed in
+    pub fn txid(args: Array, _: &ScopeRef) -> Result<Value> {
+        let tx: Transaction = args.arg_into()?;
+        Ok(tx.txid().into())
+    }
+
+    /// tx::wtxid(Transaction) -> Bytes
+    pub fn wtxid(args: Array, _: &ScopeRef) -> Result<Value> {
+        let tx: Transaction = args.arg_into()?;
+        let mut wtxid = tx.wtxid().to_byte_array().to_vec();
+        wtxid.reverse();
+        Ok(wtxid.into())
+    }
+
+    /// tx::weight(Transaction) -> Number
+    pub fn txWeight(args: Array, _: &ScopeRef) -> Result<Value> {
+        let tx: Transaction = args.arg_into()?;
+        Ok(Value::from(tx.weight().to_wu() as i64))
+    }
+
+    /// tx::vsize(Transaction) -> Number
+    pub fn txVsize(args: Array, _: &ScopeRef) -> Result<Value> {
+        let tx: Transaction = args.arg_into()?;
+        Ok(tx.vsize().into())
+    }
+
+    /// tx::fee(Transaction, Array<TxOut|Number> prevouts) -> Number
+    /// Get the fee paid by the transaction, given the outputs (or just the amounts) spent by its inputs
+    pub fn txFee(args: Array, _: &ScopeRef) -> Result<Value> {
+        let (tx, prevouts): (Transaction, Vec<Value>) = args.args_into()?;
+        Ok(Value::from(tx_fee(&tx, prevouts)?.to_sat() as i64))
+    }
+
+    /// tx::feerate(Transaction, Array<TxOut|Number> prevouts) -> Float
+    /// Get the feerate in sat/vB
+    pub fn txFeerate(args: Array, _: &ScopeRef) -> Result<Value> {
+        let (tx, prevouts): (Transaction, Vec<Value>) = args.args_into()?;
+        let fee = tx_fee(&tx, prevouts)?;
+        Ok((fee.to_sat() as f64 / tx.vsize() as f64).into())
+    }
+
+    /// tx::estimateVsize(Transaction, Array<SpentInput> spent) -> Number
+    ///
+    /// Estimate the vsize of the unsigned transaction once its inputs are satisfied, using the
+    /// max satisfaction weight of the descriptors they spend. Spent inputs can be provided as
+    /// a Descriptor, as a Descriptor:amount tuple, or as a tagged list with "descriptor",
+    /// "amount" and "leaf" (to estimate a specific tr() script-path spend instead of the max).
+    pub fn txEstimateVsize(args: Array, _: &ScopeRef) -> Result<Value> {
+        let (tx, spent): (Transaction, Vec<SpentInput>) = args.args_into()?;
+        Ok(estimate_vsize(&tx, &spent)?.into())
+    }
+
+    /// tx::change(Transaction, Array<SpentInput> spent, Number feerate, Script|Address|Descriptor change_spk) -> Number
+    ///
+    /// Compute the change amount to send to `change_spk` for the transaction to pay `feerate` (in sat/vB),
+    /// accounting for the added change output. The amounts of all spent inputs must be provided.
+    pub fn txChange(args: Array, _: &ScopeRef) -> Result<Value> {
+        let (mut tx, spent, feerate, change_spk): (Transaction, Vec<SpentInput>, f64, Value) =
+            args.args_into()?;
+
+        let input_total = sum_amounts(
+            spent
+                .iter()
+                .map(|spent| spent.amount.ok_or(Error::MissingSpentAmount))
+                .collect::<Result<Vec<_>>>()?,
+        )?;
+        let output_total = sum_amounts(tx.output.iter().map(|out| out.value))?;
+
+        tx.output.push(TxOut {
+            script_pubkey: change_spk.into_spk()?,
+            value: Amount::ZERO,
+        });
+        let vsize = estimate_vsize(&tx, &spent)?;
+        let fee = Amount::from_sat((vsize as f64 * feerate).ceil() as u64);
+
+        let change = input_total
+            .checked_sub(output_total)
+            .and_then(|available| available.checked_sub(fee))
+            .ok_or(Error::InsufficientFundsForFee(fee))?;
+        Ok(Value::from(change.to_sat() as i64))
+    }
+
+    /// Check the TRUC (v3) topology rules, raising an error if they are violated.
+    /// Transactions constructed from a tagged list are checked automatically.
+    /// tx::checkTruc(Transaction, Array<Transaction> unconfirmed_parents=[]) -> Bool
+    pub fn txCheckTruc(args: Array, _: &ScopeRef) -> Result<Value> {
+        let (tx, parents): (Transaction, Option<Vec<Transaction>>) = args.args_into()?;
+        check_truc(&tx, &parents.unwrap_or_default())?;
+        Ok(true.into())
+    }
+}
+
+fn tx_fee(tx: &Transaction, prevouts: Vec<Value>) -> Result<Amount> {
+    ensure!(
+        prevouts.len() == tx.input.len(),
+        Error::InvalidLength(prevouts.len(), tx.input.len())
+    );
+    let input_amounts = prevouts
+        .into_iter()
+        .map(|prevout| match prevout {
+            // Prevouts can be provided as their amount, or as the full TxOut
+            Value::Number(_) => prevout.try_into(),
+            prevout => Ok(TxOut::try_from(prevout)?.value),
+        })
+        .collect::<Result<Vec<Amount>>>()?;
+    let input_total = sum_amounts(input_amounts)?;
+    let output_total = sum_amounts(tx.output.iter().map(|out| out.value))?;
+
+    input_total
+        .checked_sub(output_total)
+        .ok_or(Error::TxOutputsExceedInputs(input_total, output_total))
+}
+
+/// An input spending a descriptor, used for fee estimation
+struct SpentInput {
+    descriptor: DescriptorDpk,
+    amount: Option<Amount>,
+    leaf: Option<ScriptBuf>,
+}
+
+impl TryFrom<Value> for SpentInput {
+    type Error = Error;
+    fn try_from(val: Value) -> Result<Self> {
+        let (descriptor, amount, leaf) = if val.is_tagged_array() {
+            val.tagged_into3("descriptor", "amount", "leaf")?
+        } else if val.is_array() {
+            let (descriptor, amount) = val.into_tuple()?;
+            (descriptor, Some(amount), None)
+        } else {
+            (val.try_into()?, None, None)
+        };
+        Ok(SpentInput {
+            descriptor,
+            amount,
+            leaf,
+        })
+    }
+}
+
+impl SpentInput {
+    /// The weight added by satisfying this input (scriptSig and witness)
+    fn satisfaction_weight(&self) -> Result<usize> {
+        let descriptor = self.descriptor.derive_keys()?;
+        Ok(match (&descriptor, &self.leaf) {
+            (_, None) => descriptor.max_weight_to_satisfy()?,
+            (Descriptor::Tr(tr), Some(leaf)) => {
+                let (depth, ms) = tr
+                    .iter_scripts()
+                    .find(|(_, ms)| ms.encode() == *leaf)
+                    .ok_or_else(|| Error::TaprootLeafNotFound(leaf.clone()))?;
+                let script_size = ms.script_size();
+                let control_block_size = 33 + 32 * depth as usize;
+                let stack_items = ms.max_satisfaction_witness_elements()? + 1;
+                // Calculated similarly to rust-miniscript's Tr::max_weight_to_satisfy(), for the selected leaf only
+                varint_len(stack_items) - varint_len(0)
+                    + ms.max_satisfaction_size()?
+                    + varint_len(script_size)
+                    + script_size
+                    + varint_len(control_block_size)
+                    + control_block_size
+            }
+            (_, Some(_)) => bail!(Error::InvalidArguments),
+        })
+    }
+
+    fn is_segwit(&self) -> bool {
+        self.descriptor.desc_type().segwit_version().is_some()
+    }
+}
+
+fn estimate_vsize(tx: &Transaction, spent: &[SpentInput]) -> Result<usize> {
+    ensure!(
+        spent.len() == tx.input.len(),
+        Error::InvalidLength(spent.len(), tx.input.len())
+    );
+    // Start from the weight of the transaction with no scriptSigs/witnesses
+    let mut unsigned_tx = tx.clone();
+    for input in &mut unsigned_tx.input {
+        input.script_sig = ScriptBuf::new();
+        input.witness.clear();
+    }
+    let mut weight = unsigned_tx.weight().to_wu() as usize;
+    if spent.iter().any(SpentInput::is_segwit) {
+        // The segwit marker and flag, plus the witness stack length for each input
+        weight += 2 + tx.input.len();
+    }
+    for spent_input in spent {
+        weight += spent_input.satisfaction_weight()?;
+    }
+    Ok(weight.div_ceil(4))
+}
+
+pub fn varint_len(n: usize) -> usize {
+    bitcoin::VarInt(n as u64).size()
+}
+
+pub fn sum_amounts<I: IntoIterator<Item = Amount>>(amounts: I) -> Result<Amount> {
+    amounts.into_iter().try_fold(Amount::ZERO, |total, amount| {
+        total.checked_add(amount).ok_or(Error::Overflow)
+    })
 }
 
 impl Value {
@@ -296,6 +594,10 @@ impl Value {
     pub fn is_script(&self) -> bool {
         matches!(self, Value::Script(_))
     }
+    /// Scripts that can be used as Taproot leaves, with the default or an explicit leaf version
+    pub fn is_tapleaf_like(&self) -> bool {
+        matches!(self, Value::Script(_) | Value::TapLeaf(..))
+    }
 }
 
 // Convert from Bitcoin types to Value
@@ -308,12 +610,29 @@ impl From<XOnlyPublicKey> for Value {
         }))
     }
 }
+impl From<PublicKey> for Value {
+    fn from(key: PublicKey) -> Self {
+        Value::PubKey(DescriptorPublicKey::Single(SinglePub {
+            key: SinglePubKey::FullKey(key),
+            origin: None,
+        }))
+    }
+}
 impl From<TweakedPublicKey> for Value {
     fn from(key: TweakedPublicKey) -> Self {
         key.to_inner().into()
     }
 }
 
+// Txids are represented in the reversed byte order, matching TryFrom<Value> for Txid below
+impl From<Txid> for Value {
+    fn from(txid: Txid) -> Self {
+        let mut bytes = txid.to_byte_array().to_vec();
+        bytes.reverse();
+        Value::Bytes(bytes)
+    }
+}
+
 impl From<Xpub> for Value {
     fn from(xpub: Xpub) -> Self {
         Value::PubKey(DescriptorPublicKey::XPub(DescriptorXKey {
@@ -372,7 +691,8 @@ impl TryFrom<Value> for Address {
             Value::Address(address) => address,
             Value::String(addr_str) => {
                 let addr: Address<address::NetworkUnchecked> = addr_str.parse()?;
-                // XXX avoid assume_checked? we don't always know the network at the time the address is parsed.
+                // The network is unknown at this point. Addresses retain the network they were encoded
+                // for, which address() and address::isValidFor() check against the requested network.
                 addr.assume_checked()
             }
             v => bail!(Error::NotAddress(v.into())),
@@ -388,7 +708,10 @@ impl TryFrom<Value> for Transaction {
             Value::Bytes(bytes) => bitcoin::consensus::deserialize(&bytes)?,
 
             // From tagged [ "version": $version, "locktime": $locktime, "inputs": [ .. ], "outputs": [ .. ] ]
+            // Inputs spending from a Transaction value ($parent_tx:$vout) are considered to be unconfirmed,
+            // and checked against the TRUC topology rules.
             Value::Array(_) => {
+                let mut parents = vec![];
                 let mut tx = Transaction {
                     version: Version(2),
                     lock_time: LockTime::ZERO,
@@ -399,14 +722,24 @@ impl TryFrom<Value> for Transaction {
                     match tag {
                         "version" => tx.version = val.try_into()?,
                         "locktime" => tx.lock_time = val.try_into()?,
-                        "input" => tx.input.push(val.try_into()?),
+                        "input" => {
+                            parents.extend(unconfirmed_parent(&val).cloned());
+                            tx.input.push(val.try_into()?)
+                        }
                         "output" => tx.output.push(val.try_into()?),
-                        "inputs" => tx.input.extend(val.into_vec_of()?),
+                        "inputs" => {
+                            if let Value::Array(inputs) = &val {
+                                parents
+                                    .extend(inputs.iter().filter_map(unconfirmed_parent).cloned());
+                            }
+                            tx.input.extend(val.into_vec_of()?)
+                        }
                         "outputs" => tx.output.extend(val.into_vec_of()?),
                         _ => bail!(Error::TagUnknown),
                     }
                     Ok(())
                 })?;
+                check_truc(&tx, &parents)?;
                 tx
             }
 
@@ -415,6 +748,64 @@ impl TryFrom<Value> for Transaction {
     }
 }
 
+pub const TRUC_MAX_VSIZE: usize = 10_000;
+pub const TRUC_CHILD_MAX_VSIZE: usize = 1_000;
+
+/// Check the TRUC (BIP 431) policy rules for the transaction, given its unconfirmed parents.
+/// Only the rules that can be verified from the transaction and its parents are checked.
+pub fn check_truc(tx: &Transaction, parents: &[Transaction]) -> Result<()> {
+    let mut parent_txids: Vec<Txid> = parents.iter().map(Transaction::txid).collect();
+    parent_txids.sort();
+    parent_txids.dedup();
+
+    if tx.version != Version(3) {
+        // Unconfirmed TRUC transactions may only be spent by other TRUC transactions
+        if let Some(truc_parent) = parents.iter().find(|p| p.version == Version(3)) {
+            bail!(Error::TrucNonTrucChild(tx.version.0, truc_parent.txid()));
+        }
+        return Ok(());
+    }
+
+    let vsize = tx.vsize();
+    ensure!(vsize <= TRUC_MAX_VSIZE, Error::TrucTooLarge(vsize));
+    ensure!(
+        parent_txids.len() <= 1,
+        Error::TrucMultipleParents(parent_txids.len())
+    );
+    if let Some(parent) = parents.first() {
+        ensure!(
+            parent.version == Version(3),
+            Error::TrucNonTrucParent(parent.txid())
+        );
+        ensure!(
+            vsize <= TRUC_CHILD_MAX_VSIZE,
+            Error::TrucChildTooLarge(vsize)
+        );
+    }
+    Ok(())
+}
+
+/// Get the parent Transaction referenced by a TxIn Value, as a $parent_tx:$vout tuple,
+/// a tagged [ "txid": $parent_tx, "vout": $vout ] outpoint, or within a tagged "prevout"
+fn unconfirmed_parent(input: &Value) -> Option<&Transaction> {
+    match input {
+        Value::Array(arr) => match arr.as_slice() {
+            [Value::Transaction(tx), Value::Number(_)] => Some(tx),
+            elements => elements.iter().find_map(|el| match el {
+                Value::Array(tagged) => match tagged.as_slice() {
+                    [Value::String(tag), Value::Transaction(tx)] if tag == "txid" => Some(tx),
+                    [Value::String(tag), prevout] if tag == "prevout" => {
+                        unconfirmed_parent(prevout)
+                    }
+                    _ => None,
+                },
+                _ => None,
+            }),
+        },
+        _ => None,
+    }
+}
+
 // From tagged [ "prevout": $txid:$vout, "sequence": $sequence, "script_sig": `0x00 0x01`, "witness": [ .. ] ]
 //  or just the $txid:vout
 impl TryFrom<Value> for TxIn {
@@ -461,6 +852,12 @@ impl TryFrom<Value> for OutPoint {
 impl TryFrom<Value> for Txid {
     type Error = Error;
     fn try_from(val: Value) -> Result<Self> {
+        // Transactions are referenced by their txid, to allow using $prev_tx:$vout as an OutPoint
+        match val {
+            Value::Transaction(tx) => return Ok(tx.txid()),
+            Value::ElementsTransaction(tx) => return Ok(tx.txid()),
+            _ => (),
+        }
         // Bitcoin's txid bytes needs to be reversed to match how they're commonly presented
         // XXX Could this result in the wrong behavior?
         let mut bytes = val.into_bytes()?;
@@ -575,22 +972,8 @@ pub fn fmt_script<W: fmt::Write>(
             Ok(MarkerItem::Marker(Marker { kind, body })) => {
                 match (format, kind) {
                     // Minsc formatting, as Minsc code that can re-construct the markers
-                    (ScriptFmt::Minsc(_), "label") if is_valid_ident(body) => {
-                        write!(f, "@{}", body)?
-                    }
-                    (ScriptFmt::Minsc(_), "label") => write!(f, "@{{{}}}", quote(body))?,
-                    (ScriptFmt::Minsc(_), "comment") => {
-                        write!(f, "#{}{}", iif!(indent.is_some(), " ", ""), quote(body))?
-                    }
-                    (ScriptFmt::Minsc(_), kind) if is_valid_ident(kind) => {
-                        if body.is_empty() {
-                            write!(f, "@{}()", kind)?
-                        } else {
-                            write!(f, "@{}({})", kind, quote(body))?
-                        }
-                    }
                     (ScriptFmt::Minsc(_), kind) => {
-                        write!(f, "@({}, {})", quote(kind), quote(body))?
+                        fmt_minsc_marker(f, kind, body, indent.is_some())?
                     }
 
                     // ScriptWiz formatting
@@ -643,6 +1026,24 @@ pub fn fmt_script<W: fmt::Write>(
     Ok(())
 }
 
+/// Format a script marker as Minsc code that re-constructs it
+pub fn fmt_minsc_marker<W: fmt::Write>(
+    f: &mut W,
+    kind: &str,
+    body: &str,
+    multiline: bool,
+) -> fmt::Result {
+    use crate::util::quote_str as quote;
+    match kind {
+        "label" if is_valid_ident(body) => write!(f, "@{}", body),
+        "label" => write!(f, "@{{{}}}", quote(body)),
+        "comment" => write!(f, "#{}{}", iif!(multiline, " ", ""), quote(body)),
+        kind if is_valid_ident(kind) && body.is_empty() => write!(f, "@{}()", kind),
+        kind if is_valid_ident(kind) => write!(f, "@{}({})", kind, quote(body)),
+        kind => write!(f, "@({}, {})", quote(kind), quote(body)),
+    }
+}
+
 // ScriptWiz only allows alphanumeric characters and underscores in label names
 fn encode_label(input: &str) -> String {
     input
@@ -749,6 +1150,8 @@ impl PrettyDisplay for Opcode {
             // special-case for unofficial opcodes
             (ops::OP_NOP4, _) => write!(f, "OP_CHECKTEMPLATEVERIFY"),
             (ops::OP_RETURN_215, _) => write!(f, "OP_ADD64"),
+            (ops::OP_RETURN_193, _) => write!(f, "OP_CHECKSIGFROMSTACK"),
+            (ops::OP_RETURN_194, _) => write!(f, "OP_CHECKSIGFROMSTACKVERIFY"),
             (ops::OP_RETURN_218, _) => write!(f, "OP_DIV64"),
             (ops::OP_RETURN_227, _) => write!(f, "OP_ECMULSCALARVERIFY"),
             (ops::OP_RETURN_222, _) => write!(f, "OP_GREATERTHAN64"),
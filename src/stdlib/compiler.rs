@@ -0,0 +1,446 @@
+use std::convert::TryFrom;
+use std::sync::Arc;
+
+use miniscript::descriptor::{ShInner, WshInner};
+use miniscript::plan::Assets;
+use miniscript::{DescriptorPublicKey, ScriptContext};
+
+use super::btc::varint_len;
+use super::ms::fragment_name;
+use super::taproot::tr_unspendable;
+use crate::runtime::scope::{Mutable, ScopeRef};
+use crate::runtime::{Array, Error, Result, Value};
+use crate::util::DescriptorExt;
+use crate::{DescriptorDpk as Descriptor, MiniscriptDpk as Miniscript, PolicyDpk as Policy};
+
+/// The maximum number of spending paths enumerated for calculating the expected spend weight
+const MAX_SPENDING_PATHS: usize = 10_000;
+
+pub fn attach_stdlib(scope: &ScopeRef<Mutable>) {
+    let mut scope = scope.borrow_mut();
+    scope.set_fn("compile", fns::compile).unwrap();
+    scope.set_fn("compile::compare", fns::compare).unwrap();
+}
+
+/// Compilation reports are returned as a tagged list with the compiled descriptor, its size and
+/// spend cost, the Miniscript fragments used (as a comma-separated String) and the candidate that
+/// was picked. Weights are in witness units and include the
+/// scriptSig and witness, with the expected weight averaged over the policy spending paths
+/// according to their execution probabilities.
+///
+/// Options are provided as a tagged list with:
+/// - "minimize": "expected" for the lowest expected spend weight (the default), "weight" for
+///   the lowest worst-case spend weight or "size" for the smallest script. This only ranks two
+///   candidates: the compiler's result for the policy "as-written" and for the policy with all
+///   OR branches "equalized" to equal probabilities (when that differs)
+/// - "unspendable": the internal key to use for tr() when the policy has none (defaults to TR_UNSPENDABLE)
+#[allow(non_snake_case)]
+pub mod fns {
+    use super::*;
+
+    /// compile(Policy, String target="wsh", Array options=[]) -> Array<Tagged>
+    ///
+    /// With a target of "wsh", "sh-wsh", "sh" or "tr"
+    pub fn compile(args: Array, scope: &ScopeRef) -> Result<Value> {
+        let (policy, target, options): (Policy, Option<String>, Option<Value>) =
+            args.args_into()?;
+        let target = Target::try_from(target.as_deref().unwrap_or("wsh"))?;
+        let options = CompileOptions::from_value(options, &policy, target.is_tr(), scope)?;
+        Ok(compile_report(&policy, target, &options)?.into())
+    }
+
+    /// Compare the wsh, sh-wsh and tr encodings of the policy side-by-side
+    ///
+    /// compile::compare(Policy, Array options=[]) -> Array<Tagged>
+    pub fn compare(args: Array, scope: &ScopeRef) -> Result<Value> {
+        let (policy, options): (Policy, Option<Value>) = args.args_into()?;
+        let options = CompileOptions::from_value(options, &policy, true, scope)?;
+        let reports = [Target::Wsh, Target::ShWsh, Target::Tr]
+            .iter()
+            .map(|target| {
+                let report = compile_report(&policy, *target, &options)?;
+                Ok(Value::array(vec![target.name().into(), report.into()]))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Value::array(reports))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Target {
+    Wsh,
+    ShWsh,
+    Sh,
+    Tr,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Goal {
+    ExpectedWeight,
+    MaxWeight,
+    ScriptSize,
+}
+
+struct CompileOptions {
+    goal: Goal,
+    unspendable: Option<DescriptorPublicKey>,
+}
+
+struct CompileReport {
+    descriptor: Descriptor,
+    script_size: usize,
+    spk_size: usize,
+    expected_weight: f64,
+    max_weight: usize,
+    fragments: Vec<&'static str>,
+    candidate: &'static str,
+}
+
+// A set of policy conditions that are sufficient for spending together, with the probability of it being used
+struct SpendPath<'a> {
+    prob: f64,
+    conditions: Vec<&'a Policy>,
+}
+
+/// Compile candidate descriptors for the target and pick the best one according to the goal.
+/// The candidates are the compiler's result for the policy as-written and with all OR branches
+/// given equal probabilities. No other alternatives are explored.
+fn compile_report(
+    policy: &Policy,
+    target: Target,
+    options: &CompileOptions,
+) -> Result<CompileReport> {
+    let paths = spending_paths(policy)?;
+    let equalized = equalize_probs(policy);
+
+    let mut candidates = vec![("as-written", compile_target(policy, target, options)?)];
+    if equalized != *policy {
+        candidates.push(("equalized", compile_target(&equalized, target, options)?));
+    }
+
+    let mut best: Option<CompileReport> = None;
+    for (candidate, descriptor) in candidates {
+        let report = analyze(descriptor, candidate, &paths)?;
+        if best
+            .as_ref()
+            .is_none_or(|best| report.cost(options.goal) < best.cost(options.goal))
+        {
+            best = Some(report);
+        }
+    }
+    Ok(best.expect("there is always at least one candidate"))
+}
+
+fn compile_target(policy: &Policy, target: Target, options: &CompileOptions) -> Result<Descriptor> {
+    Ok(match target {
+        Target::Wsh => Descriptor::new_wsh(policy.compile()?)?,
+        Target::ShWsh => Descriptor::new_sh_wsh(policy.compile()?)?,
+        Target::Sh => Descriptor::new_sh(policy.compile()?)?,
+        Target::Tr => policy.compile_tr(options.unspendable.clone())?,
+    })
+}
+
+fn analyze(
+    descriptor: Descriptor,
+    candidate: &'static str,
+    paths: &[SpendPath],
+) -> Result<CompileReport> {
+    let definite = descriptor.at_derivation_index(0)?;
+
+    let mut fragments = vec![];
+    let script_size = match &descriptor {
+        Descriptor::Wsh(wsh) => match wsh.as_inner() {
+            WshInner::Ms(ms) => ms_fragments(ms, &mut fragments),
+            WshInner::SortedMulti(_) => descriptor.to_explicit_script()?.len(),
+        },
+        Descriptor::Sh(sh) => match sh.as_inner() {
+            ShInner::Wsh(wsh) => match wsh.as_inner() {
+                WshInner::Ms(ms) => ms_fragments(ms, &mut fragments),
+                WshInner::SortedMulti(_) => descriptor.to_explicit_script()?.len(),
+            },
+            ShInner::Ms(ms) => ms_fragments(ms, &mut fragments),
+            _ => descriptor.to_explicit_script()?.len(),
+        },
+        // The total size of all tapscript leaves
+        Descriptor::Tr(tr) => tr
+            .iter_scripts()
+            .map(|(_, ms)| ms_fragments(ms, &mut fragments))
+            .sum(),
+        _ => 0,
+    };
+
+    // Plans don't account for the witnessScript/redeemScript, which gets added here
+    let script_weight = match &descriptor {
+        Descriptor::Wsh(_) | Descriptor::Sh(_)
+            if descriptor.desc_type().segwit_version().is_some() =>
+        {
+            varint_len(script_size) + script_size
+        }
+        Descriptor::Sh(_) => {
+            let push_opcode_size = match script_size {
+                0..=75 => 1,
+                76..=255 => 2,
+                _ => 3,
+            };
+            4 * (push_opcode_size + script_size)
+        }
+        _ => 0,
+    };
+
+    let (mut expected_weight, mut max_weight) = (0.0, 0);
+    for path in paths {
+        let assets = path_assets(path);
+        let plan = match definite.clone().plan(&assets) {
+            Ok(plan) => plan,
+            Err(definite) => definite
+                .plan_mall(&assets)
+                .map_err(|_| Error::CompileUnsatisfiablePath)?,
+        };
+        let weight = plan.satisfaction_weight() + script_weight;
+        expected_weight += path.prob * weight as f64;
+        max_weight = max_weight.max(weight);
+    }
+
+    Ok(CompileReport {
+        spk_size: descriptor.to_script_pubkey()?.len(),
+        descriptor,
+        script_size,
+        expected_weight,
+        max_weight,
+        fragments,
+        candidate,
+    })
+}
+
+// Collect the distinct fragments used by the Miniscript (in pre-order), returning its script size
+fn ms_fragments<Ctx: ScriptContext>(
+    ms: &Miniscript<Ctx>,
+    fragments: &mut Vec<&'static str>,
+) -> usize {
+    for node in ms.iter() {
+        let name = fragment_name(&node.node);
+        if !fragments.contains(&name) {
+            fragments.push(name);
+        }
+    }
+    ms.script_size()
+}
+
+/// Enumerate the minimal sets of conditions that can satisfy the policy. OR branches are weighted by
+/// their execution probabilities, and all combinations of thresh() sub-policies as equally likely.
+fn spending_paths(policy: &Policy) -> Result<Vec<SpendPath<'_>>> {
+    Ok(match policy {
+        Policy::Unsatisfiable => vec![],
+        Policy::Trivial => vec![SpendPath::new(1.0, vec![])],
+        Policy::And(subs) => all_of(subs.iter().map(|sub| &**sub))?,
+        Policy::Or(subs) => {
+            let total_weight: usize = subs.iter().map(|(weight, _)| weight).sum();
+            let mut paths = vec![];
+            for (weight, sub) in subs {
+                for mut path in spending_paths(sub)? {
+                    path.prob *= *weight as f64 / total_weight as f64;
+                    paths.push(path);
+                }
+            }
+            paths
+        }
+        Policy::Threshold(k, subs) => {
+            let combinations = combinations(subs.len(), *k)?;
+            let combination_prob = 1.0 / combinations.len() as f64;
+            let mut paths = vec![];
+            for combination in combinations {
+                for mut path in all_of(combination.into_iter().map(|i| &*subs[i]))? {
+                    path.prob *= combination_prob;
+                    paths.push(path);
+                }
+                ensure!(
+                    paths.len() <= MAX_SPENDING_PATHS,
+                    Error::CompileTooManyPaths(MAX_SPENDING_PATHS)
+                );
+            }
+            paths
+        }
+        condition => vec![SpendPath::new(1.0, vec![condition])],
+    })
+}
+
+// The cartesian product of the spending paths of all sub-policies
+fn all_of<'a>(subs: impl Iterator<Item = &'a Policy>) -> Result<Vec<SpendPath<'a>>> {
+    let mut paths = vec![SpendPath::new(1.0, vec![])];
+    for sub in subs {
+        let sub_paths = spending_paths(sub)?;
+        ensure!(
+            paths.len() * sub_paths.len() <= MAX_SPENDING_PATHS,
+            Error::CompileTooManyPaths(MAX_SPENDING_PATHS)
+        );
+        paths = paths
+            .iter()
+            .flat_map(|path| {
+                sub_paths.iter().map(move |sub_path| {
+                    let conditions = [&path.conditions[..], &sub_path.conditions[..]].concat();
+                    SpendPath::new(path.prob * sub_path.prob, conditions)
+                })
+            })
+            .collect();
+    }
+    Ok(paths)
+}
+
+// All k-sized combinations of the 0..n indexes
+fn combinations(n: usize, k: usize) -> Result<Vec<Vec<usize>>> {
+    let mut combinations = vec![];
+    let mut indexes: Vec<usize> = (0..k).collect();
+    if k > n {
+        return Ok(combinations);
+    }
+    loop {
+        combinations.push(indexes.clone());
+        ensure!(
+            combinations.len() <= MAX_SPENDING_PATHS,
+            Error::CompileTooManyPaths(MAX_SPENDING_PATHS)
+        );
+        // Find the rightmost index that can be incremented, then reset the ones following it
+        let Some(i) = (0..k).rev().find(|&i| indexes[i] < n - k + i) else {
+            return Ok(combinations);
+        };
+        indexes[i] += 1;
+        for j in i + 1..k {
+            indexes[j] = indexes[j - 1] + 1;
+        }
+    }
+}
+
+// The assets available for satisfying the spending path (signatures, preimages and timelocks)
+fn path_assets(path: &SpendPath) -> Assets {
+    let mut assets = Assets::new();
+    for condition in &path.conditions {
+        assets = match condition {
+            Policy::Key(pk) => assets.add(pk.clone()),
+            Policy::Sha256(hash) => assets.add(*hash),
+            Policy::Hash256(hash) => assets.add(*hash),
+            Policy::Ripemd160(hash) => assets.add(*hash),
+            Policy::Hash160(hash) => assets.add(*hash),
+            Policy::Older(seq) => match assets.relative_timelock {
+                Some(current) if current >= *seq => assets,
+                _ => assets.older(*seq),
+            },
+            Policy::After(locktime) => match assets.absolute_timelock {
+                Some(current) if current.to_consensus_u32() >= locktime.to_consensus_u32() => {
+                    assets
+                }
+                _ => assets.after((*locktime).into()),
+            },
+            _ => assets,
+        };
+    }
+    assets
+}
+
+// Give all OR branches equal execution probabilities
+fn equalize_probs(policy: &Policy) -> Policy {
+    match policy {
+        Policy::Or(subs) => Policy::Or(
+            subs.iter()
+                .map(|(_, sub)| (1, Arc::new(equalize_probs(sub))))
+                .collect(),
+        ),
+        Policy::And(subs) => Policy::And(
+            subs.iter()
+                .map(|sub| Arc::new(equalize_probs(sub)))
+                .collect(),
+        ),
+        Policy::Threshold(k, subs) => Policy::Threshold(
+            *k,
+            subs.iter()
+                .map(|sub| Arc::new(equalize_probs(sub)))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+impl<'a> SpendPath<'a> {
+    fn new(prob: f64, conditions: Vec<&'a Policy>) -> Self {
+        SpendPath { prob, conditions }
+    }
+}
+
+impl CompileReport {
+    fn cost(&self, goal: Goal) -> f64 {
+        match goal {
+            Goal::ExpectedWeight => self.expected_weight,
+            Goal::MaxWeight => self.max_weight as f64,
+            Goal::ScriptSize => self.script_size as f64,
+        }
+    }
+}
+
+impl CompileOptions {
+    fn from_value(
+        options: Option<Value>,
+        policy: &Policy,
+        with_tr: bool,
+        scope: &ScopeRef,
+    ) -> Result<Self> {
+        let (goal, unspendable): (Option<String>, Option<DescriptorPublicKey>) = match options {
+            Some(options) => options.tagged_into2("minimize", "unspendable")?,
+            None => (None, None),
+        };
+        let goal = match goal.as_deref() {
+            None | Some("expected") => Goal::ExpectedWeight,
+            Some("weight") => Goal::MaxWeight,
+            Some("size") => Goal::ScriptSize,
+            Some(other) => bail!(Error::InvalidCompileGoal(other.to_string())),
+        };
+        let unspendable = match unspendable {
+            Some(unspendable) => Some(unspendable),
+            None if with_tr => tr_unspendable(scope, policy.clone().into())?,
+            None => None,
+        };
+        Ok(CompileOptions { goal, unspendable })
+    }
+}
+
+impl Target {
+    fn is_tr(&self) -> bool {
+        matches!(self, Target::Tr)
+    }
+    fn name(&self) -> &'static str {
+        match self {
+            Target::Wsh => "wsh",
+            Target::ShWsh => "sh-wsh",
+            Target::Sh => "sh",
+            Target::Tr => "tr",
+        }
+    }
+}
+
+impl TryFrom<&str> for Target {
+    type Error = Error;
+    fn try_from(target: &str) -> Result<Self> {
+        Ok(match target {
+            "wsh" => Target::Wsh,
+            "sh-wsh" => Target::ShWsh,
+            "sh" => Target::Sh,
+            "tr" => Target::Tr,
+            other => bail!(Error::InvalidCompileTarget(other.to_string())),
+        })
+    }
+}
+
+impl From<CompileReport> for Value {
+    fn from(report: CompileReport) -> Self {
+        Value::array(vec![
+            Value::array(vec!["descriptor".into(), report.descriptor.into()]),
+            Value::array(vec!["script_size".into(), report.script_size.into()]),
+            Value::array(vec!["spk_size".into(), report.spk_size.into()]),
+            Value::array(vec![
+                "expected_weight".into(),
+                report.expected_weight.into(),
+            ]),
+            Value::array(vec!["max_weight".into(), report.max_weight.into()]),
+            Value::array(vec!["fragments".into(), report.fragments.join(", ").into()]),
+            Value::array(vec!["candidate".into(), report.candidate.into()]),
+        ])
+    }
+}
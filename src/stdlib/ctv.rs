@@ -1,5 +1,8 @@
 use bitcoin::hashes::{sha256, Hash};
-use bitcoin::{Transaction, TxIn};
+use bitcoin::opcodes::all::OP_NOP4 as OP_CHECKTEMPLATEVERIFY;
+use bitcoin::script::{Builder as ScriptBuilder, ScriptBuf};
+use bitcoin::{absolute::LockTime, transaction::Version, Amount, OutPoint};
+use bitcoin::{Transaction, TxIn, TxOut};
 use miniscript::bitcoin;
 
 use crate::parser::Library;
@@ -11,7 +14,8 @@ lazy_static! {
         OP_CHECKTEMPLATEVERIFY = script(0xb3);
         OP_CTV = OP_CHECKTEMPLATEVERIFY;
 
-        fn ctv($tx) = `ctvHash($tx) OP_CHECKTEMPLATEVERIFY OP_DROP`;
+        fn ctv($tx) = ctvVerify(ctvHash($tx));
+        fn ctvVerify($hash) = `$hash OP_CHECKTEMPLATEVERIFY OP_DROP`;
     "#
     .parse()
     .unwrap();
@@ -21,6 +25,7 @@ pub fn attach_stdlib(scope: &ScopeRef<Mutable>) {
     {
         let mut scope = scope.borrow_mut();
         scope.set_fn("ctvHash", fns::ctvHash).unwrap();
+        scope.set_fn("ctv::tree", fns::ctvTree).unwrap();
     }
     MINSC_CTV_LIB.exec(scope).unwrap();
 }
@@ -28,7 +33,7 @@ pub fn attach_stdlib(scope: &ScopeRef<Mutable>) {
 #[allow(non_snake_case)]
 pub mod fns {
     use super::*;
-    use crate::runtime::Array;
+    use crate::runtime::{Array, Call, Function};
 
     /// ctvHash(Array|Transaction tx, Number input_index=0) -> Hash
     ///
@@ -46,6 +51,126 @@ pub mod fns {
         let hash = get_ctv_hash(&tx, input_index.unwrap_or(0));
         Ok(hash.into())
     }
+
+    /// ctv::tree(Array<TxOut> outputs, Number radix=4, Number fee=0, OutPoint prevout=None, Function wrap=wsh) -> (Script, Array<Transaction>)
+    ///
+    /// Build a congestion-control payment tree, where each transaction pays to up to `radix` outputs that are
+    /// either final payouts or CTV-locked to a child transaction. `fee` is reserved for every intermediate
+    /// transaction. Returns the root CTV script and all the transactions in the tree, root first. If the
+    /// funding `prevout` is known, the transactions inputs are linked to their parents.
+    ///
+    /// The CTV scripts of intermediate transactions are wrapped into an output using the `wrap` function, which
+    /// gets called with the Script and may return anything convertible to a scriptPubKey (P2WSH by default).
+    /// The root script is returned unwrapped and has to be wrapped by the caller in the same way.
+    ///
+    /// Example: ctv::tree([ $alice_pk: 1 BTC, $bob_pk: 0.5 BTC, ... ], 4, 500 sats, $funding_txid:0, |$s| tr(NUMS, $s))
+    pub fn ctvTree(args: Array, scope: &ScopeRef) -> Result<Value> {
+        let mut args = args.check_varlen(1, 5)?.into_iter();
+        let outputs: Vec<TxOut> = args.next_into()?;
+        let radix: Option<usize> = args.next_into()?;
+        let fee: Option<Amount> = args.next_into()?;
+        let prevout: Option<OutPoint> = args.next_into()?;
+        let wrap: Option<Function> = args.next_into()?;
+        let radix = radix.unwrap_or(4);
+        ensure!(!outputs.is_empty() && radix >= 2, Error::InvalidArguments);
+
+        let wrap = |script: ScriptBuf| match &wrap {
+            Some(wrap) => wrap.call(vec![script.into()], scope)?.into_spk(),
+            None => Ok(script.to_p2wsh()),
+        };
+        let root = CtvNode::build(outputs, radix, fee.unwrap_or(Amount::ZERO), &wrap)?;
+        let root_script = ctv_script(&root.tx);
+        let txs = root
+            .into_txs(prevout)
+            .into_iter()
+            .map(Value::from)
+            .collect();
+
+        Ok(Value::array(vec![root_script.into(), Value::array(txs)]))
+    }
+}
+
+/// A transaction in a CTV tree, with its children identified by the output index that funds them
+struct CtvNode {
+    tx: Transaction,
+    children: Vec<(u32, CtvNode)>,
+}
+
+impl CtvNode {
+    fn build(
+        mut outputs: Vec<TxOut>,
+        radix: usize,
+        fee: Amount,
+        wrap: &dyn Fn(ScriptBuf) -> Result<ScriptBuf>,
+    ) -> Result<Self> {
+        let mut children = vec![];
+        if outputs.len() > radix {
+            // Split the outputs into `radix` chunks, each paid to by a single output of this transaction.
+            // Chunks with a single output are paid directly, others get their own CTV-locked child transaction.
+            let chunk_size = outputs.len().div_ceil(radix);
+            let chunks = outputs
+                .chunks(chunk_size)
+                .map(<[_]>::to_vec)
+                .collect::<Vec<_>>();
+            outputs = chunks
+                .into_iter()
+                .enumerate()
+                .map(|(vout, mut chunk)| {
+                    if chunk.len() == 1 {
+                        return Ok(chunk.remove(0));
+                    }
+                    let child = CtvNode::build(chunk, radix, fee, wrap)?;
+                    let txout = TxOut {
+                        value: child.total_out()?.checked_add(fee).ok_or(Error::Overflow)?,
+                        script_pubkey: wrap(ctv_script(&child.tx))?,
+                    };
+                    children.push((vout as u32, child));
+                    Ok(txout)
+                })
+                .collect::<Result<_>>()?;
+        }
+        let tx = Transaction {
+            version: Version(2),
+            lock_time: LockTime::ZERO,
+            // The only input field committed to by the CTV hash is the nSequence, so the
+            // prevout can be left unset and filled in later once it is known.
+            input: vec![TxIn::default()],
+            output: outputs,
+        };
+        Ok(CtvNode { tx, children })
+    }
+
+    fn total_out(&self) -> Result<Amount> {
+        super::btc::sum_amounts(self.tx.output.iter().map(|out| out.value))
+    }
+
+    /// Flatten the tree into a list of transactions (breadth-first), linking child inputs
+    /// to their parent's outputs when the root prevout is available
+    fn into_txs(self, prevout: Option<OutPoint>) -> Vec<Transaction> {
+        let mut txs = vec![];
+        let mut queue = std::collections::VecDeque::from([(self, prevout)]);
+        while let Some((mut node, prevout)) = queue.pop_front() {
+            if let Some(prevout) = prevout {
+                node.tx.input[0].previous_output = prevout;
+            }
+            let txid = prevout.map(|_| node.tx.txid());
+            for (vout, child) in node.children {
+                queue.push_back((child, txid.map(|txid| OutPoint::new(txid, vout))));
+            }
+            txs.push(node.tx);
+        }
+        txs
+    }
+}
+
+/// The `<hash> OP_CHECKTEMPLATEVERIFY` script locking to the given transaction.
+/// The hash is left on the stack as the truthy final element.
+fn ctv_script(tx: &Transaction) -> ScriptBuf {
+    let hash = get_ctv_hash(tx, 0);
+    ScriptBuilder::new()
+        .push_slice(hash.to_byte_array())
+        .push_opcode(OP_CHECKTEMPLATEVERIFY)
+        .into_script()
 }
 
 // Copied from https://github.com/sapio-lang/sapio/blob/master/sapio-base/src/util.rs
@@ -0,0 +1,127 @@
+use std::convert::TryInto;
+
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::key::{PublicKey, TapTweak, XOnlyPublicKey};
+use bitcoin::secp256k1::{self, constants, Scalar};
+use bitcoin::taproot::TapNodeHash;
+use miniscript::{bitcoin, DescriptorPublicKey};
+
+use crate::runtime::scope::{Mutable, ScopeRef};
+use crate::runtime::{Array, Error, Result, Value};
+use crate::util::EC;
+
+lazy_static! {
+    /// The BIP341 NUMS point H with an even Y coordinate, lift_x(SHA256(G)) using the uncompressed generator point
+    pub static ref NUMS_H: secp256k1::PublicKey = {
+        let generator = [&[0x04], &constants::GENERATOR_X[..], &constants::GENERATOR_Y[..]].concat();
+        nums_key(&generator).public_key(secp256k1::Parity::Even)
+    };
+}
+
+pub fn attach_stdlib(scope: &ScopeRef<Mutable>) {
+    let mut scope = scope.borrow_mut();
+    scope.set_fn("ec::add", fns::add).unwrap();
+    scope.set_fn("ec::negate", fns::negate).unwrap();
+    scope.set_fn("ec::mulTweak", fns::mulTweak).unwrap();
+    scope.set_fn("ec::addTweak", fns::addTweak).unwrap();
+    scope.set_fn("ec::tapTweak", fns::tapTweak).unwrap();
+    scope.set_fn("ec::hasEvenY", fns::hasEvenY).unwrap();
+    scope.set_fn("ec::nums", fns::nums).unwrap();
+}
+
+/// Point operations work on the full (33 bytes) key. X-only keys are used with their even Y
+/// coordinate and BIP32 keys are derived at index 0 if they have a wildcard.
+#[allow(non_snake_case)]
+pub mod fns {
+    use super::*;
+
+    /// ec::add(PubKey, PubKey) -> PubKey
+    pub fn add(args: Array, _: &ScopeRef) -> Result<Value> {
+        let (a, b): (DescriptorPublicKey, DescriptorPublicKey) = args.args_into()?;
+        let sum = definite_key(a)?.combine(&definite_key(b)?)?;
+        Ok(PublicKey::new(sum).into())
+    }
+
+    /// ec::negate(PubKey) -> PubKey
+    pub fn negate(args: Array, _: &ScopeRef) -> Result<Value> {
+        let pk = definite_key(args.arg_into()?)?;
+        Ok(PublicKey::new(pk.negate(&EC)).into())
+    }
+
+    /// Multiply the point by the scalar tweak, t*P
+    ///
+    /// ec::mulTweak(PubKey, Bytes tweak) -> PubKey
+    pub fn mulTweak(args: Array, _: &ScopeRef) -> Result<Value> {
+        let (pk, tweak): (DescriptorPublicKey, Vec<u8>) = args.args_into()?;
+        let tweaked = definite_key(pk)?.mul_tweak(&EC, &to_scalar(tweak)?)?;
+        Ok(PublicKey::new(tweaked).into())
+    }
+
+    /// Add the scalar tweak multiplied by the generator point, P + t*G
+    ///
+    /// ec::addTweak(PubKey, Bytes tweak) -> PubKey
+    pub fn addTweak(args: Array, _: &ScopeRef) -> Result<Value> {
+        let (pk, tweak): (DescriptorPublicKey, Vec<u8>) = args.args_into()?;
+        let tweaked = definite_key(pk)?.add_exp_tweak(&EC, &to_scalar(tweak)?)?;
+        Ok(PublicKey::new(tweaked).into())
+    }
+
+    /// Get the BIP341 output key for the internal key and script tree merkle root.
+    /// An empty merkle root (as returned by tr::merkleRoot() for key-only trees) is treated as no script tree.
+    ///
+    /// ec::tapTweak(PubKey internal_key, Hash merkle_root=None) -> PubKey
+    pub fn tapTweak(args: Array, _: &ScopeRef) -> Result<Value> {
+        let (pk, merkle_root): (DescriptorPublicKey, Option<Vec<u8>>) = args.args_into()?;
+        let merkle_root = match merkle_root {
+            Some(bytes) if !bytes.is_empty() => {
+                Some(TapNodeHash::from_slice(&bytes).map_err(Error::InvalidMerkleRoot)?)
+            }
+            _ => None,
+        };
+        let internal_key = XOnlyPublicKey::from(definite_key(pk)?);
+        let (output_key, _parity) = internal_key.tap_tweak(&EC, merkle_root);
+        Ok(output_key.into())
+    }
+
+    /// Check whether the key's Y coordinate is even. Always true for x-only keys.
+    ///
+    /// ec::hasEvenY(PubKey) -> Bool
+    pub fn hasEvenY(args: Array, _: &ScopeRef) -> Result<Value> {
+        let pk = definite_key(args.arg_into()?)?;
+        Ok((pk.x_only_public_key().1 == secp256k1::Parity::Even).into())
+    }
+
+    /// Derive a provably unspendable (Nothing Up My Sleeve) x-only key from the seed, as
+    /// lift_x(SHA256(seed)), re-hashing until a valid X coordinate is found.
+    /// Using the uncompressed generator point as the seed gives the BIP341 NUMS key.
+    ///
+    /// ec::nums(Bytes|String seed) -> PubKey
+    pub fn nums(args: Array, _: &ScopeRef) -> Result<Value> {
+        Ok(nums_key(&args.arg_into::<Vec<u8>>()?).into())
+    }
+}
+
+/// Find the first valid X coordinate in the SHA256 hash chain starting at the seed. No discrete
+/// logarithm with respect to G is known for the resulting point.
+pub fn nums_key(seed: &[u8]) -> XOnlyPublicKey {
+    let mut hash = sha256::Hash::hash(seed);
+    loop {
+        // Roughly half of all X coordinates are on the curve
+        if let Ok(key) = XOnlyPublicKey::from_slice(hash.as_ref()) {
+            return key;
+        }
+        hash = sha256::Hash::hash(hash.as_ref());
+    }
+}
+
+// Derive the DescriptorPublicKey into a definite full key
+pub fn definite_key(pk: DescriptorPublicKey) -> Result<secp256k1::PublicKey> {
+    Ok(pk.at_derivation_index(0)?.derive_public_key(&EC)?.inner)
+}
+
+pub fn to_scalar(bytes: Vec<u8>) -> Result<Scalar> {
+    let bytes: [u8; 32] = bytes
+        .try_into()
+        .map_err(|bytes: Vec<u8>| Error::InvalidLength(bytes.len(), 32))?;
+    Scalar::from_be_bytes(bytes).map_err(|_| Error::InvalidScalar)
+}
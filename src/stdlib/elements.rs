@@ -0,0 +1,774 @@
+use std::convert::{TryFrom, TryInto};
+use std::fmt;
+
+use bitcoin::bech32::{Bech32, Bech32m, ByteIterExt, Checksum, Fe32, Fe32IterExt, Hrp};
+use bitcoin::consensus::encode::{Encodable, VarInt};
+use bitcoin::hashes::{sha256, sha256d, Hash, HashEngine};
+use bitcoin::hex::DisplayHex;
+use bitcoin::{absolute::LockTime, OutPoint, Script, ScriptBuf, Sequence, Witness};
+use miniscript::{bitcoin, DescriptorPublicKey};
+
+use super::ec::definite_key;
+use crate::runtime::scope::{Mutable, ScopeRef};
+use crate::runtime::{Array, Error, Result, Value};
+use crate::util::{self, fmt_list, PrettyDisplay};
+
+pub fn attach_stdlib(scope: &ScopeRef<Mutable>) {
+    let mut scope = scope.borrow_mut();
+    scope.set_fn("elements::tx", fns::tx).unwrap();
+    scope.set_fn("elements::txid", fns::txid).unwrap();
+    scope.set_fn("elements::address", fns::address).unwrap();
+    scope
+        .set_fn("elements::issuanceEntropy", fns::issuanceEntropy)
+        .unwrap();
+    scope.set_fn("elements::assetId", fns::assetId).unwrap();
+    scope
+        .set_fn("elements::reissuanceToken", fns::reissuanceToken)
+        .unwrap();
+    scope.set_fn("elements::sighash", fns::sighash).unwrap();
+    scope
+        .set_fn("elements::sighashPreimage", fns::sighashPreimage)
+        .unwrap();
+}
+
+#[allow(non_snake_case)]
+pub mod fns {
+    use super::*;
+
+    /// elements::tx(Array<Tagged>|ElementsTransaction) -> ElementsTransaction
+    ///
+    /// Example: elements::tx([ "inputs": [ $txid:0 ], "outputs": [ [ "script_pubkey": $spk, "asset": LBTC, "value": 9000 ], [ "asset": LBTC, "value": 1000 ] ] ])
+    pub fn tx(args: Array, _: &ScopeRef) -> Result<Value> {
+        Ok(Value::ElementsTransaction(args.arg_into()?))
+    }
+
+    /// elements::txid(ElementsTransaction) -> Bytes
+    pub fn txid(args: Array, _: &ScopeRef) -> Result<Value> {
+        let tx: ElementsTransaction = args.arg_into()?;
+        Ok(tx.txid().into())
+    }
+
+    /// Generate a segwit Elements address for the scriptPubKey. Confidential (blech32) addresses
+    /// are generated when a blinding key is provided, or unconfidential bech32 addresses otherwise.
+    ///
+    /// elements::address(Script|Descriptor|Address|TapInfo spk, PubKey blinding_key=None, String network="liquid") -> String
+    pub fn address(args: Array, _: &ScopeRef) -> Result<Value> {
+        let (spk, blinding_key, network): (Value, Option<DescriptorPublicKey>, Option<String>) =
+            args.args_into()?;
+        let blinding_key = blinding_key
+            .map(|pk| Ok::<_, Error>(definite_key(pk)?.serialize()))
+            .transpose()?;
+        let network = network.as_deref().unwrap_or("liquid");
+        Ok(segwit_address(&spk.into_spk()?, blinding_key, network)?.into())
+    }
+
+    /// Calculate the asset entropy for a new issuance spending `prevout`, committing to the contract hash.
+    ///
+    /// elements::issuanceEntropy(OutPoint prevout, Bytes contract_hash=0x00*32) -> Bytes
+    pub fn issuanceEntropy(args: Array, _: &ScopeRef) -> Result<Value> {
+        let (prevout, contract_hash): (OutPoint, Option<Vec<u8>>) = args.args_into()?;
+        let contract_hash = contract_hash.map_or(Ok([0u8; 32]), |h| h.as_slice().try_into())?;
+
+        let mut prevout_enc = sha256d::Hash::engine();
+        prevout.consensus_encode(&mut prevout_enc).unwrap();
+        let prevout_hash = sha256d::Hash::from_engine(prevout_enc).to_byte_array();
+
+        Ok(fast_merkle_pair(&prevout_hash, &contract_hash)
+            .to_vec()
+            .into())
+    }
+
+    /// elements::assetId(Bytes entropy) -> Bytes
+    pub fn assetId(args: Array, _: &ScopeRef) -> Result<Value> {
+        let entropy: Vec<u8> = args.arg_into()?;
+        Ok(fast_merkle_pair(entropy.as_slice().try_into()?, &[0u8; 32])
+            .to_vec()
+            .into())
+    }
+
+    /// elements::reissuanceToken(Bytes entropy, Bool confidential=false) -> Bytes
+    pub fn reissuanceToken(args: Array, _: &ScopeRef) -> Result<Value> {
+        let (entropy, confidential): (Vec<u8>, Option<bool>) = args.args_into()?;
+        let mut token_leaf = [0u8; 32];
+        token_leaf[0] = iif!(confidential.unwrap_or(false), 2, 1);
+        Ok(
+            fast_merkle_pair(entropy.as_slice().try_into()?, &token_leaf)
+                .to_vec()
+                .into(),
+        )
+    }
+
+    /// Compute the Elements segwit v0 signature hash, as verified by OP_CHECKSIG. This is the double-SHA256
+    /// of elements::sighashPreimage(), which OP_CHECKSIGFROMSTACK covenants can reconstruct and verify.
+    ///
+    /// elements::sighash(ElementsTransaction, Number input_index, Script script_code, Number|Bytes value, Number sighash_type=SIGHASH_ALL) -> Bytes
+    pub fn sighash(args: Array, _: &ScopeRef) -> Result<Value> {
+        let preimage = sighash_preimage_args(args)?;
+        Ok(sha256d::Hash::hash(&preimage).into())
+    }
+
+    /// elements::sighashPreimage(ElementsTransaction, Number input_index, Script script_code, Number|Bytes value, Number sighash_type=SIGHASH_ALL) -> Bytes
+    pub fn sighashPreimage(args: Array, _: &ScopeRef) -> Result<Value> {
+        Ok(sighash_preimage_args(args)?.into())
+    }
+
+    fn sighash_preimage_args(args: Array) -> Result<Vec<u8>> {
+        let mut args = args.check_varlen(4, 5)?.into_iter();
+        let tx: ElementsTransaction = args.next_into()?;
+        let input_index: usize = args.next_into()?;
+        let script_code: ScriptBuf = args.next_into()?;
+        let value: ConfValue = args.next_into()?;
+        let sighash_type: Option<u32> = args.next_into()?;
+        tx.segwitv0_sighash_preimage(input_index, &script_code, &value, sighash_type.unwrap_or(1))
+    }
+}
+
+//
+// Elements transactions
+//
+
+/// An Elements transaction, with support for explicit and confidential (committed) assets/values.
+/// Peg-ins are not supported.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ElementsTransaction {
+    pub version: u32,
+    pub lock_time: LockTime,
+    pub inputs: Vec<ElementsTxIn>,
+    pub outputs: Vec<ElementsTxOut>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ElementsTxIn {
+    pub previous_output: OutPoint,
+    pub script_sig: ScriptBuf,
+    pub sequence: Sequence,
+    pub issuance: Option<AssetIssuance>,
+    pub witness: Witness,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssetIssuance {
+    /// Zero for new issuances, or the asset blinding factor for reissuances
+    pub blinding_nonce: [u8; 32],
+    /// The contract hash for new issuances, or the asset entropy for reissuances
+    pub entropy: [u8; 32],
+    pub amount: ConfValue,
+    pub inflation_keys: ConfValue,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ElementsTxOut {
+    pub asset: ConfAsset,
+    pub value: ConfValue,
+    pub nonce: ConfNonce,
+    pub script_pubkey: ScriptBuf,
+    pub surjection_proof: Vec<u8>,
+    pub range_proof: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfAsset {
+    Null,
+    Explicit([u8; 32]),
+    Confidential([u8; 33]),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfValue {
+    Null,
+    Explicit(u64),
+    Confidential([u8; 33]),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfNonce {
+    Null,
+    Explicit([u8; 32]),
+    Confidential([u8; 33]),
+}
+
+const OUTPOINT_ISSUANCE_FLAG: u32 = 1 << 31;
+
+const SIGHASH_ALL: u32 = 1;
+const SIGHASH_NONE: u32 = 2;
+const SIGHASH_SINGLE: u32 = 3;
+const SIGHASH_ANYONECANPAY: u32 = 0x80;
+
+impl ElementsTransaction {
+    pub fn txid(&self) -> bitcoin::Txid {
+        bitcoin::Txid::from_raw_hash(sha256d::Hash::hash(&self.serialize(false)))
+    }
+
+    /// Serialize using the Elements consensus encoding, with or without the witness section
+    pub fn serialize(&self, include_witness: bool) -> Vec<u8> {
+        let include_witness = include_witness && self.has_witness();
+        let mut buf = vec![];
+        self.version.consensus_encode(&mut buf).unwrap();
+        buf.push(include_witness as u8);
+        VarInt(self.inputs.len() as u64)
+            .consensus_encode(&mut buf)
+            .unwrap();
+        for input in &self.inputs {
+            input.encode(&mut buf);
+        }
+        VarInt(self.outputs.len() as u64)
+            .consensus_encode(&mut buf)
+            .unwrap();
+        for output in &self.outputs {
+            output.encode(&mut buf);
+        }
+        self.lock_time.consensus_encode(&mut buf).unwrap();
+        if include_witness {
+            for input in &self.inputs {
+                // Issuance amount and inflation keys rangeproofs (unsupported, always empty),
+                // followed by the script witness and the (empty) peg-in witness
+                buf.extend([0, 0]);
+                input.witness.consensus_encode(&mut buf).unwrap();
+                buf.push(0);
+            }
+            for output in &self.outputs {
+                output.surjection_proof.consensus_encode(&mut buf).unwrap();
+                output.range_proof.consensus_encode(&mut buf).unwrap();
+            }
+        }
+        buf
+    }
+
+    /// Transaction weight, with non-witness bytes counted 4 times
+    pub fn weight(&self) -> usize {
+        self.serialize(false).len() * 3 + self.serialize(true).len()
+    }
+
+    fn has_witness(&self) -> bool {
+        self.inputs.iter().any(|i| !i.witness.is_empty())
+            || self
+                .outputs
+                .iter()
+                .any(|o| !o.surjection_proof.is_empty() || !o.range_proof.is_empty())
+    }
+
+    /// The BIP 143-like segwit v0 sighash preimage, extended by Elements to commit to issuances and to
+    /// the assets, value commitments and nonces of outputs. The SIGHASH_RANGEPROOF flag is not supported.
+    pub fn segwitv0_sighash_preimage(
+        &self,
+        input_index: usize,
+        script_code: &Script,
+        value: &ConfValue,
+        sighash_type: u32,
+    ) -> Result<Vec<u8>> {
+        let input = self
+            .inputs
+            .get(input_index)
+            .ok_or(Error::ArrayIndexOutOfRange)?;
+        ensure!(
+            *value != ConfValue::Null
+                && matches!(
+                    sighash_type & !SIGHASH_ANYONECANPAY,
+                    SIGHASH_ALL..=SIGHASH_SINGLE
+                ),
+            Error::InvalidArguments
+        );
+        let anyone_can_pay = sighash_type & SIGHASH_ANYONECANPAY != 0;
+        let base_type = sighash_type & 0x1f;
+
+        let hash_all = |f: &dyn Fn(&ElementsTxIn, &mut Vec<u8>)| {
+            let mut buf = vec![];
+            self.inputs.iter().for_each(|input| f(input, &mut buf));
+            sha256d::Hash::hash(&buf).to_byte_array()
+        };
+        let zero = [0u8; 32];
+
+        let (hash_prevouts, hash_sequence, hash_issuance) = if anyone_can_pay {
+            (zero, zero, zero)
+        } else {
+            let hash_prevouts = hash_all(&|i, buf| {
+                i.previous_output.consensus_encode(buf).unwrap();
+            });
+            let hash_sequence = if base_type != SIGHASH_SINGLE && base_type != SIGHASH_NONE {
+                hash_all(&|i, buf| {
+                    i.sequence.consensus_encode(buf).unwrap();
+                })
+            } else {
+                zero
+            };
+            let hash_issuance = hash_all(&|i, buf| match &i.issuance {
+                Some(issuance) => issuance.encode(buf),
+                None => buf.push(0),
+            });
+            (hash_prevouts, hash_sequence, hash_issuance)
+        };
+
+        let hash_outputs = if base_type != SIGHASH_SINGLE && base_type != SIGHASH_NONE {
+            let mut buf = vec![];
+            self.outputs
+                .iter()
+                .for_each(|output| output.encode(&mut buf));
+            sha256d::Hash::hash(&buf).to_byte_array()
+        } else if base_type == SIGHASH_SINGLE && input_index < self.outputs.len() {
+            let mut buf = vec![];
+            self.outputs[input_index].encode(&mut buf);
+            sha256d::Hash::hash(&buf).to_byte_array()
+        } else {
+            zero
+        };
+
+        let mut preimage = vec![];
+        self.version.consensus_encode(&mut preimage).unwrap();
+        preimage.extend(hash_prevouts);
+        preimage.extend(hash_sequence);
+        preimage.extend(hash_issuance);
+        input
+            .previous_output
+            .consensus_encode(&mut preimage)
+            .unwrap();
+        script_code.consensus_encode(&mut preimage).unwrap();
+        value.encode(&mut preimage);
+        input.sequence.consensus_encode(&mut preimage).unwrap();
+        if let Some(issuance) = &input.issuance {
+            issuance.encode(&mut preimage);
+        }
+        preimage.extend(hash_outputs);
+        self.lock_time.consensus_encode(&mut preimage).unwrap();
+        sighash_type.consensus_encode(&mut preimage).unwrap();
+        Ok(preimage)
+    }
+}
+
+impl ElementsTxIn {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        self.previous_output.txid.consensus_encode(buf).unwrap();
+        let vout =
+            self.previous_output.vout | iif!(self.issuance.is_some(), OUTPOINT_ISSUANCE_FLAG, 0);
+        vout.consensus_encode(buf).unwrap();
+        self.script_sig.consensus_encode(buf).unwrap();
+        self.sequence.consensus_encode(buf).unwrap();
+        if let Some(issuance) = &self.issuance {
+            issuance.encode(buf);
+        }
+    }
+}
+
+impl AssetIssuance {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.extend(self.blinding_nonce);
+        buf.extend(self.entropy);
+        self.amount.encode(buf);
+        self.inflation_keys.encode(buf);
+    }
+}
+
+impl ElementsTxOut {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        self.asset.encode(buf);
+        self.value.encode(buf);
+        self.nonce.encode(buf);
+        self.script_pubkey.consensus_encode(buf).unwrap();
+    }
+}
+
+impl ConfAsset {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        match self {
+            ConfAsset::Null => buf.push(0),
+            ConfAsset::Explicit(asset) => {
+                buf.push(1);
+                buf.extend(asset)
+            }
+            ConfAsset::Confidential(commitment) => buf.extend(commitment),
+        }
+    }
+}
+impl ConfValue {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        match self {
+            ConfValue::Null => buf.push(0),
+            // Explicit values are encoded as big-endian
+            ConfValue::Explicit(value) => {
+                buf.push(1);
+                buf.extend(value.to_be_bytes())
+            }
+            ConfValue::Confidential(commitment) => buf.extend(commitment),
+        }
+    }
+}
+impl ConfNonce {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        match self {
+            ConfNonce::Null => buf.push(0),
+            ConfNonce::Explicit(nonce) => {
+                buf.push(1);
+                buf.extend(nonce)
+            }
+            ConfNonce::Confidential(commitment) => buf.extend(commitment),
+        }
+    }
+}
+
+/// The Elements fast merkle root of two leaves: a single SHA256 compression of left||right, with no padding
+fn fast_merkle_pair(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut engine = sha256::HashEngine::default();
+    engine.input(left);
+    engine.input(right);
+    engine.midstate().to_byte_array()
+}
+
+//
+// Addresses
+//
+
+/// The blech32 checksum, used for confidential segwit v0 addresses
+enum Blech32 {}
+impl Checksum for Blech32 {
+    type MidstateRepr = u64;
+    const CHECKSUM_LENGTH: usize = 12;
+    const GENERATOR_SH: [u64; 5] = [
+        0x7d52fba40bd886,
+        0x5e8dbf1a03950c,
+        0x1c3a3c74072a18,
+        0x385d72fa0e5139,
+        0x7093e5a608865b,
+    ];
+    const TARGET_RESIDUE: u64 = 1;
+}
+
+/// The blech32m checksum, used for confidential segwit v1+ addresses
+enum Blech32m {}
+impl Checksum for Blech32m {
+    type MidstateRepr = u64;
+    const CHECKSUM_LENGTH: usize = Blech32::CHECKSUM_LENGTH;
+    const GENERATOR_SH: [u64; 5] = Blech32::GENERATOR_SH;
+    const TARGET_RESIDUE: u64 = 0x455972a3350f7a1;
+}
+
+/// Encode a segwit scriptPubKey as an Elements address, confidential if a blinding key is given
+fn segwit_address(spk: &Script, blinding_key: Option<[u8; 33]>, network: &str) -> Result<String> {
+    // (confidential hrp, unconfidential hrp)
+    let (conf_hrp, unconf_hrp) = match network {
+        "liquid" => ("lq", "ex"),
+        "liquidtestnet" => ("tlq", "tex"),
+        "elementsregtest" => ("el", "ert"),
+        other => bail!(Error::InvalidElementsNetwork(other.to_string())),
+    };
+    ensure!(
+        spk.is_witness_program(),
+        Error::ElementsAddressNotSegwit(spk.to_owned())
+    );
+    let version = spk.witness_version().expect("checked above");
+    let version_fe =
+        Fe32::try_from(version.to_num()).expect("witness versions are always valid field elements");
+    let hrp = |hrp| Hrp::parse(hrp);
+    let program = &spk.as_bytes()[2..];
+
+    Ok(match (blinding_key, version.to_num()) {
+        (Some(key), 0) => {
+            encode::<Blech32>(hrp(conf_hrp)?, version_fe, &[&key[..], program].concat())
+        }
+        (Some(key), _) => {
+            encode::<Blech32m>(hrp(conf_hrp)?, version_fe, &[&key[..], program].concat())
+        }
+        (None, 0) => encode::<Bech32>(hrp(unconf_hrp)?, version_fe, program),
+        (None, _) => encode::<Bech32m>(hrp(unconf_hrp)?, version_fe, program),
+    })
+}
+
+fn encode<Ck: Checksum>(hrp: Hrp, version: Fe32, payload: &[u8]) -> String {
+    let fes = payload.iter().copied().bytes_to_fes();
+    fes.with_checksum::<Ck>(&hrp)
+        .with_witness_version(version)
+        .chars()
+        .collect()
+}
+
+//
+// Conversions
+//
+
+// From tagged [ "version": $version, "locktime": $locktime, "inputs": [ .. ], "outputs": [ .. ] ]
+impl TryFrom<Value> for ElementsTransaction {
+    type Error = Error;
+    fn try_from(value: Value) -> Result<Self> {
+        Ok(match value {
+            Value::ElementsTransaction(tx) => tx,
+            Value::Array(_) => {
+                let mut tx = ElementsTransaction {
+                    version: 2,
+                    lock_time: LockTime::ZERO,
+                    inputs: vec![],
+                    outputs: vec![],
+                };
+                value.for_each_tag(|tag, val| {
+                    match tag {
+                        "version" => tx.version = val.try_into()?,
+                        "locktime" => tx.lock_time = val.try_into()?,
+                        "input" => tx.inputs.push(val.try_into()?),
+                        "output" => tx.outputs.push(val.try_into()?),
+                        "inputs" => tx.inputs.extend(val.into_vec_of()?),
+                        "outputs" => tx.outputs.extend(val.into_vec_of()?),
+                        _ => bail!(Error::TagUnknown),
+                    }
+                    Ok(())
+                })?;
+                tx
+            }
+            other => bail!(Error::NotElementsTxLike(other.into())),
+        })
+    }
+}
+
+// From tagged [ "prevout": $txid:$vout, "sequence": $seq, "script_sig": $script, "witness": [ .. ], "issuance": [ .. ] ]
+// or from $txid:$vout
+impl TryFrom<Value> for ElementsTxIn {
+    type Error = Error;
+    fn try_from(value: Value) -> Result<Self> {
+        let mut input = ElementsTxIn {
+            previous_output: OutPoint::null(),
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::default(),
+            issuance: None,
+            witness: Witness::new(),
+        };
+        if value.is_tagged_or_empty() {
+            value.for_each_tag(|tag, val| {
+                match tag {
+                    "prevout" => input.previous_output = val.try_into()?,
+                    "sequence" => input.sequence = val.try_into()?,
+                    "script_sig" => input.script_sig = val.try_into()?,
+                    "witness" => input.witness = val.try_into()?,
+                    "issuance" => input.issuance = Some(val.try_into()?),
+                    _ => bail!(Error::TagUnknown),
+                }
+                Ok(())
+            })?;
+        } else {
+            input.previous_output = value.try_into()?;
+        }
+        Ok(input)
+    }
+}
+
+// From tagged [ "amount": $amount, "inflation_keys": $keys, "entropy": $hash, "blinding_nonce": $nonce ]
+impl TryFrom<Value> for AssetIssuance {
+    type Error = Error;
+    fn try_from(value: Value) -> Result<Self> {
+        let (amount, inflation_keys, entropy, blinding_nonce) = value
+            .tagged_into4::<ConfValue, Option<ConfValue>, Option<Vec<u8>>, Option<Vec<u8>>>(
+                "amount",
+                "inflation_keys",
+                "entropy",
+                "blinding_nonce",
+            )?;
+        let bytes32 = |b: Option<Vec<u8>>| b.map_or(Ok([0u8; 32]), |b| b.as_slice().try_into());
+        Ok(AssetIssuance {
+            amount,
+            inflation_keys: inflation_keys.unwrap_or(ConfValue::Null),
+            entropy: bytes32(entropy)?,
+            blinding_nonce: bytes32(blinding_nonce)?,
+        })
+    }
+}
+
+// From tagged [ "script_pubkey": $spk, "asset": $asset, "value": $value, "nonce": $nonce, "surjection_proof": .., "range_proof": .. ]
+// The script_pubkey may be omitted for fee outputs.
+impl TryFrom<Value> for ElementsTxOut {
+    type Error = Error;
+    fn try_from(value: Value) -> Result<Self> {
+        let (mut asset, mut value_) = (None, None);
+        let mut output = ElementsTxOut {
+            asset: ConfAsset::Null,
+            value: ConfValue::Null,
+            nonce: ConfNonce::Null,
+            script_pubkey: ScriptBuf::new(),
+            surjection_proof: vec![],
+            range_proof: vec![],
+        };
+        value.for_each_tag(|tag, val| {
+            match tag {
+                "script_pubkey" => output.script_pubkey = val.into_spk()?,
+                "asset" => asset = Some(val.try_into()?),
+                "value" => value_ = Some(val.try_into()?),
+                "nonce" => output.nonce = val.try_into()?,
+                "surjection_proof" => output.surjection_proof = val.try_into()?,
+                "range_proof" => output.range_proof = val.try_into()?,
+                _ => bail!(Error::TagUnknown),
+            }
+            Ok(())
+        })?;
+        output.asset = asset.ok_or_else(|| missing_tag("asset"))?;
+        output.value = value_.ok_or_else(|| missing_tag("value"))?;
+        Ok(output)
+    }
+}
+
+fn missing_tag(tag: &str) -> Error {
+    Error::TagError(tag.to_string(), Error::MissingValue.into())
+}
+
+// Explicit assets are given as their 32 bytes ID, confidential ones as 33 bytes commitments
+impl TryFrom<Value> for ConfAsset {
+    type Error = Error;
+    fn try_from(value: Value) -> Result<Self> {
+        Ok(match value {
+            Value::Bytes(bytes) if bytes.len() == 32 => ConfAsset::Explicit(bytes[..].try_into()?),
+            Value::Bytes(bytes) if bytes.len() == 33 && matches!(bytes[0], 0x0a | 0x0b) => {
+                ConfAsset::Confidential(bytes[..].try_into()?)
+            }
+            other => bail!(Error::InvalidConfidential("asset", other.into())),
+        })
+    }
+}
+
+// Explicit values are given as numbers, confidential ones as 33 bytes commitments
+impl TryFrom<Value> for ConfValue {
+    type Error = Error;
+    fn try_from(value: Value) -> Result<Self> {
+        Ok(match value {
+            Value::Number(num) => ConfValue::Explicit(num.into_u64()?),
+            Value::Bytes(bytes) if bytes.len() == 33 && matches!(bytes[0], 0x08 | 0x09) => {
+                ConfValue::Confidential(bytes[..].try_into()?)
+            }
+            other => bail!(Error::InvalidConfidential("value", other.into())),
+        })
+    }
+}
+
+// Nonces are typically the 33 bytes ECDH pubkey used for blinding
+impl TryFrom<Value> for ConfNonce {
+    type Error = Error;
+    fn try_from(value: Value) -> Result<Self> {
+        Ok(match value {
+            Value::Bytes(bytes) if bytes.len() == 32 => ConfNonce::Explicit(bytes[..].try_into()?),
+            Value::Bytes(bytes) if bytes.len() == 33 && matches!(bytes[0], 0x02 | 0x03) => {
+                ConfNonce::Confidential(bytes[..].try_into()?)
+            }
+            other => bail!(Error::InvalidConfidential("nonce", other.into())),
+        })
+    }
+}
+
+impl From<ElementsTransaction> for Value {
+    fn from(tx: ElementsTransaction) -> Self {
+        Value::ElementsTransaction(tx)
+    }
+}
+
+//
+// Display
+//
+
+impl PrettyDisplay for ElementsTransaction {
+    const AUTOFMT_ENABLED: bool = true;
+    const MAX_ONELINER_LENGTH: usize = 200;
+
+    fn pretty_fmt<W: fmt::Write>(&self, f: &mut W, indent: Option<usize>) -> fmt::Result {
+        let (newline_or_space, inner_indent, indent_w, inner_indent_w) =
+            util::indentation_params(indent);
+        let field_sep = format!("{newline_or_space}{:inner_indent_w$}", "");
+        write!(f, r#"elements::tx([{field_sep}"version": {}"#, self.version)?;
+        if self.lock_time != LockTime::ZERO {
+            write!(f, r#",{field_sep}"locktime": {}"#, self.lock_time)?;
+        }
+        if !self.inputs.is_empty() {
+            write!(f, r#",{field_sep}"inputs": "#)?;
+            fmt_list(f, self.inputs.iter(), inner_indent, |f, input, _| {
+                // Individual inputs are always displayed as one-liners
+                if input.sequence == Sequence::default()
+                    && input.script_sig.is_empty()
+                    && input.witness.is_empty()
+                    && input.issuance.is_none()
+                {
+                    return write!(f, "{}", input.previous_output);
+                }
+                write!(f, r#"[ "prevout": {}"#, input.previous_output)?;
+                if input.sequence != Sequence::default() {
+                    write!(f, r#", "sequence": {}"#, input.sequence)?;
+                }
+                if !input.script_sig.is_empty() {
+                    write!(f, r#", "script_sig": {}"#, input.script_sig.pretty(None))?;
+                }
+                if !input.witness.is_empty() {
+                    write!(f, r#", "witness": {}"#, input.witness.pretty(None))?;
+                }
+                if let Some(issuance) = &input.issuance {
+                    write!(f, r#", "issuance": [ "amount": {}"#, issuance.amount)?;
+                    if issuance.inflation_keys != ConfValue::Null {
+                        write!(f, r#", "inflation_keys": {}"#, issuance.inflation_keys)?;
+                    }
+                    if issuance.entropy != [0u8; 32] {
+                        write!(f, r#", "entropy": 0x{}"#, issuance.entropy.as_hex())?;
+                    }
+                    if issuance.blinding_nonce != [0u8; 32] {
+                        write!(
+                            f,
+                            r#", "blinding_nonce": 0x{}"#,
+                            issuance.blinding_nonce.as_hex()
+                        )?;
+                    }
+                    write!(f, " ]")?;
+                }
+                write!(f, " ]")
+            })?;
+        }
+        if !self.outputs.is_empty() {
+            write!(f, r#",{field_sep}"outputs": "#)?;
+            fmt_list(f, self.outputs.iter(), inner_indent, |f, output, _| {
+                // Individual outputs are always displayed as one-liners
+                write!(f, "[ ")?;
+                if !output.script_pubkey.is_empty() {
+                    write!(
+                        f,
+                        r#""script_pubkey": {}, "#,
+                        output.script_pubkey.pretty(None)
+                    )?;
+                }
+                write!(f, r#""asset": {}, "value": {}"#, output.asset, output.value)?;
+                if output.nonce != ConfNonce::Null {
+                    write!(f, r#", "nonce": {}"#, output.nonce)?;
+                }
+                if !output.surjection_proof.is_empty() {
+                    write!(
+                        f,
+                        r#", "surjection_proof": 0x{}"#,
+                        output.surjection_proof.as_hex()
+                    )?;
+                }
+                if !output.range_proof.is_empty() {
+                    write!(f, r#", "range_proof": 0x{}"#, output.range_proof.as_hex())?;
+                }
+                write!(f, " ]")
+            })?;
+        }
+        write!(f, "{newline_or_space}{:indent_w$}])", "")
+    }
+
+    fn prefer_multiline_anyway(&self) -> bool {
+        (self.inputs.len() + self.outputs.len()) > 2
+    }
+}
+
+impl fmt::Display for ConfAsset {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfAsset::Null => write!(f, "null"),
+            ConfAsset::Explicit(asset) => write!(f, "0x{}", asset.as_hex()),
+            ConfAsset::Confidential(commitment) => write!(f, "0x{}", commitment.as_hex()),
+        }
+    }
+}
+impl fmt::Display for ConfValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfValue::Null => write!(f, "null"),
+            ConfValue::Explicit(value) => write!(f, "{}", value),
+            ConfValue::Confidential(commitment) => write!(f, "0x{}", commitment.as_hex()),
+        }
+    }
+}
+impl fmt::Display for ConfNonce {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfNonce::Null => write!(f, "null"),
+            ConfNonce::Explicit(nonce) => write!(f, "0x{}", nonce.as_hex()),
+            ConfNonce::Confidential(commitment) => write!(f, "0x{}", commitment.as_hex()),
+        }
+    }
+}
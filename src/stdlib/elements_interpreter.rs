@@ -0,0 +1,755 @@
+use std::convert::TryFrom;
+
+use bitcoin::hashes::{hash160, ripemd160, sha1, sha256, sha256d, Hash};
+use bitcoin::hex::DisplayHex;
+use bitcoin::opcodes::{all::*, Class, ClassifyContext};
+use bitcoin::script::{write_scriptint, Instruction, Script};
+use bitcoin::{Opcode, Witness};
+use miniscript::bitcoin;
+
+use super::btc::SCRIPT_MARKER_MAGIC_BYTES;
+use super::elements::{ConfAsset, ConfNonce, ConfValue, ElementsTransaction, ElementsTxOut};
+use super::script_marker::{MarkerItem, ScriptMarker};
+use crate::runtime::scope::{Mutable, ScopeRef};
+use crate::runtime::{Array, Error, Result, Value};
+use crate::util::PrettyDisplay;
+
+pub fn attach_stdlib(scope: &ScopeRef<Mutable>) {
+    let mut scope = scope.borrow_mut();
+    scope.set_fn("elements::exec", fns::exec).unwrap();
+}
+
+#[allow(non_snake_case)]
+pub mod fns {
+    use super::*;
+
+    /// Execute an Elements tapscript against a transaction context, with support for the
+    /// introspection and 64-bit arithmetic opcodes
+    ///
+    /// elements::exec(Script, Array<Tagged> context, Array<Bytes> witness=[]) -> Array<Tagged>
+    ///
+    /// The context is a tagged list with the spending "tx", the "prevouts" being spent (as tagged
+    /// outputs with their asset, value and script_pubkey), the current "input_index" (defaults to 0)
+    /// and "mock_signatures" (defaults to false) to consider all non-empty signatures as valid.
+    ///
+    /// Returns whether execution succeeded with a single true element left on the stack, the final
+    /// "stack", and on failure the "error" reason with the "opcode" and script "position" where it
+    /// occurred, and the last preceding mark::comment()/mark::label() as the "marker" (if any).
+    pub fn exec(args: Array, _: &ScopeRef) -> Result<Value> {
+        let (script, ctx, witness): (Value, ExecContext, Option<Witness>) = args.args_into()?;
+        let script = script.into_script()?;
+        let stack = witness.map_or_else(Vec::new, |w| w.to_vec());
+        run(&script, &ctx, stack)
+    }
+}
+
+/// The transaction context available to the introspection opcodes
+#[derive(Debug, Clone)]
+pub struct ExecContext {
+    pub tx: ElementsTransaction,
+    pub prevouts: Vec<ElementsTxOut>,
+    pub input_index: usize,
+    pub mock_signatures: bool,
+}
+
+// From tagged [ "tx": $tx, "prevouts": [ .. ], "input_index": $index, "mock_signatures": $bool ]
+impl TryFrom<Value> for ExecContext {
+    type Error = Error;
+    fn try_from(value: Value) -> Result<Self> {
+        let (tx, prevouts, input_index, mock_signatures) = value
+            .tagged_into4::<ElementsTransaction, Option<Vec<ElementsTxOut>>, Option<usize>, Option<bool>>(
+                "tx",
+                "prevouts",
+                "input_index",
+                "mock_signatures",
+            )?;
+        let prevouts = prevouts.unwrap_or_default();
+        ensure!(
+            prevouts.is_empty() || prevouts.len() == tx.inputs.len(),
+            Error::ElementsPrevoutsMismatch(prevouts.len(), tx.inputs.len())
+        );
+        let input_index = input_index.unwrap_or(0);
+        ensure!(input_index < tx.inputs.len(), Error::ArrayIndexOutOfRange);
+        Ok(ExecContext {
+            tx,
+            prevouts,
+            input_index,
+            mock_signatures: mock_signatures.unwrap_or(false),
+        })
+    }
+}
+
+// Elements opcodes, re-using the bitcoin OP_SUCCESSx (OP_RETURN_x) opcode numbers
+const OP_CHECKSIGFROMSTACK: Opcode = OP_RETURN_193;
+const OP_CHECKSIGFROMSTACKVERIFY: Opcode = OP_RETURN_194;
+const OP_INSPECTINPUTOUTPOINT: Opcode = OP_RETURN_199;
+const OP_INSPECTINPUTASSET: Opcode = OP_RETURN_200;
+const OP_INSPECTINPUTVALUE: Opcode = OP_RETURN_201;
+const OP_INSPECTINPUTSCRIPTPUBKEY: Opcode = OP_RETURN_202;
+const OP_INSPECTINPUTSEQUENCE: Opcode = OP_RETURN_203;
+const OP_PUSHCURRENTINPUTINDEX: Opcode = OP_RETURN_205;
+const OP_INSPECTOUTPUTASSET: Opcode = OP_RETURN_206;
+const OP_INSPECTOUTPUTVALUE: Opcode = OP_RETURN_207;
+const OP_INSPECTOUTPUTNONCE: Opcode = OP_RETURN_208;
+const OP_INSPECTOUTPUTSCRIPTPUBKEY: Opcode = OP_RETURN_209;
+const OP_INSPECTVERSION: Opcode = OP_RETURN_210;
+const OP_INSPECTLOCKTIME: Opcode = OP_RETURN_211;
+const OP_INSPECTNUMINPUTS: Opcode = OP_RETURN_212;
+const OP_INSPECTNUMOUTPUTS: Opcode = OP_RETURN_213;
+const OP_TXWEIGHT: Opcode = OP_RETURN_214;
+const OP_ADD64: Opcode = OP_RETURN_215;
+const OP_SUB64: Opcode = OP_RETURN_216;
+const OP_MUL64: Opcode = OP_RETURN_217;
+const OP_DIV64: Opcode = OP_RETURN_218;
+const OP_NEG64: Opcode = OP_RETURN_219;
+const OP_LESSTHAN64: Opcode = OP_RETURN_220;
+const OP_LESSTHANOREQUAL64: Opcode = OP_RETURN_221;
+const OP_GREATERTHAN64: Opcode = OP_RETURN_222;
+const OP_GREATERTHANOREQUAL64: Opcode = OP_RETURN_223;
+const OP_SCRIPTNUMTOLE64: Opcode = OP_RETURN_224;
+const OP_LE64TOSCRIPTNUM: Opcode = OP_RETURN_225;
+const OP_LE32TOLE64: Opcode = OP_RETURN_226;
+
+const MAX_STACK_SIZE: usize = 1000;
+const MAX_ELEMENT_SIZE: usize = 520;
+
+/// The reason execution failed, reported back to the user
+type Failure = String;
+
+macro_rules! fail {
+    ($($arg:tt)*) => { return Err(format!($($arg)*)) };
+}
+
+fn run(script: &Script, ctx: &ExecContext, stack: Vec<Vec<u8>>) -> Result<Value> {
+    let instructions = script
+        .instruction_indices()
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    let markers = find_markers(script);
+
+    let mut exec = Exec {
+        ctx,
+        stack,
+        altstack: vec![],
+        conditions: vec![],
+    };
+    for (pos, instruction) in &instructions {
+        if let Err(reason) = exec.step(instruction) {
+            let opcode = match instruction {
+                Instruction::Op(op) => op.pretty(None).to_string(),
+                Instruction::PushBytes(_) => "PUSH".to_string(),
+            };
+            let marker = markers
+                .iter()
+                .rev()
+                .find(|(marker_pos, _)| marker_pos < pos);
+            let mut failure = vec![tagged("opcode", opcode), tagged("position", *pos)];
+            failure.extend(marker.map(|(_, body)| tagged("marker", body.to_string())));
+            return Ok(exec.into_result(Some(reason), failure));
+        }
+    }
+    let reason = if !exec.conditions.is_empty() {
+        Some("Unbalanced conditional, missing OP_ENDIF".to_string())
+    } else if exec.stack.len() != 1 || !cast_to_bool(&exec.stack[0]) {
+        Some("Script did not leave exactly one true element on the stack".to_string())
+    } else {
+        None
+    };
+    Ok(exec.into_result(reason, vec![]))
+}
+
+struct Exec<'a> {
+    ctx: &'a ExecContext,
+    stack: Vec<Vec<u8>>,
+    altstack: Vec<Vec<u8>>,
+    /// The OP_IF/OP_NOTIF branches being executed, with whether each of them is taken
+    conditions: Vec<bool>,
+}
+
+impl Exec<'_> {
+    fn into_result(self, error: Option<Failure>, failure: Vec<Value>) -> Value {
+        let stack = self.stack.into_iter().map(Value::Bytes).collect::<Vec<_>>();
+        let mut result = vec![tagged("success", error.is_none()), tagged("stack", stack)];
+        if let Some(error) = error {
+            result.push(tagged("error", error));
+        }
+        result.extend(failure);
+        Value::array(result)
+    }
+
+    fn step(&mut self, instruction: &Instruction) -> std::result::Result<(), Failure> {
+        let executing = self.conditions.iter().all(|taken| *taken);
+        match instruction {
+            Instruction::PushBytes(push) if executing => self.push(push.as_bytes().to_vec()),
+            Instruction::PushBytes(_) => {}
+            Instruction::Op(op) => match *op {
+                OP_IF | OP_NOTIF | OP_ELSE | OP_ENDIF => self.conditional(*op, executing)?,
+                op if executing => self.op(op)?,
+                _ => {}
+            },
+        }
+        if self.stack.len() + self.altstack.len() > MAX_STACK_SIZE {
+            fail!("Stack size limit exceeded");
+        }
+        Ok(())
+    }
+
+    fn conditional(&mut self, op: Opcode, executing: bool) -> std::result::Result<(), Failure> {
+        match op {
+            OP_IF | OP_NOTIF => {
+                let mut taken = false;
+                if executing {
+                    // Tapscript requires the MINIMALIF rule
+                    taken = match self.pop()?.as_slice() {
+                        [] => false,
+                        [1] => true,
+                        other => fail!("Non-minimal OP_IF argument 0x{}", other.as_hex()),
+                    };
+                    taken ^= op == OP_NOTIF;
+                }
+                self.conditions.push(taken);
+            }
+            OP_ELSE => match self.conditions.last_mut() {
+                Some(taken) => *taken = !*taken,
+                None => fail!("Unbalanced conditional, OP_ELSE without OP_IF"),
+            },
+            _ => {
+                if self.conditions.pop().is_none() {
+                    fail!("Unbalanced conditional, OP_ENDIF without OP_IF");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn op(&mut self, op: Opcode) -> std::result::Result<(), Failure> {
+        let ctx = self.ctx;
+        match op {
+            // Flow control and no-ops
+            OP_NOP | OP_NOP1 | OP_NOP4 | OP_NOP5 | OP_NOP6 | OP_NOP7 | OP_NOP8 | OP_NOP9
+            | OP_NOP10 | OP_CODESEPARATOR => {}
+            OP_VERIFY => {
+                if !self.pop_bool()? {
+                    fail!("Verification failed, the top stack element is false");
+                }
+            }
+            OP_RETURN => fail!("OP_RETURN encountered"),
+            OP_CLTV => self.check_locktime()?,
+            OP_CSV => self.check_sequence()?,
+
+            // Stack manipulation
+            OP_TOALTSTACK => {
+                let item = self.pop()?;
+                self.altstack.push(item);
+            }
+            OP_FROMALTSTACK => match self.altstack.pop() {
+                Some(item) => self.push(item),
+                None => fail!("Altstack underflow"),
+            },
+            OP_DROP => {
+                self.pop()?;
+            }
+            OP_2DROP => {
+                self.pop_n(2)?;
+            }
+            OP_DUP => self.rearrange(1, &[0, 0])?,
+            OP_2DUP => self.rearrange(2, &[1, 0, 1, 0])?,
+            OP_3DUP => self.rearrange(3, &[2, 1, 0, 2, 1, 0])?,
+            OP_OVER => self.rearrange(2, &[1, 0, 1])?,
+            OP_2OVER => self.rearrange(4, &[3, 2, 1, 0, 3, 2])?,
+            OP_NIP => self.rearrange(2, &[0])?,
+            OP_SWAP => self.rearrange(2, &[0, 1])?,
+            OP_2SWAP => self.rearrange(4, &[1, 0, 3, 2])?,
+            OP_ROT => self.rearrange(3, &[1, 0, 2])?,
+            OP_2ROT => self.rearrange(6, &[3, 2, 1, 0, 5, 4])?,
+            OP_TUCK => self.rearrange(2, &[0, 1, 0])?,
+            OP_IFDUP => {
+                let top = self.peek(0)?.clone();
+                if cast_to_bool(&top) {
+                    self.push(top);
+                }
+            }
+            OP_DEPTH => self.push_num(self.stack.len() as i64),
+            OP_SIZE => self.push_num(self.peek(0)?.len() as i64),
+            OP_PICK | OP_ROLL => {
+                let n = self.pop_num()?;
+                if n < 0 || n as usize >= self.stack.len() {
+                    fail!("Invalid {} depth {}", op.pretty(None), n);
+                }
+                let index = self.stack.len() - 1 - n as usize;
+                let item = iif!(
+                    op == OP_PICK,
+                    self.stack[index].clone(),
+                    self.stack.remove(index)
+                );
+                self.push(item);
+            }
+
+            // Splice and bitwise operations, re-enabled by Elements
+            OP_CAT => {
+                let (a, b) = self.pop2()?;
+                if a.len() + b.len() > MAX_ELEMENT_SIZE {
+                    fail!("OP_CAT result exceeds the maximum element size");
+                }
+                self.push([a, b].concat());
+            }
+            OP_SUBSTR | OP_LEFT | OP_RIGHT => {
+                let (start, len) = match op {
+                    OP_SUBSTR => {
+                        let len = self.pop_num()?;
+                        (self.pop_num()?, Some(len))
+                    }
+                    OP_LEFT => (0, Some(self.pop_num()?)),
+                    _ => (self.pop_num()?, None),
+                };
+                let item = self.pop()?;
+                if start < 0 || len.is_some_and(|len| len < 0) {
+                    fail!("Negative {} position", op.pretty(None));
+                }
+                let start = (start as usize).min(item.len());
+                let end = len.map_or(item.len(), |len| (start + len as usize).min(item.len()));
+                self.push(item[start..end].to_vec());
+            }
+            OP_INVERT => {
+                let item = self.pop()?;
+                self.push(item.iter().map(|b| !b).collect());
+            }
+            OP_AND | OP_OR | OP_XOR => {
+                let (a, b) = self.pop2()?;
+                if a.len() != b.len() {
+                    fail!("{} operands of different sizes", op.pretty(None));
+                }
+                let bitwise = |(a, b): (&u8, &u8)| match op {
+                    OP_AND => a & b,
+                    OP_OR => a | b,
+                    _ => a ^ b,
+                };
+                self.push(a.iter().zip(&b).map(bitwise).collect());
+            }
+
+            OP_EQUAL => {
+                let (a, b) = self.pop2()?;
+                self.push_bool(a == b);
+            }
+            OP_EQUALVERIFY => {
+                let (a, b) = self.pop2()?;
+                if a != b {
+                    fail!("Not equal: 0x{} != 0x{}", a.as_hex(), b.as_hex());
+                }
+            }
+
+            // Arithmetic on (up to 4 bytes) script numbers
+            OP_1ADD | OP_1SUB | OP_NEGATE | OP_ABS | OP_NOT | OP_0NOTEQUAL => {
+                let n = self.pop_num()?;
+                match op {
+                    OP_1ADD => self.push_num(n + 1),
+                    OP_1SUB => self.push_num(n - 1),
+                    OP_NEGATE => self.push_num(-n),
+                    OP_ABS => self.push_num(n.abs()),
+                    OP_NOT => self.push_bool(n == 0),
+                    _ => self.push_bool(n != 0),
+                }
+            }
+            OP_ADD
+            | OP_SUB
+            | OP_BOOLAND
+            | OP_BOOLOR
+            | OP_NUMEQUAL
+            | OP_NUMNOTEQUAL
+            | OP_LESSTHAN
+            | OP_GREATERTHAN
+            | OP_LESSTHANOREQUAL
+            | OP_GREATERTHANOREQUAL
+            | OP_MIN
+            | OP_MAX => {
+                let b = self.pop_num()?;
+                let a = self.pop_num()?;
+                match op {
+                    OP_ADD => self.push_num(a + b),
+                    OP_SUB => self.push_num(a - b),
+                    OP_MIN => self.push_num(a.min(b)),
+                    OP_MAX => self.push_num(a.max(b)),
+                    OP_BOOLAND => self.push_bool(a != 0 && b != 0),
+                    OP_BOOLOR => self.push_bool(a != 0 || b != 0),
+                    OP_NUMEQUAL => self.push_bool(a == b),
+                    OP_NUMNOTEQUAL => self.push_bool(a != b),
+                    OP_LESSTHAN => self.push_bool(a < b),
+                    OP_GREATERTHAN => self.push_bool(a > b),
+                    OP_LESSTHANOREQUAL => self.push_bool(a <= b),
+                    _ => self.push_bool(a >= b),
+                }
+            }
+            OP_NUMEQUALVERIFY => {
+                let b = self.pop_num()?;
+                let a = self.pop_num()?;
+                if a != b {
+                    fail!("Numbers not equal: {} != {}", a, b);
+                }
+            }
+            OP_WITHIN => {
+                let max = self.pop_num()?;
+                let min = self.pop_num()?;
+                let n = self.pop_num()?;
+                self.push_bool(min <= n && n < max);
+            }
+
+            // Hashing
+            OP_RIPEMD160 => self.hash_top(|d| ripemd160::Hash::hash(d).to_byte_array().to_vec())?,
+            OP_SHA1 => self.hash_top(|d| sha1::Hash::hash(d).to_byte_array().to_vec())?,
+            OP_SHA256 => self.hash_top(|d| sha256::Hash::hash(d).to_byte_array().to_vec())?,
+            OP_HASH160 => self.hash_top(|d| hash160::Hash::hash(d).to_byte_array().to_vec())?,
+            OP_HASH256 => self.hash_top(|d| sha256d::Hash::hash(d).to_byte_array().to_vec())?,
+
+            // Signatures can only be mocked, considering all non-empty signatures as valid
+            OP_CHECKSIG
+            | OP_CHECKSIGVERIFY
+            | OP_CHECKSIGADD
+            | OP_CHECKSIGFROMSTACK
+            | OP_CHECKSIGFROMSTACKVERIFY => {
+                if !ctx.mock_signatures {
+                    fail!("Signature verification is unsupported, enable mock_signatures");
+                }
+                let pubkey = self.pop()?;
+                if matches!(op, OP_CHECKSIGFROMSTACK | OP_CHECKSIGFROMSTACKVERIFY) {
+                    self.pop()?; // the message
+                }
+                let acc = iif!(op == OP_CHECKSIGADD, Some(self.pop_num()?), None);
+                let valid = !self.pop()?.is_empty();
+                if pubkey.is_empty() {
+                    fail!("Empty public key");
+                }
+                match (op, acc) {
+                    (_, Some(acc)) => self.push_num(acc + valid as i64),
+                    (OP_CHECKSIGVERIFY | OP_CHECKSIGFROMSTACKVERIFY, _) if !valid => {
+                        fail!("Empty signature")
+                    }
+                    (OP_CHECKSIGVERIFY | OP_CHECKSIGFROMSTACKVERIFY, _) => {}
+                    _ => self.push_bool(valid),
+                }
+            }
+
+            // Elements transaction introspection
+            OP_INSPECTINPUTOUTPOINT => {
+                let input = &ctx.tx.inputs[self.pop_input_index()?];
+                let flag = iif!(input.issuance.is_some(), 0x80, 0x00);
+                self.push(input.previous_output.txid.to_byte_array().to_vec());
+                self.push(input.previous_output.vout.to_le_bytes().to_vec());
+                self.push(vec![flag]);
+            }
+            OP_INSPECTINPUTASSET | OP_INSPECTINPUTVALUE | OP_INSPECTINPUTSCRIPTPUBKEY => {
+                let index = self.pop_input_index()?;
+                let prevout = match ctx.prevouts.get(index) {
+                    Some(prevout) => prevout,
+                    None => fail!("Missing prevout for input #{}", index),
+                };
+                self.push_output_field(op, prevout)?;
+            }
+            OP_INSPECTINPUTSEQUENCE => {
+                let input = &ctx.tx.inputs[self.pop_input_index()?];
+                self.push(input.sequence.0.to_le_bytes().to_vec());
+            }
+            OP_PUSHCURRENTINPUTINDEX => self.push_num(ctx.input_index as i64),
+            OP_INSPECTOUTPUTASSET
+            | OP_INSPECTOUTPUTVALUE
+            | OP_INSPECTOUTPUTNONCE
+            | OP_INSPECTOUTPUTSCRIPTPUBKEY => {
+                let index = self.pop_index(ctx.tx.outputs.len(), "output")?;
+                self.push_output_field(op, &ctx.tx.outputs[index])?;
+            }
+            OP_INSPECTVERSION => self.push(ctx.tx.version.to_le_bytes().to_vec()),
+            OP_INSPECTLOCKTIME => {
+                let lock_time = ctx.tx.lock_time.to_consensus_u32();
+                self.push(lock_time.to_le_bytes().to_vec());
+            }
+            OP_INSPECTNUMINPUTS => self.push_num(ctx.tx.inputs.len() as i64),
+            OP_INSPECTNUMOUTPUTS => self.push_num(ctx.tx.outputs.len() as i64),
+            OP_TXWEIGHT => self.push_le64(ctx.tx.weight() as i64),
+
+            // 64-bit arithmetic. Operations that may overflow push their result followed by a
+            // success flag, or just a false flag on overflow.
+            OP_ADD64 | OP_SUB64 | OP_MUL64 => {
+                let b = self.pop_le64()?;
+                let a = self.pop_le64()?;
+                let res = match op {
+                    OP_ADD64 => a.checked_add(b),
+                    OP_SUB64 => a.checked_sub(b),
+                    _ => a.checked_mul(b),
+                };
+                self.push_checked(res.map(|res| vec![res]));
+            }
+            OP_DIV64 => {
+                let b = self.pop_le64()?;
+                let a = self.pop_le64()?;
+                // Euclidean division, with a non-negative remainder
+                let res = a
+                    .checked_rem_euclid(b)
+                    .zip(a.checked_div_euclid(b))
+                    .map(|(rem, quot)| vec![rem, quot]);
+                self.push_checked(res);
+            }
+            OP_NEG64 => {
+                let a = self.pop_le64()?;
+                self.push_checked(a.checked_neg().map(|res| vec![res]));
+            }
+            OP_LESSTHAN64 | OP_LESSTHANOREQUAL64 | OP_GREATERTHAN64 | OP_GREATERTHANOREQUAL64 => {
+                let b = self.pop_le64()?;
+                let a = self.pop_le64()?;
+                self.push_bool(match op {
+                    OP_LESSTHAN64 => a < b,
+                    OP_LESSTHANOREQUAL64 => a <= b,
+                    OP_GREATERTHAN64 => a > b,
+                    _ => a >= b,
+                });
+            }
+            OP_SCRIPTNUMTOLE64 => {
+                let n = read_num(&self.pop()?, 8)?;
+                self.push_le64(n);
+            }
+            OP_LE64TOSCRIPTNUM => {
+                let n = self.pop_le64()?;
+                if n == i64::MIN {
+                    fail!("Number out of the script number range");
+                }
+                self.push_num(n);
+            }
+            OP_LE32TOLE64 => match <[u8; 4]>::try_from(self.pop()?.as_slice()) {
+                Ok(bytes) => self.push_le64(u32::from_le_bytes(bytes) as i64),
+                Err(_) => fail!("Expected a 4-byte LE32 number"),
+            },
+
+            op => match op.classify(ClassifyContext::TapScript) {
+                Class::PushNum(num) => self.push_num(num as i64),
+                _ => fail!("Unsupported opcode"),
+            },
+        }
+        Ok(())
+    }
+
+    /// Push the asset, value, nonce or scriptPubKey of a (previous) output
+    fn push_output_field(
+        &mut self,
+        op: Opcode,
+        output: &ElementsTxOut,
+    ) -> std::result::Result<(), Failure> {
+        match op {
+            // Explicit assets and values are pushed followed by a 0x01 prefix, confidential ones as the
+            // commitment's 32 bytes followed by its prefix byte. Explicit values are LE64-encoded.
+            OP_INSPECTINPUTASSET | OP_INSPECTOUTPUTASSET => match &output.asset {
+                ConfAsset::Explicit(asset) => self.push_prefixed(asset.to_vec(), 1),
+                ConfAsset::Confidential(c) => self.push_prefixed(c[1..].to_vec(), c[0]),
+                ConfAsset::Null => fail!("Null asset"),
+            },
+            OP_INSPECTINPUTVALUE | OP_INSPECTOUTPUTVALUE => match &output.value {
+                ConfValue::Explicit(value) => self.push_prefixed(value.to_le_bytes().to_vec(), 1),
+                ConfValue::Confidential(c) => self.push_prefixed(c[1..].to_vec(), c[0]),
+                ConfValue::Null => fail!("Null value"),
+            },
+            OP_INSPECTOUTPUTNONCE => match &output.nonce {
+                ConfNonce::Null => self.push(vec![]),
+                ConfNonce::Explicit(nonce) => self.push([&[1u8][..], nonce].concat()),
+                ConfNonce::Confidential(c) => self.push(c.to_vec()),
+            },
+            // Witness programs are pushed followed by their version, others as their SHA256 followed by -1
+            _ => {
+                let spk = &output.script_pubkey;
+                if spk.is_witness_program() {
+                    let version = spk.witness_version().expect("is a witness program");
+                    self.push(spk.as_bytes()[2..].to_vec());
+                    self.push_num(version.to_num() as i64);
+                } else {
+                    self.push(sha256::Hash::hash(spk.as_bytes()).to_byte_array().to_vec());
+                    self.push_num(-1);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn check_locktime(&mut self) -> std::result::Result<(), Failure> {
+        const THRESHOLD: i64 = 500_000_000;
+        let required = read_num(self.peek(0)?, 5)?;
+        let tx_locktime = self.ctx.tx.lock_time.to_consensus_u32() as i64;
+        let input = &self.ctx.tx.inputs[self.ctx.input_index];
+        if required < 0 {
+            fail!("Negative locktime");
+        }
+        if (required < THRESHOLD) != (tx_locktime < THRESHOLD) {
+            fail!("Locktime type mismatch (block height vs time)");
+        }
+        if required > tx_locktime {
+            fail!(
+                "Locktime requirement not satisfied: {} > {}",
+                required,
+                tx_locktime
+            );
+        }
+        if input.sequence.is_final() {
+            fail!("Locktime is disabled by the input's final sequence");
+        }
+        Ok(())
+    }
+
+    fn check_sequence(&mut self) -> std::result::Result<(), Failure> {
+        const DISABLE_FLAG: i64 = 1 << 31;
+        const TYPE_FLAG: i64 = 1 << 22;
+        const MASK: i64 = TYPE_FLAG | 0xffff;
+        let required = read_num(self.peek(0)?, 5)?;
+        if required < 0 {
+            fail!("Negative sequence");
+        }
+        if required & DISABLE_FLAG != 0 {
+            return Ok(());
+        }
+        let sequence = self.ctx.tx.inputs[self.ctx.input_index].sequence.0 as i64;
+        if self.ctx.tx.version < 2 || sequence & DISABLE_FLAG != 0 {
+            fail!("Relative locktime is disabled for the input");
+        }
+        let (required, sequence) = (required & MASK, sequence & MASK);
+        if (required & TYPE_FLAG) != (sequence & TYPE_FLAG) {
+            fail!("Relative locktime type mismatch (blocks vs time)");
+        }
+        if required > sequence {
+            fail!(
+                "Relative locktime requirement not satisfied: {} > {}",
+                required,
+                sequence
+            );
+        }
+        Ok(())
+    }
+
+    fn push(&mut self, item: Vec<u8>) {
+        self.stack.push(item);
+    }
+    fn push_num(&mut self, num: i64) {
+        let mut buf = [0u8; 8];
+        let len = write_scriptint(&mut buf, num);
+        self.push(buf[..len].to_vec());
+    }
+    fn push_bool(&mut self, val: bool) {
+        self.push(iif!(val, vec![1], vec![]));
+    }
+    fn push_le64(&mut self, num: i64) {
+        self.push(num.to_le_bytes().to_vec());
+    }
+    fn push_prefixed(&mut self, item: Vec<u8>, prefix: u8) {
+        self.push(item);
+        self.push(vec![prefix]);
+    }
+    /// Push the results followed by a true flag, or just a false flag on overflow
+    fn push_checked(&mut self, results: Option<Vec<i64>>) {
+        match results {
+            Some(results) => {
+                results.into_iter().for_each(|res| self.push_le64(res));
+                self.push_bool(true);
+            }
+            None => self.push_bool(false),
+        }
+    }
+
+    fn pop(&mut self) -> std::result::Result<Vec<u8>, Failure> {
+        self.stack
+            .pop()
+            .ok_or_else(|| "Stack underflow".to_string())
+    }
+    fn pop2(&mut self) -> std::result::Result<(Vec<u8>, Vec<u8>), Failure> {
+        let b = self.pop()?;
+        Ok((self.pop()?, b))
+    }
+    fn pop_n(&mut self, n: usize) -> std::result::Result<(), Failure> {
+        for _ in 0..n {
+            self.pop()?;
+        }
+        Ok(())
+    }
+    fn pop_bool(&mut self) -> std::result::Result<bool, Failure> {
+        Ok(cast_to_bool(&self.pop()?))
+    }
+    fn pop_num(&mut self) -> std::result::Result<i64, Failure> {
+        read_num(&self.pop()?, 4)
+    }
+    fn pop_le64(&mut self) -> std::result::Result<i64, Failure> {
+        match <[u8; 8]>::try_from(self.pop()?.as_slice()) {
+            Ok(bytes) => Ok(i64::from_le_bytes(bytes)),
+            Err(_) => fail!("Expected an 8-byte LE64 number"),
+        }
+    }
+    fn pop_index(&mut self, len: usize, kind: &str) -> std::result::Result<usize, Failure> {
+        let index = self.pop_num()?;
+        if index < 0 || index as usize >= len {
+            fail!("Invalid {} index {}", kind, index);
+        }
+        Ok(index as usize)
+    }
+    fn pop_input_index(&mut self) -> std::result::Result<usize, Failure> {
+        self.pop_index(self.ctx.tx.inputs.len(), "input")
+    }
+    fn peek(&self, depth: usize) -> std::result::Result<&Vec<u8>, Failure> {
+        match self.stack.len().checked_sub(depth + 1) {
+            Some(index) => Ok(&self.stack[index]),
+            None => fail!("Stack underflow"),
+        }
+    }
+    /// Pop n items, then push back the items at the given depths (counted from the top, before popping)
+    fn rearrange(&mut self, n: usize, depths: &[usize]) -> std::result::Result<(), Failure> {
+        let popped = (0..n)
+            .map(|_| self.pop())
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        for depth in depths {
+            self.push(popped[*depth].clone());
+        }
+        Ok(())
+    }
+    fn hash_top(&mut self, hash_fn: fn(&[u8]) -> Vec<u8>) -> std::result::Result<(), Failure> {
+        let item = self.pop()?;
+        self.push(hash_fn(&item));
+        Ok(())
+    }
+}
+
+/// Decode a minimally-encoded script number of up to `max_len` bytes
+fn read_num(bytes: &[u8], max_len: usize) -> std::result::Result<i64, Failure> {
+    let last = match bytes.last() {
+        Some(last) => *last,
+        None => return Ok(0),
+    };
+    if bytes.len() > max_len {
+        fail!(
+            "Script number 0x{} exceeds {} bytes",
+            bytes.as_hex(),
+            max_len
+        );
+    }
+    // The most significant byte may only be 0x00/0x80 if the sign bit is needed for the previous byte
+    if last & 0x7f == 0 && (bytes.len() == 1 || bytes[bytes.len() - 2] & 0x80 == 0) {
+        fail!("Non-minimal script number 0x{}", bytes.as_hex());
+    }
+    let magnitude = bytes
+        .iter()
+        .enumerate()
+        .fold(0u64, |acc, (i, b)| acc | (*b as u64) << (8 * i));
+    let magnitude = (magnitude & !(0x80u64 << (8 * (bytes.len() - 1)))) as i64;
+    Ok(iif!(last & 0x80 != 0, -magnitude, magnitude))
+}
+
+fn cast_to_bool(item: &[u8]) -> bool {
+    match item.split_last() {
+        None => false,
+        // Negative zero (0x80 as the last byte with all other bytes zero) is false too
+        Some((last, rest)) => rest.iter().any(|b| *b != 0) || (*last & 0x7f) != 0,
+    }
+}
+
+/// Find the script markers, as (position, body) tuples. Malformed markers are ignored.
+fn find_markers(script: &Script) -> Vec<(usize, &str)> {
+    script
+        .iter_with_markers_indices(SCRIPT_MARKER_MAGIC_BYTES)
+        .filter_map(|item| match item {
+            Ok((pos, MarkerItem::Marker(marker))) => Some((pos, marker.body)),
+            _ => None,
+        })
+        .collect()
+}
+
+fn tagged<V: Into<Value>>(tag: &str, val: V) -> Value {
+    Value::array(vec![tag.into(), val.into()])
+}
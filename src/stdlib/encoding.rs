@@ -0,0 +1,141 @@
+use miniscript::bitcoin::base58;
+use miniscript::bitcoin::bech32::{self, Bech32, Bech32m, Hrp};
+use miniscript::bitcoin::hex::{DisplayHex, FromHex};
+
+use crate::runtime::scope::{Mutable, ScopeRef};
+use crate::runtime::{Array, Error, Result, Value};
+
+pub fn attach_stdlib(scope: &ScopeRef<Mutable>) {
+    let mut scope = scope.borrow_mut();
+    scope.set_fn("hex::encode", fns::hexEncode).unwrap();
+    scope.set_fn("hex::decode", fns::hexDecode).unwrap();
+    scope.set_fn("base58::encode", fns::base58Encode).unwrap();
+    scope.set_fn("base58::decode", fns::base58Decode).unwrap();
+    scope
+        .set_fn("base58check::encode", fns::base58checkEncode)
+        .unwrap();
+    scope
+        .set_fn("base58check::decode", fns::base58checkDecode)
+        .unwrap();
+    scope.set_fn("base64::encode", fns::base64Encode).unwrap();
+    scope.set_fn("base64::decode", fns::base64Decode).unwrap();
+    scope.set_fn("bech32::encode", fns::bech32Encode).unwrap();
+    scope.set_fn("bech32::decode", fns::bech32Decode).unwrap();
+}
+
+#[allow(non_snake_case)]
+pub mod fns {
+    use super::*;
+
+    /// hex::encode(Bytes) -> String
+    pub fn hexEncode(args: Array, _: &ScopeRef) -> Result<Value> {
+        Ok(args.arg_into::<Vec<u8>>()?.to_lower_hex_string().into())
+    }
+
+    /// hex::decode(String) -> Bytes
+    pub fn hexDecode(args: Array, _: &ScopeRef) -> Result<Value> {
+        Ok(Vec::<u8>::from_hex(&args.arg_into::<String>()?)?.into())
+    }
+
+    /// base58::encode(Bytes) -> String
+    pub fn base58Encode(args: Array, _: &ScopeRef) -> Result<Value> {
+        Ok(base58::encode(&args.arg_into::<Vec<u8>>()?).into())
+    }
+
+    /// base58::decode(String) -> Bytes
+    pub fn base58Decode(args: Array, _: &ScopeRef) -> Result<Value> {
+        Ok(base58::decode(&args.arg_into::<String>()?)?.into())
+    }
+
+    /// base58check::encode(Bytes) -> String
+    pub fn base58checkEncode(args: Array, _: &ScopeRef) -> Result<Value> {
+        Ok(base58::encode_check(&args.arg_into::<Vec<u8>>()?).into())
+    }
+
+    /// Decode and verify the checksum, returning the payload without it
+    ///
+    /// base58check::decode(String) -> Bytes
+    pub fn base58checkDecode(args: Array, _: &ScopeRef) -> Result<Value> {
+        Ok(base58::decode_check(&args.arg_into::<String>()?)?.into())
+    }
+
+    /// base64::encode(Bytes) -> String
+    pub fn base64Encode(args: Array, _: &ScopeRef) -> Result<Value> {
+        Ok(base64_encode_bytes(&args.arg_into::<Vec<u8>>()?).into())
+    }
+
+    /// base64::decode(String) -> Bytes
+    pub fn base64Decode(args: Array, _: &ScopeRef) -> Result<Value> {
+        Ok(base64_decode_str(&args.arg_into::<String>()?)?.into())
+    }
+
+    /// Encode the Bytes as-is (without segwit version/program conversion) with a checksum
+    ///
+    /// bech32::encode(String hrp, Bytes, String variant="bech32") -> String
+    pub fn bech32Encode(args: Array, _: &ScopeRef) -> Result<Value> {
+        let (hrp, data, variant): (String, Vec<u8>, Option<String>) = args.args_into()?;
+        let hrp = Hrp::parse(&hrp)?;
+        Ok(match variant.as_deref() {
+            None | Some("bech32") => bech32::encode::<Bech32>(hrp, &data)?,
+            Some("bech32m") => bech32::encode::<Bech32m>(hrp, &data)?,
+            Some(other) => bail!(Error::InvalidBech32Variant(other.to_string())),
+        }
+        .into())
+    }
+
+    /// Decode a bech32 or bech32m string, verifying its checksum
+    ///
+    /// bech32::decode(String) -> String hrp:Bytes
+    pub fn bech32Decode(args: Array, _: &ScopeRef) -> Result<Value> {
+        let (hrp, data) = bech32::decode(&args.arg_into::<String>()?)?;
+        Ok(Value::array(vec![hrp.to_lowercase().into(), data.into()]))
+    }
+}
+
+const BASE64_CHARS: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Standard base64 encoding (RFC 4648) with padding
+fn base64_encode_bytes(data: &[u8]) -> String {
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, byte)| n | (*byte as u32) << (16 - i * 8));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(BASE64_CHARS[(n >> (18 - i * 6)) as usize & 0x3f] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+// usize::is_multiple_of() requires Rust 1.87, newer than the MSRV
+#[allow(clippy::manual_is_multiple_of)]
+fn base64_decode_str(encoded: &str) -> Result<Vec<u8>> {
+    let encoded = encoded.as_bytes();
+    ensure!(encoded.len() % 4 == 0, Error::InvalidBase64);
+    let mut data = Vec::with_capacity(encoded.len() / 4 * 3);
+    for (chunk_i, chunk) in encoded.chunks(4).enumerate() {
+        let is_last = chunk_i == encoded.len() / 4 - 1;
+        let padding = chunk.iter().rev().take_while(|c| **c == b'=').count();
+        ensure!(
+            padding <= 2 && (padding == 0 || is_last),
+            Error::InvalidBase64
+        );
+
+        let mut n = 0u32;
+        for (i, c) in chunk[..4 - padding].iter().enumerate() {
+            let value = BASE64_CHARS
+                .iter()
+                .position(|b| b == c)
+                .ok_or(Error::InvalidBase64)?;
+            n |= (value as u32) << (18 - i * 6);
+        }
+        data.extend_from_slice(&n.to_be_bytes()[1..4 - padding]);
+    }
+    Ok(data)
+}
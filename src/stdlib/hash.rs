@@ -0,0 +1,79 @@
+use bitcoin::hashes::{hash160, hmac, ripemd160, sha1, sha256, sha256d, sha512, Hash, HashEngine};
+use miniscript::bitcoin;
+
+use crate::runtime::scope::{Mutable, ScopeRef};
+use crate::runtime::{Array, Result, Value};
+
+pub fn attach_stdlib(scope: &ScopeRef<Mutable>) {
+    let mut scope = scope.borrow_mut();
+    scope.set_fn("hash::sha256", fns::sha256).unwrap();
+    scope.set_fn("hash::sha256d", fns::sha256d).unwrap();
+    scope.set_fn("hash::hash160", fns::hash160).unwrap();
+    scope.set_fn("hash::ripemd160", fns::ripemd160).unwrap();
+    scope.set_fn("hash::sha1", fns::sha1).unwrap();
+    scope.set_fn("hash::hmac_sha512", fns::hmac_sha512).unwrap();
+    scope.set_fn("hash::taggedHash", fns::taggedHash).unwrap();
+}
+
+#[allow(non_snake_case)]
+pub mod fns {
+    use super::*;
+
+    /// hash::sha256(Bytes preimage) -> Bytes hash
+    pub fn sha256(args: Array, _: &ScopeRef) -> Result<Value> {
+        Ok(hash_arg::<sha256::Hash>(args)?.into())
+    }
+
+    /// Double SHA256, as used for txids and block hashes
+    ///
+    /// hash::sha256d(Bytes preimage) -> Bytes hash
+    pub fn sha256d(args: Array, _: &ScopeRef) -> Result<Value> {
+        Ok(hash_arg::<sha256d::Hash>(args)?.into())
+    }
+
+    /// RIPEMD160(SHA256(preimage)), as used for P2PKH/P2WPKH
+    ///
+    /// hash::hash160(Bytes preimage) -> Bytes hash
+    pub fn hash160(args: Array, _: &ScopeRef) -> Result<Value> {
+        Ok(hash_arg::<hash160::Hash>(args)?.into())
+    }
+
+    /// hash::ripemd160(Bytes preimage) -> Bytes hash
+    pub fn ripemd160(args: Array, _: &ScopeRef) -> Result<Value> {
+        Ok(hash_arg::<ripemd160::Hash>(args)?.into())
+    }
+
+    /// hash::sha1(Bytes preimage) -> Bytes hash
+    pub fn sha1(args: Array, _: &ScopeRef) -> Result<Value> {
+        Ok(hash_arg::<sha1::Hash>(args)?.into())
+    }
+
+    /// hash::hmac_sha512(Bytes key, Bytes data) -> Bytes hmac
+    pub fn hmac_sha512(args: Array, _: &ScopeRef) -> Result<Value> {
+        let (key, data): (Vec<u8>, Vec<u8>) = args.args_into()?;
+        let mut engine = hmac::HmacEngine::<sha512::Hash>::new(&key);
+        engine.input(&data);
+        Ok(hmac::Hmac::from_engine(engine).into())
+    }
+
+    /// BIP340 tagged hash, SHA256(SHA256(tag) || SHA256(tag) || data)
+    ///
+    /// hash::taggedHash(String|Bytes tag, Bytes data) -> Bytes hash
+    pub fn taggedHash(args: Array, _: &ScopeRef) -> Result<Value> {
+        let (tag, data): (Vec<u8>, Vec<u8>) = args.args_into()?;
+        Ok(tagged_hash(&tag, &data).into())
+    }
+}
+
+fn hash_arg<H: Hash>(args: Array) -> Result<H> {
+    Ok(<H as Hash>::hash(&args.arg_into::<Vec<u8>>()?))
+}
+
+pub fn tagged_hash(tag: &[u8], data: &[u8]) -> sha256::Hash {
+    let tag_hash = sha256::Hash::hash(tag);
+    let mut engine = sha256::Hash::engine();
+    engine.input(tag_hash.as_ref());
+    engine.input(tag_hash.as_ref());
+    engine.input(data);
+    sha256::Hash::from_engine(engine)
+}
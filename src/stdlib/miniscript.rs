@@ -143,6 +143,7 @@ pub mod fns {
                 let miniscript = policy.compile()?;
                 Descriptor::new_wsh(miniscript)?.into()
             }
+            Value::Miniscript(ms) => Descriptor::new_wsh(ms.into_miniscript()?)?.into(),
             Value::Script(script) => script.to_p2wsh().into(),
             _ => bail!(Error::InvalidArguments),
         })
@@ -167,17 +168,15 @@ pub mod fns {
         Ok(descriptor.to_explicit_script()?.into())
     }
 
-    /// Policy -> Script witnessScript
+    /// Policy|Miniscript -> Script witnessScript
     pub fn tapscript(args: Array, _: &ScopeRef) -> Result<Value> {
-        let policy: Policy = args.arg_into()?;
-        let miniscript = policy.compile::<miniscript::Tap>()?;
+        let miniscript: Miniscript<miniscript::Tap> = args.arg_into()?;
         Ok(miniscript.derive_keys()?.encode().into())
     }
 
-    /// Policy -> Script witnessScript
+    /// Policy|Miniscript -> Script witnessScript
     pub fn segwitv0(args: Array, _: &ScopeRef) -> Result<Value> {
-        let policy: Policy = args.arg_into()?;
-        let miniscript = policy.compile::<miniscript::Segwitv0>()?;
+        let miniscript: Miniscript<miniscript::Segwitv0> = args.arg_into()?;
         Ok(miniscript.derive_keys()?.encode().into())
     }
 
@@ -241,7 +240,11 @@ impl TryFrom<Value> for Descriptor {
 impl<Ctx: ScriptContext> TryFrom<Value> for Miniscript<Ctx> {
     type Error = Error;
     fn try_from(value: Value) -> Result<Self> {
-        Ok(value.into_policy()?.compile()?)
+        match value {
+            // Miniscript values are converted into the requested context, policies are compiled for it
+            Value::Miniscript(ms) => ms.into_miniscript(),
+            other => Ok(other.into_policy()?.compile()?),
+        }
     }
 }
 
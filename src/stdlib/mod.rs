@@ -3,13 +3,25 @@ use std::convert::TryInto;
 use ::miniscript::bitcoin::hashes::{sha256, Hash};
 
 use crate::runtime::scope::{Mutable, ScopeRef};
-use crate::runtime::{Array, Error, Execute, Number, Result, Symbol, Value};
+use crate::runtime::{limits, Array, Error, Execute, Number, Result, Symbol, Value};
 use crate::{time, Library};
 
 pub mod btc;
+pub mod compiler;
 pub mod ctv;
+pub mod ec;
+pub mod elements;
+pub mod elements_interpreter;
+pub mod encoding;
+pub mod hash;
 pub mod miniscript;
+pub mod ms;
+pub mod script_analysis;
+pub mod script_decompiler;
 pub mod script_marker;
+pub mod script_optimizer;
+pub mod standardness;
+pub mod strings;
 pub mod tagged;
 pub mod taproot;
 
@@ -57,11 +69,23 @@ pub fn attach_stdlib(scope: &ScopeRef<Mutable>) {
         scope.set("MIN_NUMBER", i64::MIN).unwrap();
     }
 
+    // String manipulation and encodings
+    self::strings::attach_stdlib(scope);
+    self::encoding::attach_stdlib(scope);
+
+    // Hash functions
+    self::hash::attach_stdlib(scope);
+
     // Bitcoin related functions
     self::btc::attach_stdlib(scope);
 
     // Miniscript related functions
     self::miniscript::attach_stdlib(scope);
+    self::ms::attach_stdlib(scope);
+    self::compiler::attach_stdlib(scope);
+
+    // Elliptic curve operations
+    self::ec::attach_stdlib(scope);
 
     // Taproot related functions
     self::taproot::attach_stdlib(scope);
@@ -69,8 +93,20 @@ pub fn attach_stdlib(scope: &ScopeRef<Mutable>) {
     // CTV
     self::ctv::attach_stdlib(scope);
 
+    // Script analysis
+    self::script_analysis::attach_stdlib(scope);
+    self::script_decompiler::attach_stdlib(scope);
+    self::script_optimizer::attach_stdlib(scope);
+
+    // Relay policy (standardness) checks
+    self::standardness::attach_stdlib(scope);
+
     // Standard library implemented in Minsc
     MINSC_STDLIB.exec(scope).unwrap();
+
+    // Elements transactions, addresses and the Minsc Elements library
+    self::elements::attach_stdlib(scope);
+    self::elements_interpreter::attach_stdlib(scope);
     ELEMENTS_STDLIB.exec(scope).unwrap();
 }
 
@@ -81,7 +117,7 @@ pub mod fns {
     use crate::util::PrettyDisplay;
 
     /// Get the argument type as a string
-    /// One of: pubkey, number, bool, bytes, policy, withprob, descriptor, address, script, function, network, tapinfo, array, symbol
+    /// One of: pubkey, number, bool, bytes, policy, withprob, tapleaf, miniscript, descriptor, address, script, transaction, elementstx, function, network, tapinfo, array, symbol
     /// typeof(Value) -> String
     pub fn r#typeof(args: Array, _: &ScopeRef) -> Result<Value> {
         let type_of = args.arg_into::<Value>()?.type_of();
@@ -138,6 +174,7 @@ pub mod fns {
     /// Return an array of the specified size, using the callback function to produce values
     pub fn fillArray(args: Array, scope: &ScopeRef) -> Result<Value> {
         let (num, producer): (usize, Value) = args.args_into()?;
+        limits::check_array_len(num)?;
         Ok(match producer {
             Value::Function(callback) => Value::array(
                 (0..num)
@@ -202,7 +239,7 @@ pub mod fns {
     /// SHA256(Bytes preimage) -> Bytes hash
     /// Hash some data with SHA256
     /// Named in upper-case to avoid a conflict with the Miniscript sha256(Bytes) policy function
-    /// (Yes, this is awfully confusing and requires a better solution. :<)
+    /// Kept for backwards compatibility, hash::sha256() should be preferred
     pub fn SHA256(args: Array, _: &ScopeRef) -> Result<Value> {
         let bytes: Vec<u8> = args.arg_into()?;
         let hash = sha256::Hash::hash(&bytes);
@@ -0,0 +1,632 @@
+use std::convert::{TryFrom, TryInto};
+use std::fmt;
+use std::sync::Arc;
+
+use bitcoin::hashes::{hash160, ripemd160, sha256, Hash};
+use bitcoin::{absolute, ScriptBuf, Sequence};
+use miniscript::expression::{self, FromTree};
+use miniscript::miniscript::types::{Base, Dissat, Input};
+use miniscript::{bitcoin, hash256, AbsLockTime, DescriptorPublicKey, Legacy, MiniscriptKey};
+use miniscript::{ScriptContext, Segwitv0, Tap, Terminal};
+
+use super::standardness::ScriptCtx;
+use crate::runtime::scope::{Mutable, ScopeRef};
+use crate::runtime::{Array, Error, FromValue, Result, Value};
+use crate::util::{MiniscriptExt, PrettyDisplay, LIST_INDENT_WIDTH};
+use crate::{MiniscriptDpk as Miniscript, PolicyDpk as Policy};
+
+/// Miniscript along with its script context. Unlike Script, this retains the fragment tree
+/// and its type information.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AnyMiniscript {
+    Legacy(Miniscript<Legacy>),
+    Segwitv0(Miniscript<Segwitv0>),
+    Tapscript(Miniscript<Tap>),
+}
+
+// Evaluate the expression with `$ms` bound to the inner Miniscript, for any script context
+macro_rules! with_ms {
+    ($any_ms:expr, $ms:ident => $expr:expr) => {
+        match $any_ms {
+            AnyMiniscript::Legacy($ms) => $expr,
+            AnyMiniscript::Segwitv0($ms) => $expr,
+            AnyMiniscript::Tapscript($ms) => $expr,
+        }
+    };
+}
+
+pub fn attach_stdlib(scope: &ScopeRef<Mutable>) {
+    let mut scope = scope.borrow_mut();
+    scope.set_fn("miniscript", fns::miniscript).unwrap();
+    scope.set_fn("ms::type", fns::r#type).unwrap();
+
+    // Fragments
+    scope
+        .set("ms::TRUE", new_leaf(Terminal::True).unwrap())
+        .unwrap();
+    scope
+        .set("ms::FALSE", new_leaf(Terminal::False).unwrap())
+        .unwrap();
+    scope.set_fn("ms::pk_k", fns::pk_k).unwrap();
+    scope.set_fn("ms::pk_h", fns::pk_h).unwrap();
+    scope.set_fn("ms::pk", fns::pk).unwrap();
+    scope.set_fn("ms::pkh", fns::pkh).unwrap();
+    scope.set_fn("ms::older", fns::older).unwrap();
+    scope.set_fn("ms::after", fns::after).unwrap();
+    scope.set_fn("ms::sha256", fns::sha256).unwrap();
+    scope.set_fn("ms::hash256", fns::hash256).unwrap();
+    scope.set_fn("ms::ripemd160", fns::ripemd160).unwrap();
+    scope.set_fn("ms::hash160", fns::hash160).unwrap();
+    scope.set_fn("ms::and_v", fns::and_v).unwrap();
+    scope.set_fn("ms::and_b", fns::and_b).unwrap();
+    scope.set_fn("ms::and_n", fns::and_n).unwrap();
+    scope.set_fn("ms::andor", fns::andor).unwrap();
+    scope.set_fn("ms::or_b", fns::or_b).unwrap();
+    scope.set_fn("ms::or_c", fns::or_c).unwrap();
+    scope.set_fn("ms::or_d", fns::or_d).unwrap();
+    scope.set_fn("ms::or_i", fns::or_i).unwrap();
+    scope.set_fn("ms::thresh", fns::thresh).unwrap();
+    scope.set_fn("ms::multi", fns::multi).unwrap();
+    scope.set_fn("ms::multi_a", fns::multi_a).unwrap();
+
+    // Wrappers
+    scope.set_fn("ms::a", fns::a).unwrap();
+    scope.set_fn("ms::s", fns::s).unwrap();
+    scope.set_fn("ms::c", fns::c).unwrap();
+    scope.set_fn("ms::d", fns::d).unwrap();
+    scope.set_fn("ms::v", fns::v).unwrap();
+    scope.set_fn("ms::j", fns::j).unwrap();
+    scope.set_fn("ms::n", fns::n).unwrap();
+    scope.set_fn("ms::t", fns::t).unwrap();
+    scope.set_fn("ms::l", fns::l).unwrap();
+    scope.set_fn("ms::u", fns::u).unwrap();
+}
+
+/// Fragments are type-checked as they get constructed, but are not required to be sane (e.g. may be malleable),
+/// to allow for hand-tuned miniscript. Fragments with no miniscript children use the segwitv0 context, unless
+/// they're only valid under tapscript (like x-only keys and multi_a). Fragments with children inherit their context.
+#[allow(non_snake_case)]
+pub mod fns {
+    use super::*;
+
+    /// Parse the miniscript string, compile the policy or convert the miniscript into the script context.
+    /// The context defaults to "segwitv0" for strings and policies.
+    ///
+    /// miniscript(String|Policy|Miniscript, String ctx=None) -> Miniscript
+    pub fn miniscript(args: Array, _: &ScopeRef) -> Result<Value> {
+        let (value, ctx): (Value, Option<ScriptCtx>) = args.args_into()?;
+        Ok(match value {
+            Value::Miniscript(ms) => match ctx {
+                Some(ctx) => ms.into_ctx(ctx)?,
+                None => ms,
+            },
+            Value::String(ms) => AnyMiniscript::parse(&ms, ctx.unwrap_or(ScriptCtx::Segwitv0))?,
+            policy => {
+                let policy = policy.into_policy()?;
+                AnyMiniscript::compile(&policy, ctx.unwrap_or(ScriptCtx::Segwitv0))?
+            }
+        }
+        .into())
+    }
+
+    /// Get the fragment type (B, V, K or W) and its properties, for example "B/onduesmk"
+    ///
+    /// ms::type(Miniscript) -> String
+    pub fn r#type(args: Array, _: &ScopeRef) -> Result<Value> {
+        let ms: AnyMiniscript = args.arg_into()?;
+        Ok(with_ms!(&ms, ms => type_str(ms)).into())
+    }
+
+    /// ms::pk_k(PubKey) -> Miniscript
+    pub fn pk_k(args: Array, _: &ScopeRef) -> Result<Value> {
+        new_leaf(Terminal::PkK(args.arg_into()?))
+    }
+    /// ms::pk_h(PubKey) -> Miniscript
+    pub fn pk_h(args: Array, _: &ScopeRef) -> Result<Value> {
+        new_leaf(Terminal::PkH(args.arg_into()?))
+    }
+    /// ms::pk(PubKey) -> Miniscript
+    pub fn pk(args: Array, _: &ScopeRef) -> Result<Value> {
+        wrap('c', new_leaf(Terminal::PkK(args.arg_into()?))?.try_into()?)
+    }
+    /// ms::pkh(PubKey) -> Miniscript
+    pub fn pkh(args: Array, _: &ScopeRef) -> Result<Value> {
+        wrap('c', new_leaf(Terminal::PkH(args.arg_into()?))?.try_into()?)
+    }
+
+    /// ms::older(Int) -> Miniscript
+    pub fn older(args: Array, _: &ScopeRef) -> Result<Value> {
+        let sequence = Sequence::from_consensus(args.arg_into()?);
+        new_leaf(Terminal::Older(sequence))
+    }
+    /// ms::after(Int) -> Miniscript
+    pub fn after(args: Array, _: &ScopeRef) -> Result<Value> {
+        let locktime = absolute::LockTime::from_consensus(args.arg_into()?);
+        new_leaf(Terminal::After(AbsLockTime::from(locktime)))
+    }
+
+    /// ms::sha256(Bytes hash) -> Miniscript
+    pub fn sha256(args: Array, _: &ScopeRef) -> Result<Value> {
+        let hash = sha256::Hash::from_slice(&args.arg_into::<Vec<u8>>()?)?;
+        new_leaf(Terminal::Sha256(hash))
+    }
+    /// ms::hash256(Bytes hash) -> Miniscript
+    pub fn hash256(args: Array, _: &ScopeRef) -> Result<Value> {
+        let hash = hash256::Hash::from_slice(&args.arg_into::<Vec<u8>>()?)?;
+        new_leaf(Terminal::Hash256(hash))
+    }
+    /// ms::ripemd160(Bytes hash) -> Miniscript
+    pub fn ripemd160(args: Array, _: &ScopeRef) -> Result<Value> {
+        let hash = ripemd160::Hash::from_slice(&args.arg_into::<Vec<u8>>()?)?;
+        new_leaf(Terminal::Ripemd160(hash))
+    }
+    /// ms::hash160(Bytes hash) -> Miniscript
+    pub fn hash160(args: Array, _: &ScopeRef) -> Result<Value> {
+        let hash = hash160::Hash::from_slice(&args.arg_into::<Vec<u8>>()?)?;
+        new_leaf(Terminal::Hash160(hash))
+    }
+
+    /// ms::and_v(Miniscript, Miniscript) -> Miniscript
+    pub fn and_v(args: Array, _: &ScopeRef) -> Result<Value> {
+        let (x, y) = args.args_into()?;
+        combine("and_v", None, vec![x, y])
+    }
+    /// ms::and_b(Miniscript, Miniscript) -> Miniscript
+    pub fn and_b(args: Array, _: &ScopeRef) -> Result<Value> {
+        let (x, y) = args.args_into()?;
+        combine("and_b", None, vec![x, y])
+    }
+    /// ms::and_n(Miniscript, Miniscript) -> Miniscript
+    pub fn and_n(args: Array, _: &ScopeRef) -> Result<Value> {
+        let (x, y) = args.args_into()?;
+        combine("and_n", None, vec![x, y])
+    }
+    /// ms::andor(Miniscript, Miniscript, Miniscript) -> Miniscript
+    pub fn andor(args: Array, _: &ScopeRef) -> Result<Value> {
+        let (x, y, z) = args.args_into()?;
+        combine("andor", None, vec![x, y, z])
+    }
+    /// ms::or_b(Miniscript, Miniscript) -> Miniscript
+    pub fn or_b(args: Array, _: &ScopeRef) -> Result<Value> {
+        let (x, z) = args.args_into()?;
+        combine("or_b", None, vec![x, z])
+    }
+    /// ms::or_c(Miniscript, Miniscript) -> Miniscript
+    pub fn or_c(args: Array, _: &ScopeRef) -> Result<Value> {
+        let (x, z) = args.args_into()?;
+        combine("or_c", None, vec![x, z])
+    }
+    /// ms::or_d(Miniscript, Miniscript) -> Miniscript
+    pub fn or_d(args: Array, _: &ScopeRef) -> Result<Value> {
+        let (x, z) = args.args_into()?;
+        combine("or_d", None, vec![x, z])
+    }
+    /// ms::or_i(Miniscript, Miniscript) -> Miniscript
+    pub fn or_i(args: Array, _: &ScopeRef) -> Result<Value> {
+        let (x, z) = args.args_into()?;
+        combine("or_i", None, vec![x, z])
+    }
+
+    /// ms::thresh(Int k, Miniscript, Miniscript, ...) -> Miniscript
+    /// ms::thresh(Int k, Array<Miniscript>) -> Miniscript
+    pub fn thresh(args: Array, _: &ScopeRef) -> Result<Value> {
+        let (k, subs) = thresh_args(args)?;
+        combine("thresh", Some(k), subs)
+    }
+
+    /// ms::multi(Int k, PubKey, PubKey, ...) -> Miniscript
+    /// ms::multi(Int k, Array<PubKey>) -> Miniscript
+    pub fn multi(args: Array, _: &ScopeRef) -> Result<Value> {
+        let (k, pks) = thresh_args(args)?;
+        new_leaf(Terminal::Multi(k, pks))
+    }
+    /// ms::multi_a(Int k, PubKey, PubKey, ...) -> Miniscript
+    /// ms::multi_a(Int k, Array<PubKey>) -> Miniscript
+    pub fn multi_a(args: Array, _: &ScopeRef) -> Result<Value> {
+        let (k, pks) = thresh_args(args)?;
+        new_leaf(Terminal::MultiA(k, pks))
+    }
+
+    /// ms::a(Miniscript) -> Miniscript
+    pub fn a(args: Array, _: &ScopeRef) -> Result<Value> {
+        wrap('a', args.arg_into()?)
+    }
+    /// ms::s(Miniscript) -> Miniscript
+    pub fn s(args: Array, _: &ScopeRef) -> Result<Value> {
+        wrap('s', args.arg_into()?)
+    }
+    /// ms::c(Miniscript) -> Miniscript
+    pub fn c(args: Array, _: &ScopeRef) -> Result<Value> {
+        wrap('c', args.arg_into()?)
+    }
+    /// ms::d(Miniscript) -> Miniscript
+    pub fn d(args: Array, _: &ScopeRef) -> Result<Value> {
+        wrap('d', args.arg_into()?)
+    }
+    /// ms::v(Miniscript) -> Miniscript
+    pub fn v(args: Array, _: &ScopeRef) -> Result<Value> {
+        wrap('v', args.arg_into()?)
+    }
+    /// ms::j(Miniscript) -> Miniscript
+    pub fn j(args: Array, _: &ScopeRef) -> Result<Value> {
+        wrap('j', args.arg_into()?)
+    }
+    /// ms::n(Miniscript) -> Miniscript
+    pub fn n(args: Array, _: &ScopeRef) -> Result<Value> {
+        wrap('n', args.arg_into()?)
+    }
+    /// ms::t(Miniscript) -> Miniscript, same as and_v(X,1)
+    pub fn t(args: Array, _: &ScopeRef) -> Result<Value> {
+        wrap('t', args.arg_into()?)
+    }
+    /// ms::l(Miniscript) -> Miniscript, same as or_i(0,X)
+    pub fn l(args: Array, _: &ScopeRef) -> Result<Value> {
+        wrap('l', args.arg_into()?)
+    }
+    /// ms::u(Miniscript) -> Miniscript, same as or_i(X,0)
+    pub fn u(args: Array, _: &ScopeRef) -> Result<Value> {
+        wrap('u', args.arg_into()?)
+    }
+}
+
+impl AnyMiniscript {
+    /// Parse a miniscript fragment, which is not required to be of the top-level B type
+    pub fn parse(ms: &str, ctx: ScriptCtx) -> Result<Self> {
+        let tree = expression::Tree::from_str(ms)?;
+        Ok(match ctx {
+            ScriptCtx::Legacy => AnyMiniscript::Legacy(Miniscript::from_tree(&tree)?),
+            ScriptCtx::Segwitv0 => AnyMiniscript::Segwitv0(Miniscript::from_tree(&tree)?),
+            ScriptCtx::Tapscript => AnyMiniscript::Tapscript(Miniscript::from_tree(&tree)?),
+        })
+    }
+
+    pub fn compile(policy: &Policy, ctx: ScriptCtx) -> Result<Self> {
+        Ok(match ctx {
+            ScriptCtx::Legacy => AnyMiniscript::Legacy(policy.compile()?),
+            ScriptCtx::Segwitv0 => AnyMiniscript::Segwitv0(policy.compile()?),
+            ScriptCtx::Tapscript => AnyMiniscript::Tapscript(policy.compile()?),
+        })
+    }
+
+    pub fn ctx(&self) -> ScriptCtx {
+        match self {
+            AnyMiniscript::Legacy(_) => ScriptCtx::Legacy,
+            AnyMiniscript::Segwitv0(_) => ScriptCtx::Segwitv0,
+            AnyMiniscript::Tapscript(_) => ScriptCtx::Tapscript,
+        }
+    }
+
+    /// Convert into another script context, re-checking the fragments validity under it
+    pub fn into_ctx(self, ctx: ScriptCtx) -> Result<Self> {
+        Ok(match ctx {
+            _ if self.ctx() == ctx => self,
+            ScriptCtx::Legacy => AnyMiniscript::Legacy(self.into_miniscript()?),
+            ScriptCtx::Segwitv0 => AnyMiniscript::Segwitv0(self.into_miniscript()?),
+            ScriptCtx::Tapscript => AnyMiniscript::Tapscript(self.into_miniscript()?),
+        })
+    }
+
+    pub fn into_miniscript<Ctx: ScriptContext>(self) -> Result<Miniscript<Ctx>> {
+        with_ms!(self, ms => Ok(Miniscript::from_ast(convert_ctx(&ms.node)?)?))
+    }
+
+    pub fn encode(&self) -> Result<ScriptBuf> {
+        with_ms!(self, ms => Ok(ms.clone().derive_keys()?.encode()))
+    }
+}
+
+/// The fragment type followed by its properties, using the notation from the Miniscript spec
+pub fn type_str<Pk: MiniscriptKey, Ctx: ScriptContext>(
+    ms: &miniscript::Miniscript<Pk, Ctx>,
+) -> String {
+    let (corr, mall) = (ms.ty.corr, ms.ty.mall);
+    let base = match corr.base {
+        Base::B => "B",
+        Base::V => "V",
+        Base::K => "K",
+        Base::W => "W",
+    };
+    let mut props = match corr.input {
+        Input::Zero => "z",
+        Input::One => "o",
+        Input::OneNonZero => "on",
+        Input::AnyNonZero => "n",
+        Input::Any => "",
+    }
+    .to_string();
+    if corr.dissatisfiable {
+        props.push('d');
+    }
+    if corr.unit {
+        props.push('u');
+    }
+    match mall.dissat {
+        Dissat::None => props.push('f'),
+        Dissat::Unique => props.push('e'),
+        Dissat::Unknown => {}
+    }
+    if mall.safe {
+        props.push('s');
+    }
+    if mall.non_malleable {
+        props.push('m');
+    }
+    if !ms.has_mixed_timelocks() {
+        props.push('k');
+    }
+    iif!(
+        props.is_empty(),
+        base.to_string(),
+        format!("{}/{}", base, props)
+    )
+}
+
+pub fn fragment_name<Pk: MiniscriptKey, Ctx: ScriptContext>(
+    node: &Terminal<Pk, Ctx>,
+) -> &'static str {
+    match node {
+        Terminal::True => "1",
+        Terminal::False => "0",
+        Terminal::PkK(_) => "pk_k",
+        Terminal::PkH(_) => "pk_h",
+        Terminal::RawPkH(_) => "expr_raw_pkh",
+        Terminal::After(_) => "after",
+        Terminal::Older(_) => "older",
+        Terminal::Sha256(_) => "sha256",
+        Terminal::Hash256(_) => "hash256",
+        Terminal::Ripemd160(_) => "ripemd160",
+        Terminal::Hash160(_) => "hash160",
+        Terminal::Alt(_) => "a:",
+        Terminal::Swap(_) => "s:",
+        Terminal::Check(_) => "c:",
+        Terminal::DupIf(_) => "d:",
+        Terminal::Verify(_) => "v:",
+        Terminal::NonZero(_) => "j:",
+        Terminal::ZeroNotEqual(_) => "n:",
+        Terminal::AndV(..) => "and_v",
+        Terminal::AndB(..) => "and_b",
+        Terminal::AndOr(..) => "andor",
+        Terminal::OrB(..) => "or_b",
+        Terminal::OrD(..) => "or_d",
+        Terminal::OrC(..) => "or_c",
+        Terminal::OrI(..) => "or_i",
+        Terminal::Thresh(..) => "thresh",
+        Terminal::Multi(..) => "multi",
+        Terminal::MultiA(..) => "multi_a",
+    }
+}
+
+// Construct a fragment with no miniscript children, under segwitv0 or under tapscript if it's only valid there
+fn new_leaf(node: Terminal<DescriptorPublicKey, Segwitv0>) -> Result<Value> {
+    let ms = match Miniscript::from_ast(node.clone()) {
+        Ok(ms) => AnyMiniscript::Segwitv0(ms),
+        Err(err) => {
+            AnyMiniscript::Tapscript(Miniscript::from_ast(convert_ctx(&node)?).map_err(|_| err)?)
+        }
+    };
+    Ok(ms.into())
+}
+
+// Combine the sub-fragments (with an optional threshold) under the context they share
+fn combine(name: &str, k: Option<usize>, subs: Vec<AnyMiniscript>) -> Result<Value> {
+    // Segwitv0 fragments are accepted in the other contexts, which take precedence
+    let mut ctx = ScriptCtx::Segwitv0;
+    for sub in &subs {
+        match (ctx, sub.ctx()) {
+            (_, ScriptCtx::Segwitv0) => {}
+            (ScriptCtx::Segwitv0, sub_ctx) => ctx = sub_ctx,
+            (ctx, sub_ctx) if ctx == sub_ctx => {}
+            (ctx, sub_ctx) => bail!(Error::MiniscriptMixedContexts(ctx.name(), sub_ctx.name())),
+        }
+    }
+    Ok(match ctx {
+        ScriptCtx::Legacy => AnyMiniscript::Legacy(combine_ctx(name, k, subs)?),
+        ScriptCtx::Segwitv0 => AnyMiniscript::Segwitv0(combine_ctx(name, k, subs)?),
+        ScriptCtx::Tapscript => AnyMiniscript::Tapscript(combine_ctx(name, k, subs)?),
+    }
+    .into())
+}
+
+fn combine_ctx<Ctx: ScriptContext>(
+    name: &str,
+    k: Option<usize>,
+    subs: Vec<AnyMiniscript>,
+) -> Result<Miniscript<Ctx>> {
+    let subs = subs
+        .into_iter()
+        .map(|sub| Ok(Arc::new(sub.into_miniscript()?)))
+        .collect::<Result<Vec<_>>>()?;
+    let mut subs_iter = subs.iter().cloned();
+    let mut next = || subs_iter.next().expect("checked by the caller");
+    let node = match name {
+        "and_v" => Terminal::AndV(next(), next()),
+        "and_b" => Terminal::AndB(next(), next()),
+        "and_n" => Terminal::AndOr(
+            next(),
+            next(),
+            Arc::new(Miniscript::from_ast(Terminal::False)?),
+        ),
+        "andor" => Terminal::AndOr(next(), next(), next()),
+        "or_b" => Terminal::OrB(next(), next()),
+        "or_c" => Terminal::OrC(next(), next()),
+        "or_d" => Terminal::OrD(next(), next()),
+        "or_i" => Terminal::OrI(next(), next()),
+        "thresh" => Terminal::Thresh(k.expect("thresh has k"), subs),
+        _ => unreachable!("unknown fragment {}", name),
+    };
+    Ok(Miniscript::from_ast(node)?)
+}
+
+fn wrap(wrapper: char, ms: AnyMiniscript) -> Result<Value> {
+    Ok(match ms {
+        AnyMiniscript::Legacy(ms) => AnyMiniscript::Legacy(wrap_ctx(wrapper, ms)?),
+        AnyMiniscript::Segwitv0(ms) => AnyMiniscript::Segwitv0(wrap_ctx(wrapper, ms)?),
+        AnyMiniscript::Tapscript(ms) => AnyMiniscript::Tapscript(wrap_ctx(wrapper, ms)?),
+    }
+    .into())
+}
+
+fn wrap_ctx<Ctx: ScriptContext>(wrapper: char, ms: Miniscript<Ctx>) -> Result<Miniscript<Ctx>> {
+    let ms = Arc::new(ms);
+    let true_ = || Miniscript::from_ast(Terminal::True).map(Arc::new);
+    let false_ = || Miniscript::from_ast(Terminal::False).map(Arc::new);
+    let node = match wrapper {
+        'a' => Terminal::Alt(ms),
+        's' => Terminal::Swap(ms),
+        'c' => Terminal::Check(ms),
+        'd' => Terminal::DupIf(ms),
+        'v' => Terminal::Verify(ms),
+        'j' => Terminal::NonZero(ms),
+        'n' => Terminal::ZeroNotEqual(ms),
+        't' => Terminal::AndV(ms, true_()?),
+        'u' => Terminal::OrI(ms, false_()?),
+        // l:0 is rejected by the Miniscript parser, and would not round-trip through its string form
+        'l' if ms.node == Terminal::False => bail!(miniscript::Error::LikelyFalse),
+        'l' => Terminal::OrI(false_()?, ms),
+        _ => unreachable!("unknown wrapper {}", wrapper),
+    };
+    Ok(Miniscript::from_ast(node)?)
+}
+
+// Rebuild the fragment tree under another script context. The children are type-checked under it
+// as they get rebuilt, with the caller checking the root.
+fn convert_ctx<FromCtx: ScriptContext, Ctx: ScriptContext>(
+    node: &Terminal<DescriptorPublicKey, FromCtx>,
+) -> Result<Terminal<DescriptorPublicKey, Ctx>> {
+    let sub = |sub: &Miniscript<FromCtx>| -> Result<Arc<Miniscript<Ctx>>> {
+        Ok(Arc::new(Miniscript::from_ast(convert_ctx(&sub.node)?)?))
+    };
+    Ok(match node {
+        Terminal::True => Terminal::True,
+        Terminal::False => Terminal::False,
+        Terminal::PkK(pk) => Terminal::PkK(pk.clone()),
+        Terminal::PkH(pk) => Terminal::PkH(pk.clone()),
+        Terminal::RawPkH(hash) => Terminal::RawPkH(*hash),
+        Terminal::After(locktime) => Terminal::After(*locktime),
+        Terminal::Older(sequence) => Terminal::Older(*sequence),
+        Terminal::Sha256(hash) => Terminal::Sha256(*hash),
+        Terminal::Hash256(hash) => Terminal::Hash256(*hash),
+        Terminal::Ripemd160(hash) => Terminal::Ripemd160(*hash),
+        Terminal::Hash160(hash) => Terminal::Hash160(*hash),
+        Terminal::Alt(x) => Terminal::Alt(sub(x)?),
+        Terminal::Swap(x) => Terminal::Swap(sub(x)?),
+        Terminal::Check(x) => Terminal::Check(sub(x)?),
+        Terminal::DupIf(x) => Terminal::DupIf(sub(x)?),
+        Terminal::Verify(x) => Terminal::Verify(sub(x)?),
+        Terminal::NonZero(x) => Terminal::NonZero(sub(x)?),
+        Terminal::ZeroNotEqual(x) => Terminal::ZeroNotEqual(sub(x)?),
+        Terminal::AndV(x, y) => Terminal::AndV(sub(x)?, sub(y)?),
+        Terminal::AndB(x, y) => Terminal::AndB(sub(x)?, sub(y)?),
+        Terminal::AndOr(x, y, z) => Terminal::AndOr(sub(x)?, sub(y)?, sub(z)?),
+        Terminal::OrB(x, z) => Terminal::OrB(sub(x)?, sub(z)?),
+        Terminal::OrD(x, z) => Terminal::OrD(sub(x)?, sub(z)?),
+        Terminal::OrC(x, z) => Terminal::OrC(sub(x)?, sub(z)?),
+        Terminal::OrI(x, z) => Terminal::OrI(sub(x)?, sub(z)?),
+        Terminal::Thresh(k, subs) => {
+            Terminal::Thresh(*k, subs.iter().map(|x| sub(x)).collect::<Result<_>>()?)
+        }
+        Terminal::Multi(k, pks) => Terminal::Multi(*k, pks.clone()),
+        Terminal::MultiA(k, pks) => Terminal::MultiA(*k, pks.clone()),
+    })
+}
+
+// Called as thresh($k, $subs_array) or as thresh($k, $sub1, $sub2, ...)
+fn thresh_args<T: FromValue>(args: Array) -> Result<(usize, Vec<T>)> {
+    let args = args.check_varlen(2, usize::MAX)?;
+    let is_array_call = args.len() == 2 && args[1].is_array();
+    let mut args_iter = args.into_iter();
+    let k: usize = args_iter.next_into()?;
+    let subs = if is_array_call {
+        args_iter.next_into::<Value>()?.into_vec_of()?
+    } else {
+        args_iter.map(T::from_value).collect::<Result<_>>()?
+    };
+    Ok((k, subs))
+}
+
+impl TryFrom<Value> for AnyMiniscript {
+    type Error = Error;
+    fn try_from(value: Value) -> Result<Self> {
+        match value {
+            Value::Miniscript(ms) => Ok(ms),
+            other => Err(Error::NotMiniscript(other.into())),
+        }
+    }
+}
+
+// Display the raw Miniscript, with no context information
+impl fmt::Display for AnyMiniscript {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        with_ms!(self, ms => write!(f, "{}", ms))
+    }
+}
+
+impl PrettyDisplay for AnyMiniscript {
+    const AUTOFMT_ENABLED: bool = true;
+
+    fn pretty_fmt<W: fmt::Write>(&self, f: &mut W, indent: Option<usize>) -> fmt::Result {
+        match (indent, self.ctx()) {
+            // Multi-line fragment tree, annotated with the fragment types as comments
+            (Some(indent), ctx) if with_ms!(self, ms => !ms.branches().is_empty()) => {
+                let ctx_note = iif!(
+                    ctx != ScriptCtx::Segwitv0,
+                    format!(" ({})", ctx),
+                    String::new()
+                );
+                with_ms!(self, ms => fmt_tree(f, ms, indent, "", &ctx_note))
+            }
+            // Round-trip-able one-liner
+            (_, ScriptCtx::Segwitv0) => write!(f, "miniscript(\"{}\")", self),
+            (_, ctx) => write!(f, "miniscript(\"{}\", \"{}\")", self, ctx),
+        }
+    }
+}
+
+fn fmt_tree<W: fmt::Write, Ctx: ScriptContext>(
+    f: &mut W,
+    ms: &Miniscript<Ctx>,
+    indent: usize,
+    suffix: &str,
+    note: &str,
+) -> fmt::Result {
+    // Look through the wrappers for the inner fragment
+    let (mut inner, mut wrappers) = (ms, String::new());
+    while let Terminal::Alt(sub)
+    | Terminal::Swap(sub)
+    | Terminal::Check(sub)
+    | Terminal::DupIf(sub)
+    | Terminal::Verify(sub)
+    | Terminal::NonZero(sub)
+    | Terminal::ZeroNotEqual(sub) = &inner.node
+    {
+        wrappers.push_str(fragment_name(&inner.node).trim_end_matches(':'));
+        inner = sub;
+    }
+
+    let branches = inner.branches();
+    if branches.is_empty() {
+        // Fragments with no miniscript children are displayed on a single line, including their wrappers
+        return write!(f, "{}{} // {}{}", ms, suffix, type_str(ms), note);
+    }
+
+    let (indent_w, inner_indent_w) = (indent * LIST_INDENT_WIDTH, (indent + 1) * LIST_INDENT_WIDTH);
+    if !wrappers.is_empty() {
+        write!(f, "{}:", wrappers)?;
+    }
+    write!(
+        f,
+        "{}( // {}{}",
+        fragment_name(&inner.node),
+        type_str(ms),
+        note
+    )?;
+    if let Terminal::Thresh(k, _) = &inner.node {
+        write!(f, "\n{:inner_indent_w$}{},", "", k)?;
+    }
+    for (i, branch) in branches.iter().enumerate() {
+        write!(f, "\n{:inner_indent_w$}", "")?;
+        let suffix = iif!(i + 1 < branches.len(), ",", "");
+        fmt_tree(f, branch, indent + 1, suffix, "")?;
+    }
+    write!(f, "\n{:indent_w$}){}", "", suffix)
+}
@@ -0,0 +1,486 @@
+use std::cmp;
+
+use miniscript::bitcoin;
+
+use bitcoin::opcodes::{all::*, Class};
+use bitcoin::script::{read_scriptint, Instruction, Script};
+use bitcoin::Opcode;
+
+use super::standardness::ScriptCtx;
+
+/// The maximum combined size of the stack and altstack, in items
+const MAX_STACK_SIZE: usize = 1000;
+
+/// The maximum number of keys for OP_CHECKMULTISIG(VERIFY)
+const MAX_PUBKEYS_PER_MULTISIG: usize = 20;
+use crate::runtime::scope::{Mutable, ScopeRef};
+use crate::runtime::{Array, Result, Value};
+
+pub fn attach_stdlib(scope: &ScopeRef<Mutable>) {
+    let mut scope = scope.borrow_mut();
+    scope
+        .set_fn("script::stackEffect", fns::stackEffect)
+        .unwrap();
+}
+
+#[allow(non_snake_case)]
+pub mod fns {
+    use super::*;
+
+    /// Statically analyze the script's effect on the stack and altstack
+    ///
+    /// script::stackEffect(Script, String ctx="segwitv0") -> Array<Tagged>
+    ///
+    /// Returns the number of stack items "consumed" from the initial stack and "produced" onto it,
+    /// the "max_depth" reached (counting the consumed items), the same for the "altstack", the
+    /// stack effects of each OP_IF's "branches", and any "issues" found (like OP_IF branches that
+    /// leave the stack at different heights, or stack depths that could not be determined).
+    ///
+    /// The ctx is one of "legacy", "segwitv0" or "tapscript", which determines the available opcodes.
+    pub fn stackEffect(args: Array, _: &ScopeRef) -> Result<Value> {
+        let (script, ctx): (Value, Option<ScriptCtx>) = args.args_into()?;
+        analyze(&script.into_script()?, ctx.unwrap_or(ScriptCtx::Segwitv0))
+    }
+}
+
+/// A stack whose items are tracked relative to the untouched part of the initial stack
+#[derive(Debug, Clone, Default)]
+struct Stack {
+    /// Items pushed onto the stack, as known constant numbers (where possible)
+    items: Vec<Option<i64>>,
+    /// The number of items popped from the initial stack
+    consumed: usize,
+    /// The maximum net change in stack height (pushed minus popped items) reached
+    max_net: isize,
+}
+
+impl Stack {
+    fn push(&mut self, item: Option<i64>) {
+        self.items.push(item);
+        self.max_net = cmp::max(self.max_net, self.net());
+    }
+    fn pop(&mut self) -> Option<i64> {
+        self.items.pop().unwrap_or_else(|| {
+            self.consumed += 1;
+            None
+        })
+    }
+    fn net(&self) -> isize {
+        self.items.len() as isize - self.consumed as isize
+    }
+    /// Apply the effect of another (sub-)Stack analysis on top of this one
+    fn apply(&mut self, other: &Stack) {
+        self.max_net = cmp::max(self.max_net, self.net() + other.max_net);
+        for _ in 0..other.consumed {
+            self.pop();
+        }
+        self.items.extend(&other.items);
+    }
+    /// Pop n items, then push back the items at the given depths (counted from the top, before popping)
+    fn rearrange(&mut self, n: usize, depths: &[usize]) {
+        let popped: Vec<_> = (0..n).map(|_| self.pop()).collect();
+        for depth in depths {
+            self.push(popped[*depth]);
+        }
+    }
+    fn pop_n(&mut self, n: usize) {
+        for _ in 0..n {
+            self.pop();
+        }
+    }
+    fn tagged_values(&self) -> Vec<Value> {
+        vec![
+            tagged("consumed", self.consumed),
+            tagged("produced", self.items.len()),
+            tagged(
+                "max_depth",
+                (self.consumed as isize + self.max_net) as usize,
+            ),
+        ]
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Effect {
+    main: Stack,
+    alt: Stack,
+    /// Execution reached an OP_RETURN (or another always-failing opcode)
+    terminated: bool,
+    /// The stack height could not be determined from some point onward
+    unknown: bool,
+}
+
+impl Effect {
+    fn into_value(self, extra: Vec<Value>) -> Value {
+        let mut tagged_values = self.main.tagged_values();
+        tagged_values.push(tagged("altstack", self.alt.tagged_values()));
+        if self.terminated {
+            tagged_values.push(tagged("always_fails", true));
+        }
+        if self.unknown {
+            tagged_values.push(tagged("unknown", true));
+        }
+        tagged_values.extend(extra);
+        Value::array(tagged_values)
+    }
+}
+
+struct IfBranches {
+    position: usize,
+    branches: Vec<Effect>,
+}
+
+struct Analysis {
+    ctx: ScriptCtx,
+    ifs: Vec<IfBranches>,
+    issues: Vec<String>,
+}
+
+fn analyze(script: &Script, ctx: ScriptCtx) -> Result<Value> {
+    let instructions = script
+        .instruction_indices()
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    let mut analysis = Analysis {
+        ctx,
+        ifs: vec![],
+        issues: vec![],
+    };
+    let (effect, end) = analyze_block(&mut instructions.into_iter(), &mut analysis);
+    if let Some((pos, op)) = end {
+        analysis
+            .issues
+            .push(format!("{} at position {} has no matching OP_IF", op, pos));
+    }
+    analysis.ifs.sort_by_key(|branches| branches.position);
+
+    let branches = analysis.ifs.into_iter().map(|if_branches| {
+        let branches = if_branches.branches.into_iter();
+        Value::array(vec![
+            tagged("position", if_branches.position),
+            tagged(
+                "branches",
+                branches.map(|b| b.into_value(vec![])).collect::<Vec<_>>(),
+            ),
+        ])
+    });
+    let issues = analysis.issues.into_iter().map(Value::from);
+    Ok(effect.into_value(vec![
+        tagged("branches", branches.collect::<Vec<_>>()),
+        tagged("issues", issues.collect::<Vec<_>>()),
+    ]))
+}
+
+type Instructions<'a> = std::vec::IntoIter<(usize, Instruction<'a>)>;
+
+/// Analyze instructions up to the end of the script, or up to an OP_ELSE/OP_ENDIF (which is returned)
+fn analyze_block(
+    instructions: &mut Instructions,
+    analysis: &mut Analysis,
+) -> (Effect, Option<(usize, Opcode)>) {
+    let mut effect = Effect::default();
+    while let Some((pos, instruction)) = instructions.next() {
+        let op = match instruction {
+            // Once the stack height is unknown, only the OP_IF structure is followed
+            Instruction::PushBytes(_) if effect.unknown => continue,
+            Instruction::PushBytes(push) => {
+                let num = read_scriptint(push.as_bytes()).ok();
+                effect.main.push(num);
+                continue;
+            }
+            Instruction::Op(op) => op,
+        };
+        match op {
+            OP_IF | OP_NOTIF => {
+                if !effect.unknown {
+                    effect.main.pop();
+                }
+                let nested_issues_at = analysis.issues.len();
+                let mut branches = vec![];
+                let end = loop {
+                    let (branch, end) = analyze_block(instructions, analysis);
+                    branches.push(branch);
+                    match end {
+                        Some((_, OP_ELSE)) => continue,
+                        end => break end,
+                    }
+                };
+                let if_issues_at = analysis.issues.len();
+                if end.is_none() {
+                    analysis.issues.push(format!(
+                        "{} at position {} has no matching OP_ENDIF",
+                        op, pos
+                    ));
+                }
+                // A single OP_ELSE-less branch is equivalent to an empty else branch
+                if branches.len() == 1 {
+                    branches.push(Effect::default());
+                }
+                if !effect.unknown {
+                    merge_branches(&mut effect, pos, &branches, analysis);
+                }
+                // Report this OP_IF's issues before the issues of the OP_IFs nested within it
+                let if_issues = analysis.issues.len() - if_issues_at;
+                analysis.issues[nested_issues_at..].rotate_right(if_issues);
+                analysis.ifs.push(IfBranches {
+                    position: pos,
+                    branches,
+                });
+            }
+            OP_ELSE | OP_ENDIF => return (effect, Some((pos, op))),
+            _ if effect.unknown => {}
+            _ => apply_op(&mut effect, op, pos, analysis),
+        }
+    }
+    (effect, None)
+}
+
+/// Apply the effects of the OP_IF branches, checking that they leave the stacks at the same height
+fn merge_branches(effect: &mut Effect, pos: usize, branches: &[Effect], analysis: &mut Analysis) {
+    let live: Vec<&Effect> = branches.iter().filter(|b| !b.terminated).collect();
+    if live.is_empty() {
+        effect.terminated = true;
+        return;
+    }
+    if live.iter().any(|b| b.unknown) {
+        effect.unknown = true;
+        return;
+    }
+
+    let nets = |stack: fn(&Effect) -> &Stack| {
+        live.iter()
+            .map(|branch| stack(branch).net())
+            .collect::<Vec<_>>()
+    };
+    let (main_nets, alt_nets) = (nets(|b| &b.main), nets(|b| &b.alt));
+    if main_nets.iter().any(|net| *net != main_nets[0]) {
+        analysis.issues.push(format!(
+            "OP_IF at position {} has branches that leave different stack heights ({})",
+            pos,
+            fmt_nets(&main_nets)
+        ));
+    }
+    if alt_nets.iter().any(|net| *net != alt_nets[0]) {
+        analysis.issues.push(format!(
+            "OP_IF at position {} has branches that leave different altstack heights ({})",
+            pos,
+            fmt_nets(&alt_nets)
+        ));
+    }
+
+    // Continue with the branch that consumes the most items. Known constants are
+    // only retained if they're the same in all branches.
+    let applied: Vec<Effect> = live
+        .iter()
+        .map(|branch| {
+            let mut applied = effect.clone();
+            applied.main.apply(&branch.main);
+            applied.alt.apply(&branch.alt);
+            applied
+        })
+        .collect();
+    let mut merged = applied
+        .iter()
+        .max_by_key(|e| (e.main.consumed, e.alt.consumed))
+        .unwrap()
+        .clone();
+    merged.main.max_net = applied.iter().map(|e| e.main.max_net).max().unwrap();
+    merged.alt.max_net = applied.iter().map(|e| e.alt.max_net).max().unwrap();
+    for other in &applied {
+        forget_differing(&mut merged.main, &other.main);
+        forget_differing(&mut merged.alt, &other.alt);
+    }
+    *effect = merged;
+}
+
+fn forget_differing(stack: &mut Stack, other: &Stack) {
+    let other_items = other.items.iter().rev().chain(std::iter::repeat(&None));
+    for (item, other_item) in stack.items.iter_mut().rev().zip(other_items) {
+        if item != other_item {
+            *item = None;
+        }
+    }
+}
+
+// Record an issue for an opcode that can never succeed. The analysis does not continue past it, to
+// avoid tracking an unbounded number of stack items.
+fn always_fails(effect: &mut Effect, analysis: &mut Analysis, issue: String) {
+    analysis.issues.push(issue);
+    effect.terminated = true;
+    effect.unknown = true;
+}
+
+fn apply_op(effect: &mut Effect, op: Opcode, pos: usize, analysis: &mut Analysis) {
+    let stack = &mut effect.main;
+    match op {
+        // Flow control and no-ops
+        OP_NOP | OP_NOP1 | OP_NOP4 | OP_NOP5 | OP_NOP6 | OP_NOP7 | OP_NOP8 | OP_NOP9 | OP_NOP10
+        | OP_CODESEPARATOR => {}
+        OP_CLTV | OP_CSV => stack.rearrange(1, &[0]),
+        OP_VERIFY => stack.pop_n(1),
+        OP_RETURN => effect.terminated = true,
+
+        // Altstack
+        OP_TOALTSTACK => {
+            let item = stack.pop();
+            effect.alt.push(item);
+        }
+        OP_FROMALTSTACK => {
+            let item = effect.alt.pop();
+            stack.push(item);
+        }
+
+        // Stack manipulation
+        OP_DROP => stack.pop_n(1),
+        OP_2DROP => stack.pop_n(2),
+        OP_DUP => stack.rearrange(1, &[0, 0]),
+        OP_2DUP => stack.rearrange(2, &[1, 0, 1, 0]),
+        OP_3DUP => stack.rearrange(3, &[2, 1, 0, 2, 1, 0]),
+        OP_OVER => stack.rearrange(2, &[1, 0, 1]),
+        OP_2OVER => stack.rearrange(4, &[3, 2, 1, 0, 3, 2]),
+        OP_NIP => stack.rearrange(2, &[0]),
+        OP_SWAP => stack.rearrange(2, &[0, 1]),
+        OP_2SWAP => stack.rearrange(4, &[1, 0, 3, 2]),
+        OP_ROT => stack.rearrange(3, &[1, 0, 2]),
+        OP_2ROT => stack.rearrange(6, &[3, 2, 1, 0, 5, 4]),
+        OP_TUCK => stack.rearrange(2, &[0, 1, 0]),
+        OP_DEPTH => stack.push(None),
+        OP_SIZE => {
+            stack.rearrange(1, &[0]);
+            stack.push(None);
+        }
+        OP_IFDUP => {
+            match stack.pop() {
+                Some(0) => stack.push(Some(0)),
+                Some(n) => {
+                    stack.push(Some(n));
+                    stack.push(Some(n));
+                }
+                None => {
+                    stack.push(None);
+                    analysis.issues.push(format!(
+                        "OP_IFDUP at position {} leaves a variable stack height, assuming no duplication",
+                        pos
+                    ));
+                }
+            };
+        }
+        OP_PICK | OP_ROLL => {
+            let n = match stack.pop() {
+                Some(n) if n < 0 || n >= MAX_STACK_SIZE as i64 => {
+                    return always_fails(effect, analysis, format!(
+                        "{} at position {} has an out of range depth of {}",
+                        op, pos, n
+                    ));
+                }
+                Some(n) => n as usize,
+                _ => {
+                    analysis.issues.push(format!(
+                        "{} at position {} has a non-constant depth, assuming 0",
+                        op, pos
+                    ));
+                    0
+                }
+            };
+            // Keep the items above the nth (and the nth itself for OP_PICK), then move/copy it to the top
+            let kept = if op == OP_PICK { n + 1 } else { n };
+            let mut depths: Vec<usize> = (0..kept).rev().collect();
+            depths.push(n);
+            stack.rearrange(n + 1, &depths);
+        }
+
+        // Tapscript-only opcodes, which are OP_SUCCESSx/disabled in the other contexts
+        OP_CHECKSIGADD if analysis.ctx == ScriptCtx::Tapscript => {
+            stack.pop_n(3);
+            stack.push(None);
+        }
+
+        // Multisig, with the number of keys/signatures popped from the stack (disabled in tapscript)
+        OP_CHECKMULTISIG | OP_CHECKMULTISIGVERIFY if analysis.ctx != ScriptCtx::Tapscript => {
+            // The number of keys, followed by the number of signatures (which cannot exceed it)
+            let mut max_n = MAX_PUBKEYS_PER_MULTISIG;
+            for _ in 0..2 {
+                match stack.pop() {
+                    Some(n) if n < 0 || n > max_n as i64 => {
+                        return always_fails(effect, analysis, format!(
+                            "{} at position {} has an out of range number of keys/signatures ({})",
+                            op, pos, n
+                        ));
+                    }
+                    Some(n) => {
+                        stack.pop_n(n as usize);
+                        max_n = n as usize;
+                    }
+                    _ => {
+                        analysis.issues.push(format!(
+                            "{} at position {} has a non-constant number of keys/signatures, the stack height is unknown from here on",
+                            op, pos
+                        ));
+                        effect.unknown = true;
+                        return;
+                    }
+                }
+            }
+            stack.pop_n(1); // the dummy element
+            if op == OP_CHECKMULTISIG {
+                stack.push(None);
+            }
+        }
+
+        op => match op.classify(analysis.ctx.classify_ctx()) {
+            Class::PushNum(num) => stack.push(Some(num as i64)),
+            Class::ReturnOp | Class::IllegalOp => effect.terminated = true,
+            Class::SuccessOp => analysis.issues.push(format!(
+                "{} at position {} is an OP_SUCCESSx opcode, making the script succeed unconditionally",
+                op, pos
+            )),
+            _ => match simple_op_arity(op) {
+                Some((pops, pushes)) => {
+                    stack.pop_n(pops);
+                    for _ in 0..pushes {
+                        stack.push(None);
+                    }
+                }
+                None => analysis.issues.push(format!(
+                    "{} at position {} has an unknown stack effect",
+                    op, pos
+                )),
+            },
+        },
+    }
+}
+
+/// The number of items popped and pushed by opcodes that replace their inputs with new outputs
+fn simple_op_arity(op: Opcode) -> Option<(usize, usize)> {
+    Some(match op {
+        OP_1ADD | OP_1SUB | OP_NEGATE | OP_ABS | OP_NOT | OP_0NOTEQUAL | OP_RIPEMD160 | OP_SHA1
+        | OP_SHA256 | OP_HASH160 | OP_HASH256 => (1, 1),
+        OP_EQUAL
+        | OP_ADD
+        | OP_SUB
+        | OP_BOOLAND
+        | OP_BOOLOR
+        | OP_NUMEQUAL
+        | OP_NUMNOTEQUAL
+        | OP_LESSTHAN
+        | OP_GREATERTHAN
+        | OP_LESSTHANOREQUAL
+        | OP_GREATERTHANOREQUAL
+        | OP_MIN
+        | OP_MAX
+        | OP_CHECKSIG => (2, 1),
+        OP_EQUALVERIFY | OP_NUMEQUALVERIFY | OP_CHECKSIGVERIFY => (2, 0),
+        OP_WITHIN => (3, 1),
+        _ => return None,
+    })
+}
+
+fn fmt_nets(nets: &[isize]) -> String {
+    nets.iter()
+        .map(|net| format!("{:+}", net))
+        .collect::<Vec<_>>()
+        .join(" vs ")
+}
+
+fn tagged<V: Into<Value>>(tag: &str, val: V) -> Value {
+    Value::array(vec![tag.into(), val.into()])
+}
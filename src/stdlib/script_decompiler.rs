@@ -0,0 +1,192 @@
+use std::fmt::{self, Write};
+
+use miniscript::bitcoin;
+use miniscript::policy::{semantic::Policy as SemanticPolicy, Liftable};
+use miniscript::{Miniscript, MiniscriptKey, ScriptContext, Segwitv0, Tap};
+
+use bitcoin::hex::DisplayHex;
+use bitcoin::opcodes::all::{OP_DROP, OP_ELSE, OP_ENDIF, OP_IF, OP_NOP4, OP_NOTIF};
+use bitcoin::script::{Instruction, Script, ScriptBuf};
+
+use super::btc::{fmt_minsc_marker, SCRIPT_MARKER_MAGIC_BYTES};
+use super::script_marker::{Marker, MarkerItem, ScriptMarker};
+use super::standardness::minimal_push_opcode;
+use crate::runtime::scope::{Mutable, ScopeRef};
+use crate::runtime::{Array, Result, Value};
+use crate::util::{PrettyDisplay, LIST_INDENT_WIDTH as INDENT};
+
+pub fn attach_stdlib(scope: &ScopeRef<Mutable>) {
+    let mut scope = scope.borrow_mut();
+    scope.set_fn("script::decompile", fns::decompile).unwrap();
+}
+
+#[allow(non_snake_case)]
+pub mod fns {
+    use super::*;
+
+    /// Decompile raw Script bytes into Minsc code that re-constructs it
+    ///
+    /// script::decompile(Bytes|Script, Bool lift=false) -> String
+    ///
+    /// With `lift`, scripts that are valid Miniscript (for segwitv0 or tapscript)
+    /// are lifted into a Minsc policy expression instead.
+    pub fn decompile(args: Array, scope: &ScopeRef) -> Result<Value> {
+        let (script, lift): (Value, Option<bool>) = args.args_into()?;
+        let script = match script {
+            Value::Bytes(bytes) => ScriptBuf::from(bytes),
+            other => other.into_script()?,
+        };
+        Ok(decompile_script(&script, lift.unwrap_or(false), None, scope)?.into())
+    }
+}
+
+/// Decompile the Script into Minsc code, formatted over multiple lines when an indentation level is given
+pub fn decompile_script(
+    script: &Script,
+    lift: bool,
+    indent: Option<usize>,
+    scope: &ScopeRef,
+) -> Result<String> {
+    if lift {
+        if let Some(policy) = lift_policy(script) {
+            return Ok(policy);
+        }
+    }
+    let items = script
+        .iter_with_markers_indices(SCRIPT_MARKER_MAGIC_BYTES)
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    let mut tokens = vec![];
+    let mut items = &items[..];
+    while !items.is_empty() {
+        let (token, consumed) = decompile_next(items, script, scope, indent.is_some());
+        tokens.push(token);
+        items = &items[consumed..];
+    }
+
+    let mut minsc = String::new();
+    fmt_tokens(&mut minsc, tokens, indent)?;
+    Ok(minsc)
+}
+
+/// Decompile the next item(s) into a Minsc token, returning it along with the number of items consumed
+fn decompile_next(
+    items: &[(usize, MarkerItem)],
+    script: &Script,
+    scope: &ScopeRef,
+    multiline: bool,
+) -> (String, usize) {
+    use Instruction::{Op, PushBytes};
+    use MarkerItem::{Instruction as Inst, Marker as Mark};
+
+    match items {
+        // Non-minimal pushes are kept as their raw bytes, since Minsc would encode them minimally
+        [(pos, Inst(PushBytes(push))), ..]
+            if script.as_bytes()[*pos] != minimal_push_opcode(push.as_bytes()) =>
+        {
+            let raw = &script.as_bytes()
+                [*pos..pos + push_encoded_len(script.as_bytes()[*pos], push.len())];
+            (format!("script(0x{})", raw.as_hex()), 1)
+        }
+
+        // Known stdlib fragments
+        [(_, Inst(PushBytes(hash))), (_, Inst(Op(OP_NOP4))), (_, Inst(Op(OP_DROP))), ..]
+            if hash.len() == 32 =>
+        {
+            (format!("ctvVerify(0x{})", hash.as_bytes().as_hex()), 3)
+        }
+
+        [(_, Inst(PushBytes(push))), ..] if push.is_empty() => ("<0>".into(), 1),
+        [(_, Inst(PushBytes(push))), ..] => (format!("<0x{}>", push.as_bytes().as_hex()), 1),
+        [(_, Inst(Op(opcode))), ..] => {
+            let name = opcode.pretty_str();
+            // Opcodes that have no corresponding Minsc variable are constructed from their byte
+            if name.starts_with('<') || scope.borrow().get(&name.as_str().into()).is_some() {
+                (name, 1)
+            } else {
+                (format!("script(0x{:02x})", opcode.to_u8()), 1)
+            }
+        }
+        [(_, Mark(Marker { kind, body })), ..] => {
+            let mut marker = String::new();
+            fmt_minsc_marker(&mut marker, kind, body, multiline).unwrap();
+            (marker, 1)
+        }
+        [] => unreachable!("checked by caller"),
+    }
+}
+
+/// The length of the encoded push instruction, including the opcode and length prefix
+pub fn push_encoded_len(opcode: u8, data_len: usize) -> usize {
+    let prefix_len = match opcode {
+        0x4c => 1, // OP_PUSHDATA1
+        0x4d => 2, // OP_PUSHDATA2
+        0x4e => 4, // OP_PUSHDATA4
+        _ => 0,
+    };
+    1 + prefix_len + data_len
+}
+
+fn fmt_tokens<W: Write>(f: &mut W, tokens: Vec<String>, indent: Option<usize>) -> fmt::Result {
+    let indent = match indent {
+        Some(indent) => indent,
+        None => return write!(f, "`{}`", tokens.join(" ")),
+    };
+    let (else_str, endif_str) = (OP_ELSE.pretty_str(), OP_ENDIF.pretty_str());
+    let (if_str, notif_str) = (OP_IF.pretty_str(), OP_NOTIF.pretty_str());
+
+    let mut depth = indent + 1;
+    writeln!(f, "`")?;
+    for token in tokens {
+        if token == else_str || token == endif_str {
+            depth = depth.saturating_sub(1).max(indent + 1);
+        }
+        writeln!(f, "{:i$}{}", "", token, i = depth * INDENT)?;
+        if token == if_str || token == notif_str || token == else_str {
+            depth += 1;
+        }
+    }
+    write!(f, "{:i$}`", "", i = indent * INDENT)
+}
+
+/// Lift the Script into a Minsc policy expression, if it is valid Miniscript
+fn lift_policy(script: &Script) -> Option<String> {
+    fn lift<Ctx: ScriptContext>(script: &Script) -> Option<String> {
+        let policy = Miniscript::<Ctx::Key, Ctx>::parse(script)
+            .ok()?
+            .lift()
+            .ok()?;
+        let mut minsc = String::new();
+        fmt_policy(&mut minsc, &policy).ok()?;
+        Some(minsc)
+    }
+    lift::<Segwitv0>(script).or_else(|| lift::<Tap>(script))
+}
+
+fn fmt_policy<Pk: MiniscriptKey, W: Write>(f: &mut W, policy: &SemanticPolicy<Pk>) -> fmt::Result {
+    match policy {
+        SemanticPolicy::Key(pk) => write!(f, "pk(0x{})", pk),
+        SemanticPolicy::After(n) => write!(f, "after({})", n.to_consensus_u32()),
+        SemanticPolicy::Older(n) => write!(f, "older({})", n.to_consensus_u32()),
+        SemanticPolicy::Sha256(h) => write!(f, "sha256(0x{})", h),
+        SemanticPolicy::Hash256(h) => write!(f, "hash256(0x{})", h),
+        SemanticPolicy::Ripemd160(h) => write!(f, "ripemd160(0x{})", h),
+        SemanticPolicy::Hash160(h) => write!(f, "hash160(0x{})", h),
+        SemanticPolicy::Threshold(k, subs) => {
+            match (k, subs.len()) {
+                (2, 2) => write!(f, "and(")?,
+                (1, 2) => write!(f, "or(")?,
+                (k, _) => write!(f, "thresh({}, ", k)?,
+            }
+            for (i, sub) in subs.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                fmt_policy(f, sub)?;
+            }
+            write!(f, ")")
+        }
+        // Not representable as Minsc policies
+        SemanticPolicy::Unsatisfiable | SemanticPolicy::Trivial => Err(fmt::Error),
+    }
+}
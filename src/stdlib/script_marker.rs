@@ -1,7 +1,7 @@
 use std::{iter, str};
 
 use bitcoin::opcodes::all as ops;
-use bitcoin::script::{Instruction, Instructions, Script, ScriptBuf};
+use bitcoin::script::{Instruction, InstructionIndices, Script, ScriptBuf};
 use miniscript::bitcoin;
 
 pub trait ScriptMarker {
@@ -9,6 +9,9 @@ pub trait ScriptMarker {
     /// as PUSH(magic_bytes) OP_DROP PUSH(kind) OP_DROP PUSH(body) OP_DROP
     fn iter_with_markers<'a, 'b>(&'a self, magic_bytes: &'b [u8]) -> MarkerIterator<'a, 'b>;
 
+    /// Like iter_with_markers(), also yielding the byte position in the Script where each item starts
+    fn iter_with_markers_indices<'a, 'b>(&'a self, magic_bytes: &'b [u8]) -> MarkerIndices<'a, 'b>;
+
     /// Strip out script markers
     fn strip_markers(&self, magic_bytes: &[u8]) -> Result<ScriptBuf, MarkerError>;
 }
@@ -16,11 +19,15 @@ pub trait ScriptMarker {
 impl ScriptMarker for Script {
     fn iter_with_markers<'a, 'b>(&'a self, magic_bytes: &'b [u8]) -> MarkerIterator<'a, 'b> {
         MarkerIterator {
-            inner: self.instructions().peekable(),
+            inner: self.instruction_indices().peekable(),
             magic_bytes,
         }
     }
 
+    fn iter_with_markers_indices<'a, 'b>(&'a self, magic_bytes: &'b [u8]) -> MarkerIndices<'a, 'b> {
+        MarkerIndices(self.iter_with_markers(magic_bytes))
+    }
+
     fn strip_markers(&self, magic_bytes: &[u8]) -> Result<ScriptBuf, MarkerError> {
         self.iter_with_markers(magic_bytes)
             .filter_map(|item| match item {
@@ -35,10 +42,16 @@ impl ScriptMarker for Script {
 /// An iterator over Script that detects and extracts script markers encoded in it
 #[derive(Debug)]
 pub struct MarkerIterator<'a, 'b> {
-    inner: iter::Peekable<Instructions<'a>>,
+    inner: Instructions<'a>,
     magic_bytes: &'b [u8],
 }
 
+/// An iterator over Script markers and instructions along with their byte position
+#[derive(Debug)]
+pub struct MarkerIndices<'a, 'b>(MarkerIterator<'a, 'b>);
+
+type Instructions<'a> = iter::Peekable<InstructionIndices<'a>>;
+
 /// Items found during iteration, either a standard Script instruction or a Marker
 #[derive(Debug)]
 pub enum MarkerItem<'a> {
@@ -54,34 +67,46 @@ pub struct Marker<'a> {
     pub body: &'a str,
 }
 
-impl<'a, 'b> Iterator for MarkerIterator<'a, 'b> {
-    type Item = Result<MarkerItem<'a>, MarkerError>;
-
-    fn next(&mut self) -> Option<Result<MarkerItem<'a>, MarkerError>> {
+impl<'a, 'b> MarkerIterator<'a, 'b> {
+    fn next_indexed(&mut self) -> Option<Result<(usize, MarkerItem<'a>), MarkerError>> {
         Some(match self.inner.next()? {
             Err(e) => Err(MarkerError::InvalidScript(e)),
-            Ok(Instruction::PushBytes(push))
+            Ok((pos, Instruction::PushBytes(push)))
                 if push.as_bytes() == self.magic_bytes && next_is_drop(&mut self.inner) =>
             {
                 self.inner.next(); // consume the OP_DROP following the marker magic bytes
-                read_marker(&mut self.inner).map(MarkerItem::Marker)
+                read_marker(&mut self.inner).map(|marker| (pos, MarkerItem::Marker(marker)))
             }
-            Ok(instruction) => Ok(MarkerItem::Instruction(instruction)),
+            Ok((pos, instruction)) => Ok((pos, MarkerItem::Instruction(instruction))),
         })
     }
 }
 
-fn next_is_drop(iter: &mut iter::Peekable<Instructions>) -> bool {
-    matches!(iter.peek(), Some(Ok(Instruction::Op(ops::OP_DROP))))
+impl<'a, 'b> Iterator for MarkerIterator<'a, 'b> {
+    type Item = Result<MarkerItem<'a>, MarkerError>;
+
+    fn next(&mut self) -> Option<Result<MarkerItem<'a>, MarkerError>> {
+        Some(self.next_indexed()?.map(|(_, item)| item))
+    }
+}
+
+impl<'a, 'b> Iterator for MarkerIndices<'a, 'b> {
+    type Item = Result<(usize, MarkerItem<'a>), MarkerError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next_indexed()
+    }
+}
+
+fn next_is_drop(iter: &mut Instructions) -> bool {
+    matches!(iter.peek(), Some(Ok((_, Instruction::Op(ops::OP_DROP)))))
 }
 
 // Try to read the marker's contents following the marker magic PUSH-then-DROP (already read by now).
 // Instructions will be consumed from the iterator as long as they match the expected format.
 // The first non-matching instruction will result in an error, but remain available in the iterator
 // so that they can be included when encoding Script to a string.
-fn read_marker<'a>(
-    instructions: &mut iter::Peekable<Instructions<'a>>,
-) -> Result<Marker<'a>, MarkerError> {
+fn read_marker<'a>(instructions: &mut Instructions<'a>) -> Result<Marker<'a>, MarkerError> {
     let res = (|| {
         let kind = str::from_utf8(read_pushdrop(instructions)?)?;
         let body = str::from_utf8(read_pushdrop(instructions)?)?;
@@ -104,9 +129,7 @@ fn read_marker<'a>(
 // Look for PUSH followed by a DROP and consume them from the Instructions iterator.
 // Non-matching instructions will result in an error, but remain in the iterator.
 // Note: a PUSH followed by a non-DROP will leave the non-DROP but consume the PUSH.
-fn read_pushdrop<'a>(
-    instructions: &mut iter::Peekable<Instructions<'a>>,
-) -> Result<&'a [u8], MarkerError> {
+fn read_pushdrop<'a>(instructions: &mut Instructions<'a>) -> Result<&'a [u8], MarkerError> {
     match peek_next_instruction(instructions)? {
         Instruction::PushBytes(push) => {
             let push_data = push.as_bytes();
@@ -119,7 +142,7 @@ fn read_pushdrop<'a>(
 }
 
 // Verify that the next instruction is an OP_DROP and consume it
-fn verify_drop(instructions: &mut iter::Peekable<Instructions>) -> Result<(), MarkerError> {
+fn verify_drop(instructions: &mut Instructions) -> Result<(), MarkerError> {
     match peek_next_instruction(instructions)? {
         Instruction::Op(opcode) if *opcode == ops::OP_DROP => {
             let _ = instructions.next().expect("just peeked at");
@@ -132,10 +155,10 @@ fn verify_drop(instructions: &mut iter::Peekable<Instructions>) -> Result<(), Ma
 // Peek at the next instruction first, so that non-matching script instructions
 // are not prematurely consumed from the inner Instructions iterator.
 fn peek_next_instruction<'a, 'b>(
-    instructions: &'b mut iter::Peekable<Instructions<'a>>,
+    instructions: &'b mut Instructions<'a>,
 ) -> Result<&'b Instruction<'a>, MarkerError> {
     match instructions.peek() {
-        Some(Ok(inst)) => Ok(inst),
+        Some(Ok((_, inst))) => Ok(inst),
         Some(Err(e)) => Err(MarkerError::InvalidMarkScript(e.clone())),
         None => Err(MarkerError::EarlyEos),
     }
@@ -0,0 +1,216 @@
+use std::convert::TryFrom;
+
+use miniscript::bitcoin;
+
+use bitcoin::opcodes::{all::*, Class, ClassifyContext};
+use bitcoin::script::{Builder as ScriptBuilder, Instruction, PushBytesBuf, Script, ScriptBuf};
+use bitcoin::Opcode;
+
+use super::btc::SCRIPT_MARKER_MAGIC_BYTES;
+use super::script_decompiler::push_encoded_len;
+use super::script_marker::{Marker, MarkerItem, ScriptMarker};
+use super::standardness::{minimal_push_opcode, MAX_SCRIPT_ELEMENT_SIZE};
+use crate::runtime::scope::{Mutable, ScopeRef};
+use crate::runtime::{Array, Result, Value};
+
+pub fn attach_stdlib(scope: &ScopeRef<Mutable>) {
+    let mut scope = scope.borrow_mut();
+    scope.set_fn("script::optimize", fns::optimize).unwrap();
+}
+
+#[allow(non_snake_case)]
+pub mod fns {
+    use super::*;
+
+    /// Apply peephole optimizations to the Script, returning the optimized Script and the number of bytes saved
+    ///
+    /// script::optimize(Script, Bool assume_valid=false) -> Script:Number
+    ///
+    /// Script markers are preserved, and act as boundaries that rewrites don't cross (as do OP_CODESEPARATORs).
+    /// Non-minimal pushes are kept byte-for-byte.
+    ///
+    /// With `assume_valid`, rewrites that remove checks which could fail the script on malformed witness
+    /// input are applied too (like the number check performed by `0 OP_ADD`, or the stack underflow of
+    /// `OP_DUP OP_DROP`). This is only safe if the witness is known to be well-formed.
+    pub fn optimize(args: Array, _: &ScopeRef) -> Result<Value> {
+        let (script, assume_valid): (ScriptBuf, Option<bool>) = args.args_into()?;
+        let optimized = optimize_script(&script, assume_valid.unwrap_or(false))?;
+        let saved = script.len() as i64 - optimized.len() as i64;
+        Ok(Value::array(vec![optimized.into(), saved.into()]))
+    }
+}
+
+/// An owned Script instruction
+#[derive(Debug, Clone, PartialEq)]
+enum Inst {
+    Op(Opcode),
+    Push(PushBytesBuf),
+    /// A non-minimal push, kept as its raw bytes and left untouched by the rewrite rules
+    /// (it may fail under MINIMALDATA, which is consensus for Tapscript)
+    Raw(Vec<u8>),
+}
+
+pub fn optimize_script(script: &Script, assume_valid: bool) -> Result<ScriptBuf> {
+    let mut builder = ScriptBuilder::new();
+    let mut segment = vec![];
+    for item in script.iter_with_markers_indices(SCRIPT_MARKER_MAGIC_BYTES) {
+        let (pos, item) = item?;
+        match item {
+            MarkerItem::Instruction(Instruction::Op(OP_CODESEPARATOR)) => {
+                builder = write_insts(builder, optimize_insts(segment, assume_valid));
+                builder = builder.push_opcode(OP_CODESEPARATOR);
+                segment = vec![];
+            }
+            MarkerItem::Instruction(Instruction::Op(op)) => segment.push(Inst::Op(op)),
+            MarkerItem::Instruction(Instruction::PushBytes(push)) => {
+                let opcode = script.as_bytes()[pos];
+                segment.push(if opcode == minimal_push_opcode(push.as_bytes()) {
+                    Inst::Push(push.to_owned())
+                } else {
+                    let len = push_encoded_len(opcode, push.len());
+                    Inst::Raw(script.as_bytes()[pos..pos + len].to_vec())
+                })
+            }
+            MarkerItem::Marker(Marker { kind, body }) => {
+                builder = write_insts(builder, optimize_insts(segment, assume_valid));
+                segment = vec![];
+                for data in [SCRIPT_MARKER_MAGIC_BYTES, kind.as_bytes(), body.as_bytes()] {
+                    builder = builder
+                        .push_slice(<&bitcoin::script::PushBytes>::try_from(data)?)
+                        .push_opcode(OP_DROP);
+                }
+            }
+        }
+    }
+    builder = write_insts(builder, optimize_insts(segment, assume_valid));
+    Ok(builder.into_script())
+}
+
+/// Apply the rewrite rules repeatedly, until no more rules match
+fn optimize_insts(mut insts: Vec<Inst>, assume_valid: bool) -> Vec<Inst> {
+    let mut changed = true;
+    while changed {
+        changed = false;
+        let mut i = 0;
+        while i < insts.len() {
+            let rewritten = rewrite(&insts[i..]).or_else(|| {
+                assume_valid
+                    .then(|| rewrite_assume_valid(&insts[i..]))
+                    .flatten()
+            });
+            if let Some((matched_len, replacement)) = rewritten {
+                insts.splice(i..i + matched_len, replacement);
+                changed = true;
+                // Step back to allow rules to match against the preceding instruction
+                i = i.saturating_sub(1);
+            } else {
+                i += 1;
+            }
+        }
+    }
+    insts
+}
+
+/// Match the rewrite rules at the start of the instructions, returning the number of
+/// instructions matched and their replacement. These preserve the script semantics
+/// exactly, including failing on the same invalid inputs.
+fn rewrite(insts: &[Inst]) -> Option<(usize, Vec<Inst>)> {
+    use Inst::Op;
+
+    // An item verified to be equal to a constant, dropped and then pushed again
+    if let [Op(OP_DUP), item, Op(OP_EQUALVERIFY), Op(OP_DROP), pushed, ..] = insts {
+        if is_push(item) && item == pushed {
+            return Some((5, insts[..3].to_vec()));
+        }
+    }
+
+    let (first, second) = match insts {
+        [first, second, ..] => (first, second),
+        _ => return None,
+    };
+    Some(match (first, num(first), second) {
+        // Use the VERIFY variant of opcodes followed by an OP_VERIFY
+        (Op(op), _, Op(OP_VERIFY)) if verify_variant(*op).is_some() => {
+            (2, vec![Op(verify_variant(*op).unwrap())])
+        }
+
+        // Arithmetic with constants
+        (_, Some(1), Op(OP_ADD)) => (2, vec![Op(OP_1ADD)]),
+        (_, Some(1), Op(OP_SUB)) => (2, vec![Op(OP_1SUB)]),
+
+        // Stack manipulation with constants
+        (_, Some(0), Op(OP_PICK)) => (2, vec![Op(OP_DUP)]),
+        (_, Some(1), Op(OP_PICK)) => (2, vec![Op(OP_OVER)]),
+        (_, Some(1), Op(OP_ROLL)) => (2, vec![Op(OP_SWAP)]),
+        (_, Some(2), Op(OP_ROLL)) => (2, vec![Op(OP_ROT)]),
+
+        // Pushes that get dropped right away (oversized pushes fail the script and are kept)
+        (Inst::Push(push), _, Op(OP_DROP)) if push.len() <= MAX_SCRIPT_ELEMENT_SIZE => (2, vec![]),
+        (_, Some(_), Op(OP_DROP)) => (2, vec![]),
+        (Op(OP_DROP), _, Op(OP_DROP)) => (2, vec![Op(OP_2DROP)]),
+
+        _ => return None,
+    })
+}
+
+/// Rewrite rules that remove operations which would fail the script on malformed input,
+/// like the number check performed by `0 OP_ADD` or the stack underflow of `OP_DUP OP_DROP`
+fn rewrite_assume_valid(insts: &[Inst]) -> Option<(usize, Vec<Inst>)> {
+    use Inst::Op;
+    let (first, second) = match insts {
+        [first, second, ..] => (first, second),
+        _ => return None,
+    };
+    Some(match (first, num(first), second) {
+        (_, Some(0), Op(OP_ADD | OP_SUB)) => (2, vec![]),
+        (_, Some(0), Op(OP_ROLL)) => (2, vec![]),
+        (Op(OP_DUP), _, Op(OP_DROP)) => (2, vec![]),
+        (Op(OP_SWAP), _, Op(OP_SWAP)) => (2, vec![]),
+        (Op(OP_TOALTSTACK), _, Op(OP_FROMALTSTACK)) => (2, vec![]),
+        _ => return None,
+    })
+}
+
+/// Whether the instruction pushes a constant (data pushes, OP_0 and OP_1NEGATE to OP_16)
+fn is_push(inst: &Inst) -> bool {
+    matches!(inst, Inst::Push(_)) || num(inst).is_some()
+}
+
+fn verify_variant(op: Opcode) -> Option<Opcode> {
+    Some(match op {
+        OP_EQUAL => OP_EQUALVERIFY,
+        OP_NUMEQUAL => OP_NUMEQUALVERIFY,
+        OP_CHECKSIG => OP_CHECKSIGVERIFY,
+        OP_CHECKMULTISIG => OP_CHECKMULTISIGVERIFY,
+        _ => return None,
+    })
+}
+
+/// The number pushed by the instruction, for OP_0 and OP_1NEGATE to OP_16 (or their push equivalents)
+fn num(inst: &Inst) -> Option<i64> {
+    match inst {
+        Inst::Op(op) => match op.classify(ClassifyContext::Legacy) {
+            Class::PushNum(num) => Some(num as i64),
+            _ => None,
+        },
+        // OP_0 is parsed as an empty push
+        Inst::Push(push) if push.is_empty() => Some(0),
+        Inst::Push(_) | Inst::Raw(_) => None,
+    }
+}
+
+/// Write the instructions, with non-minimal pushes kept byte-for-byte
+fn write_insts(mut builder: ScriptBuilder, insts: Vec<Inst>) -> ScriptBuilder {
+    for inst in insts {
+        builder = match inst {
+            Inst::Op(op) => builder.push_opcode(op),
+            Inst::Push(push) => builder.push_slice(push),
+            Inst::Raw(raw) => {
+                let mut bytes = builder.into_bytes();
+                bytes.extend(raw);
+                ScriptBuilder::from(bytes)
+            }
+        };
+    }
+    builder
+}
@@ -0,0 +1,391 @@
+use std::convert::TryFrom;
+use std::fmt;
+
+use miniscript::bitcoin;
+
+use bitcoin::opcodes::all::{OP_CHECKMULTISIG, OP_CHECKMULTISIGVERIFY, OP_PUSHNUM_16};
+use bitcoin::opcodes::{Class, ClassifyContext};
+use bitcoin::script::{Instruction, Script};
+use bitcoin::transaction::{Transaction, TxOut};
+use bitcoin::{Opcode, Weight};
+
+use super::btc::sum_amounts;
+use crate::runtime::scope::{Mutable, ScopeRef};
+use crate::runtime::{Array, Error, Result, Value};
+
+// Limits enforced by Bitcoin Core's relay policy as of v30 (or by consensus, where noted)
+const MAX_STANDARD_TX_WEIGHT: Weight = Weight::from_wu(400_000);
+const MAX_STANDARD_P2WSH_SCRIPT_SIZE: usize = 3600;
+const MAX_STANDARD_P2WSH_STACK_ITEMS: usize = 100;
+const MAX_STANDARD_P2WSH_STACK_ITEM_SIZE: usize = 80;
+const MAX_STANDARD_TAPSCRIPT_STACK_ITEM_SIZE: usize = 80;
+const MAX_STANDARD_SCRIPTSIG_SIZE: usize = 1650;
+const MAX_OP_RETURN_RELAY: usize = 100_000; // -datacarriersize, for all OP_RETURN outputs combined
+const MAX_DUST_OUTPUTS_PER_TX: usize = 1; // ephemeral dust
+pub const MAX_SCRIPT_ELEMENT_SIZE: usize = 520; // consensus
+const MAX_OPS_PER_SCRIPT: usize = 201; // consensus, for legacy/segwitv0
+const MAX_PUBKEYS_PER_MULTISIG: usize = 20; // consensus
+
+const TAPROOT_ANNEX_PREFIX: u8 = 0x50;
+const TAPROOT_LEAF_TAPSCRIPT: u8 = 0xc0;
+
+pub fn attach_stdlib(scope: &ScopeRef<Mutable>) {
+    let mut scope = scope.borrow_mut();
+    scope
+        .set_fn("policy::checkScript", fns::checkScript)
+        .unwrap();
+    scope.set_fn("policy::checkTx", fns::checkTx).unwrap();
+}
+
+#[allow(non_snake_case)]
+pub mod fns {
+    use super::*;
+
+    /// Check the script for relay policy (and some consensus) issues, returning them as an array
+    /// of human-readable strings. An empty array means that no issues were found.
+    ///
+    /// policy::checkScript(Script, String ctx="segwitv0") -> Array<String>
+    ///
+    /// The ctx is one of "legacy" (a P2SH redeemScript), "segwitv0" (a P2WSH witnessScript)
+    /// or "tapscript" (a Taproot leaf script).
+    pub fn checkScript(args: Array, _: &ScopeRef) -> Result<Value> {
+        let (script, ctx): (Value, Option<ScriptCtx>) = args.args_into()?;
+        let script = script.into_script()?;
+        let issues = check_script(&script, ctx.unwrap_or(ScriptCtx::Segwitv0));
+        Ok(into_issues_array(issues))
+    }
+
+    /// Check the transaction for relay policy issues (as of Bitcoin Core v30), returning them as an
+    /// array of human-readable strings. An empty array means that no issues were found.
+    ///
+    /// policy::checkTx(Transaction, Array<TxOut> prevouts=[]) -> Array<String>
+    ///
+    /// The witnesses of P2WSH and P2TR script-path inputs are only checked when the prevouts
+    /// spent by the transaction are provided, in the order of the inputs.
+    pub fn checkTx(args: Array, _: &ScopeRef) -> Result<Value> {
+        let (tx, prevouts): (Transaction, Option<Vec<TxOut>>) = args.args_into()?;
+        let prevouts = prevouts.unwrap_or_default();
+        ensure!(
+            prevouts.is_empty() || prevouts.len() == tx.input.len(),
+            Error::InvalidLength(prevouts.len(), tx.input.len())
+        );
+        Ok(into_issues_array(check_tx(&tx, &prevouts)))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScriptCtx {
+    Legacy,
+    Segwitv0,
+    Tapscript,
+}
+
+impl ScriptCtx {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ScriptCtx::Legacy => "legacy",
+            ScriptCtx::Segwitv0 => "segwitv0",
+            ScriptCtx::Tapscript => "tapscript",
+        }
+    }
+
+    pub fn classify_ctx(&self) -> ClassifyContext {
+        match self {
+            ScriptCtx::Tapscript => ClassifyContext::TapScript,
+            ScriptCtx::Legacy | ScriptCtx::Segwitv0 => ClassifyContext::Legacy,
+        }
+    }
+}
+
+impl fmt::Display for ScriptCtx {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl TryFrom<Value> for ScriptCtx {
+    type Error = Error;
+    fn try_from(val: Value) -> Result<Self> {
+        Ok(match val {
+            Value::String(ctx) if ctx == "legacy" => ScriptCtx::Legacy,
+            Value::String(ctx) if ctx == "segwitv0" => ScriptCtx::Segwitv0,
+            Value::String(ctx) if ctx == "tapscript" => ScriptCtx::Tapscript,
+            other => bail!(Error::InvalidScriptContext(other.into())),
+        })
+    }
+}
+
+pub fn check_script(script: &Script, ctx: ScriptCtx) -> Vec<String> {
+    let mut issues = vec![];
+
+    match ctx {
+        ScriptCtx::Legacy if script.len() > MAX_SCRIPT_ELEMENT_SIZE => issues.push(format!(
+            "Script size of {} bytes exceeds the {} bytes limit for P2SH redeemScripts",
+            script.len(),
+            MAX_SCRIPT_ELEMENT_SIZE
+        )),
+        ScriptCtx::Segwitv0 if script.len() > MAX_STANDARD_P2WSH_SCRIPT_SIZE => {
+            issues.push(format!(
+                "Script size of {} bytes exceeds the {} bytes standardness limit for P2WSH witnessScripts",
+                script.len(),
+                MAX_STANDARD_P2WSH_SCRIPT_SIZE
+            ))
+        }
+        _ => {}
+    }
+
+    let mut op_count = 0;
+    // The number pushed by the previous instruction, if it was a constant
+    let mut prev_num = None;
+    for instruction in script.instruction_indices() {
+        let (pos, instruction) = match instruction {
+            Ok(instruction) => instruction,
+            Err(e) => {
+                issues.push(format!("Invalid script: {}", e));
+                break;
+            }
+        };
+        let num = match instruction {
+            Instruction::PushBytes(push) => match push.as_bytes() {
+                [] => Some(0),
+                [n @ 0..=0x7f] => Some(*n as usize),
+                _ => None,
+            },
+            Instruction::Op(op) => match op.classify(ClassifyContext::Legacy) {
+                Class::PushNum(n) => usize::try_from(n).ok(),
+                _ => None,
+            },
+        };
+        match instruction {
+            Instruction::PushBytes(push) => {
+                let data = push.as_bytes();
+                if script.as_bytes()[pos] != minimal_push_opcode(data) {
+                    issues.push(format!(
+                        "Non-minimal push of {} bytes at position {}",
+                        data.len(),
+                        pos
+                    ));
+                }
+                if data.len() > MAX_SCRIPT_ELEMENT_SIZE {
+                    issues.push(format!(
+                        "Push of {} bytes at position {} exceeds the {} bytes element size limit",
+                        data.len(),
+                        pos,
+                        MAX_SCRIPT_ELEMENT_SIZE
+                    ));
+                }
+            }
+            Instruction::Op(op) => {
+                if op.to_u8() > OP_PUSHNUM_16.to_u8() {
+                    op_count += 1;
+                }
+                // Like Bitcoin Core, multisig also counts its number of keys (the maximum if not a constant)
+                if op == OP_CHECKMULTISIG || op == OP_CHECKMULTISIGVERIFY {
+                    op_count += prev_num
+                        .filter(|n| *n <= MAX_PUBKEYS_PER_MULTISIG)
+                        .unwrap_or(MAX_PUBKEYS_PER_MULTISIG);
+                }
+                match op.classify(ctx.classify_ctx()) {
+                    Class::SuccessOp => issues.push(format!(
+                        "{} at position {} is an OP_SUCCESSx opcode, reserved for future upgrades",
+                        op, pos
+                    )),
+                    Class::IllegalOp => {
+                        issues.push(format!("{} at position {} is disabled", op, pos))
+                    }
+                    Class::NoOp if is_upgradable_nop(op) => issues.push(format!(
+                        "{} at position {} is an upgradable NOP, reserved for future upgrades",
+                        op, pos
+                    )),
+                    _ => {}
+                }
+            }
+        }
+        prev_num = num;
+    }
+
+    if ctx != ScriptCtx::Tapscript && op_count > MAX_OPS_PER_SCRIPT {
+        issues.push(format!(
+            "Script has an opcode count of {}, exceeding the limit of {}",
+            op_count, MAX_OPS_PER_SCRIPT
+        ));
+    }
+    issues
+}
+
+pub fn check_tx(tx: &Transaction, prevouts: &[TxOut]) -> Vec<String> {
+    let mut issues = vec![];
+
+    if !(1..=3).contains(&tx.version.0) {
+        issues.push(format!("Non-standard transaction version {}", tx.version.0));
+    }
+    if tx.weight() > MAX_STANDARD_TX_WEIGHT {
+        issues.push(format!(
+            "Transaction weight of {} WU exceeds the {} WU standardness limit",
+            tx.weight().to_wu(),
+            MAX_STANDARD_TX_WEIGHT.to_wu()
+        ));
+    }
+    if let Err(e) = super::btc::check_truc(tx, &[]) {
+        issues.push(e.to_string());
+    }
+
+    for (vin, input) in tx.input.iter().enumerate() {
+        let script_sig = &input.script_sig;
+        if script_sig.len() > MAX_STANDARD_SCRIPTSIG_SIZE {
+            issues.push(format!(
+                "Input #{} scriptSig size of {} bytes exceeds the {} bytes standardness limit",
+                vin,
+                script_sig.len(),
+                MAX_STANDARD_SCRIPTSIG_SIZE
+            ));
+        }
+        if !script_sig.is_push_only() {
+            issues.push(format!("Input #{} scriptSig is not push-only", vin));
+        }
+
+        // Advisory only, this is not a relay policy rule
+        let sequence = input.sequence;
+        if sequence.is_relative_lock_time() && tx.version.0 < 2 {
+            issues.push(format!(
+                "Input #{} sequence {:#x} has a relative timelock, which is not enforced for transaction version {} (advisory)",
+                vin, sequence.0, tx.version.0
+            ));
+        }
+
+        if let Some(prevout) = prevouts.get(vin) {
+            check_witness(
+                vin,
+                input.witness.to_vec(),
+                &prevout.script_pubkey,
+                &mut issues,
+            );
+        }
+    }
+
+    let op_return_size: usize = (tx.output.iter())
+        .filter(|out| out.script_pubkey.is_op_return())
+        .map(|out| out.script_pubkey.len())
+        .sum();
+    if op_return_size > MAX_OP_RETURN_RELAY {
+        issues.push(format!(
+            "OP_RETURN outputs total script size of {} bytes exceeds the {} bytes standardness limit",
+            op_return_size, MAX_OP_RETURN_RELAY
+        ));
+    }
+
+    let is_dust = |out: &TxOut| {
+        !out.script_pubkey.is_op_return() && out.value < out.script_pubkey.dust_value()
+    };
+    let dust_outputs = tx.output.iter().filter(|out| is_dust(out)).count();
+    if dust_outputs > MAX_DUST_OUTPUTS_PER_TX {
+        for (vout, output) in tx.output.iter().enumerate().filter(|(_, out)| is_dust(out)) {
+            issues.push(format!(
+                "Output #{} amount of {} is below the dust threshold of {}",
+                vout,
+                output.value,
+                output.script_pubkey.dust_value()
+            ));
+        }
+    } else if dust_outputs > 0 {
+        // A single ephemeral dust output is allowed if the transaction pays zero fees
+        // (it must also get spent by a child in the same package, which is not checked here)
+        if prevouts.is_empty() {
+            issues.push(
+                "Transaction has an ephemeral dust output, which requires zero fees (unverifiable without the prevouts)"
+                    .into(),
+            );
+        } else {
+            let input_total = sum_amounts(prevouts.iter().map(|out| out.value));
+            let output_total = sum_amounts(tx.output.iter().map(|out| out.value));
+            if let (Ok(input_total), Ok(output_total)) = (input_total, output_total) {
+                if input_total != output_total {
+                    issues.push(format!(
+                        "Transaction has an ephemeral dust output, but pays fees ({} in, {} out) rather than zero",
+                        input_total, output_total
+                    ));
+                }
+            }
+        }
+    }
+
+    issues
+}
+
+/// Check the witness items of P2WSH and P2TR script-path spends
+fn check_witness(vin: usize, mut items: Vec<Vec<u8>>, spk: &Script, issues: &mut Vec<String>) {
+    if spk.is_p2wsh() {
+        let script = match items.pop() {
+            Some(script) => Script::from_bytes(&script).to_owned(),
+            None => return,
+        };
+        if items.len() > MAX_STANDARD_P2WSH_STACK_ITEMS {
+            issues.push(format!(
+                "Input #{} has {} witness stack items, exceeding the {} items standardness limit for P2WSH",
+                vin,
+                items.len(),
+                MAX_STANDARD_P2WSH_STACK_ITEMS
+            ));
+        }
+        check_stack_items(vin, &items, MAX_STANDARD_P2WSH_STACK_ITEM_SIZE, issues);
+        for issue in check_script(&script, ScriptCtx::Segwitv0) {
+            issues.push(format!("Input #{} witnessScript: {}", vin, issue));
+        }
+    } else if spk.is_p2tr() {
+        if items.len() >= 2 && items.last().unwrap().first() == Some(&TAPROOT_ANNEX_PREFIX) {
+            issues.push(format!(
+                "Input #{} has a Taproot annex, which is non-standard",
+                vin
+            ));
+            items.pop();
+        }
+        // Key-path spends have a single witness item, script-path spends at least two
+        if items.len() < 2 {
+            return;
+        }
+        let control_block = items.pop().unwrap();
+        let script = items.pop().unwrap();
+        if control_block.first().map(|b| b & 0xfe) == Some(TAPROOT_LEAF_TAPSCRIPT) {
+            check_stack_items(vin, &items, MAX_STANDARD_TAPSCRIPT_STACK_ITEM_SIZE, issues);
+            for issue in check_script(Script::from_bytes(&script), ScriptCtx::Tapscript) {
+                issues.push(format!("Input #{} tapscript: {}", vin, issue));
+            }
+        }
+    }
+}
+
+fn check_stack_items(vin: usize, items: &[Vec<u8>], max_size: usize, issues: &mut Vec<String>) {
+    for (i, item) in items.iter().enumerate() {
+        if item.len() > max_size {
+            issues.push(format!(
+                "Input #{} witness item #{} size of {} bytes exceeds the {} bytes standardness limit",
+                vin,
+                i,
+                item.len(),
+                max_size
+            ));
+        }
+    }
+}
+
+/// The opcode that pushes the data using its minimal encoding (like Bitcoin Core's CheckMinimalPush())
+pub fn minimal_push_opcode(data: &[u8]) -> u8 {
+    match data {
+        [] => 0x00,                                // OP_0
+        [n @ 1..=16] => 0x50 + n,                  // OP_1 to OP_16
+        [0x81] => 0x4f,                            // OP_1NEGATE
+        _ if data.len() <= 75 => data.len() as u8, // OP_PUSHBYTES_N
+        _ if data.len() <= 0xff => 0x4c,           // OP_PUSHDATA1
+        _ if data.len() <= 0xffff => 0x4d,         // OP_PUSHDATA2
+        _ => 0x4e,                                 // OP_PUSHDATA4
+    }
+}
+
+/// OP_NOP1 and OP_NOP4-OP_NOP10 (OP_NOP2/3 are CLTV/CSV)
+fn is_upgradable_nop(op: Opcode) -> bool {
+    matches!(op.to_u8(), 0xb0 | 0xb3..=0xb9)
+}
+
+fn into_issues_array(issues: Vec<String>) -> Value {
+    Value::array(issues.into_iter().map(Value::from).collect())
+}
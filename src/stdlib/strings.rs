@@ -0,0 +1,132 @@
+use crate::runtime::scope::{Mutable, ScopeRef};
+use crate::runtime::{limits, Array, Error, Result, Value};
+
+pub fn attach_stdlib(scope: &ScopeRef<Mutable>) {
+    let mut scope = scope.borrow_mut();
+    scope.set_fn("str::split", fns::split).unwrap();
+    scope.set_fn("str::slice", fns::slice).unwrap();
+    scope.set_fn("str::replace", fns::replace).unwrap();
+    scope.set_fn("str::upper", fns::upper).unwrap();
+    scope.set_fn("str::lower", fns::lower).unwrap();
+    scope.set_fn("str::contains", fns::contains).unwrap();
+    scope.set_fn("str::format", fns::format).unwrap();
+    scope.set_fn("str::fromBytes", fns::fromBytes).unwrap();
+}
+
+#[allow(non_snake_case)]
+pub mod fns {
+    use super::*;
+
+    /// str::split(String, String separator) -> Array<String>
+    pub fn split(args: Array, _: &ScopeRef) -> Result<Value> {
+        let (string, separator): (String, String) = args.args_into()?;
+        limits::check_array_len(string.matches(&separator).count() + 1)?;
+        let parts = string.split(&separator).map(|part| part.to_string().into());
+        Ok(Value::array(parts.collect()))
+    }
+
+    /// Get a substring by character index, up to the end of the string if no length is given
+    ///
+    /// str::slice(String, Number start, Number length=None) -> String
+    pub fn slice(args: Array, _: &ScopeRef) -> Result<Value> {
+        let (string, start, len): (String, usize, Option<usize>) = args.args_into()?;
+        let chars_len = string.chars().count();
+        let len = len.unwrap_or_else(|| chars_len.saturating_sub(start));
+        ensure!(
+            start.checked_add(len).is_some_and(|end| end <= chars_len),
+            Error::ArrayIndexOutOfRange
+        );
+        Ok(string
+            .chars()
+            .skip(start)
+            .take(len)
+            .collect::<String>()
+            .into())
+    }
+
+    /// str::replace(String, String from, String to) -> String
+    pub fn replace(args: Array, _: &ScopeRef) -> Result<Value> {
+        let (string, from, to): (String, String, String) = args.args_into()?;
+        let replaced = string.replace(&from, &to);
+        limits::check_bytes_len(replaced.len())?;
+        Ok(replaced.into())
+    }
+
+    /// str::upper(String) -> String
+    pub fn upper(args: Array, _: &ScopeRef) -> Result<Value> {
+        Ok(args.arg_into::<String>()?.to_uppercase().into())
+    }
+
+    /// str::lower(String) -> String
+    pub fn lower(args: Array, _: &ScopeRef) -> Result<Value> {
+        Ok(args.arg_into::<String>()?.to_lowercase().into())
+    }
+
+    /// str::contains(String, String substring) -> Bool
+    pub fn contains(args: Array, _: &ScopeRef) -> Result<Value> {
+        let (string, substring): (String, String) = args.args_into()?;
+        Ok(string.contains(&substring).into())
+    }
+
+    /// Format a string, replacing `{}` placeholders with the arguments in order, or `{N}` with the N-th argument.
+    /// Strings are inserted as-is, other values using their Minsc representation. Use `{{` and `}}` to escape.
+    ///
+    /// str::format(String template, ...Value) -> String
+    pub fn format(args: Array, _: &ScopeRef) -> Result<Value> {
+        let mut args = args.into_inner().into_iter();
+        let template: String = args.next().ok_or(Error::InvalidArguments)?.into_string()?;
+        let formatted = format_str(&template, &args.collect::<Vec<_>>())?;
+        limits::check_bytes_len(formatted.len())?;
+        Ok(formatted.into())
+    }
+
+    /// Decode UTF-8 encoded Bytes into a String
+    ///
+    /// str::fromBytes(Bytes) -> String
+    pub fn fromBytes(args: Array, _: &ScopeRef) -> Result<Value> {
+        let bytes: Vec<u8> = args.arg_into()?;
+        Ok(String::from_utf8(bytes)?.into())
+    }
+}
+
+fn format_str(template: &str, args: &[Value]) -> Result<String> {
+    let mut formatted = String::with_capacity(template.len());
+    let mut chars = template.chars().peekable();
+    let mut next_index = 0;
+    while let Some(ch) = chars.next() {
+        match (ch, chars.peek()) {
+            ('{', Some('{')) | ('}', Some('}')) => {
+                chars.next();
+                formatted.push(ch);
+            }
+            ('{', _) => {
+                let mut placeholder = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(ch) => placeholder.push(ch),
+                        None => bail!(Error::InvalidFormatString("unclosed {".into())),
+                    }
+                }
+                let index = if placeholder.is_empty() {
+                    next_index += 1;
+                    next_index - 1
+                } else {
+                    placeholder.parse().map_err(|_| {
+                        Error::InvalidFormatString(format!(
+                            "invalid placeholder {{{}}}",
+                            placeholder
+                        ))
+                    })?
+                };
+                match args.get(index).ok_or(Error::FormatMissingArg(index))? {
+                    Value::String(string) => formatted.push_str(string),
+                    other => formatted.push_str(&other.to_string()),
+                }
+            }
+            ('}', _) => bail!(Error::InvalidFormatString("unmatched }".into())),
+            (ch, _) => formatted.push(ch),
+        }
+    }
+    Ok(formatted)
+}
@@ -1,15 +1,25 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
 use std::convert::TryInto;
 use std::fmt;
 use std::sync::Arc;
 
+use bitcoin::bip32::{ChainCode, ChildNumber, Xpub};
 use bitcoin::hashes::{sha256, Hash, HashEngine};
 use bitcoin::key::XOnlyPublicKey;
-use bitcoin::taproot::{LeafVersion, NodeInfo, TapLeafHash, TapNodeHash, TaprootSpendInfo};
-use miniscript::{bitcoin, descriptor::TapTree, DescriptorPublicKey};
-
+use bitcoin::taproot::{
+    ControlBlock, LeafVersion, NodeInfo, TapLeafHash, TapNodeHash, TaprootBuilderError,
+    TaprootSpendInfo, TAPROOT_ANNEX_PREFIX, TAPROOT_CONTROL_MAX_NODE_COUNT,
+};
+use bitcoin::ScriptBuf;
+use miniscript::descriptor::{DescriptorXKey, TapTree};
+use miniscript::policy::{semantic::Policy as SemanticPolicy, Liftable};
+use miniscript::{bitcoin, DescriptorPublicKey};
+
+use super::ec::{self, NUMS_H};
 use super::miniscript::{multi_andor, AndOr};
-use crate::runtime::scope::{Mutable, Scope, ScopeRef};
-use crate::runtime::{Error, Result, Value};
+use crate::runtime::scope::{Mutable, ScopeRef};
+use crate::runtime::{Array, Call, Error, Int, Result, Value};
 use crate::util::{fmt_list, PrettyDisplay, EC};
 use crate::{DescriptorDpk as Descriptor, PolicyDpk as Policy};
 
@@ -18,16 +28,34 @@ pub fn attach_stdlib(scope: &ScopeRef<Mutable>) {
 
     // Taproot Descriptor/TaprootSpendInfo construction
     scope.set_fn("tr", fns::tr).unwrap();
+    scope.set_fn("tr::huffman", fns::huffman).unwrap();
+    scope.set_fn("tr::balanced", fns::balanced).unwrap();
 
     // Functions for extracting information out of Descriptors/TaprootSpendInfo
     scope.set_fn("tr::internalKey", fns::internalKey).unwrap();
     scope.set_fn("tr::outputKey", fns::outputKey).unwrap();
     scope.set_fn("tr::merkleRoot", fns::merkleRoot).unwrap();
     scope.set_fn("tr::scripts", fns::scripts).unwrap();
+    scope.set_fn("tr::leaves", fns::leaves).unwrap();
+
+    // Script-path spending
+    scope.set_fn("tr::witness", fns::witness).unwrap();
+    scope
+        .set_fn("tr::verifyControlBlock", fns::verifyControlBlock)
+        .unwrap();
+
+    // Unlinkable provably unspendable internal keys
+    scope.set_fn("tr::unspendable", fns::unspendable).unwrap();
+    scope
+        .set_fn("tr::verifyUnspendable", fns::verifyUnspendable)
+        .unwrap();
 
     // Convert a tr() descriptor into a TaprootSpendInfo
     scope.set_fn("tr::tapInfo", fns::tapInfo).unwrap();
 
+    // Script with an explicit leaf version
+    scope.set_fn("leaf", fns::leaf).unwrap();
+
     // Low-level leaf/branch hash calculation. Shouldn't be used directly typically.
     scope.set_fn("tr::tapLeaf", fns::tapLeaf).unwrap();
     scope.set_fn("tr::tapBranch", fns::tapBranch).unwrap();
@@ -36,8 +64,6 @@ pub fn attach_stdlib(scope: &ScopeRef<Mutable>) {
 #[allow(non_snake_case)]
 pub mod fns {
     use super::*;
-    use crate::runtime::{Array, Int};
-    use bitcoin::ScriptBuf;
 
     /// Construct a tr() descriptor:
     /// tr(PubKey) -> Descriptor
@@ -50,7 +76,35 @@ pub mod fns {
     /// tr(PubKey, Hash) -> TaprootSpendInfo
     pub fn tr(args: Array, scope: &ScopeRef) -> Result<Value> {
         let (a, b): (Value, Option<Value>) = args.args_into()?;
-        super::tr(a, b, &scope.borrow())
+        super::tr(a, b, scope)
+    }
+
+    /// Construct a Huffman script tree out of the weighted Policies (compiled individually into leaves)
+    /// or Scripts, optionally failing if the tree is deeper than max_depth. Uses TR_UNSPENDABLE if no
+    /// internal key is given.
+    ///
+    /// tr::huffman(PubKey internal_key=TR_UNSPENDABLE, Array<Policy>, Number max_depth=128) -> Descriptor
+    /// tr::huffman(PubKey internal_key=TR_UNSPENDABLE, Array<Script>, Number max_depth=128) -> TapInfo
+    pub fn huffman(args: Array, scope: &ScopeRef) -> Result<Value> {
+        let (internal_key, nodes, max_depth) = shape_args(args, scope)?;
+        let max_depth = max_depth.map_or(Ok(TAPROOT_CONTROL_MAX_NODE_COUNT), Value::into_usize)?;
+        tr_with_shape(internal_key, nodes, TreeShape::Huffman, max_depth)
+    }
+
+    /// Construct a balanced script tree out of the Policies (compiled individually into leaves) or Scripts.
+    /// Uses TR_UNSPENDABLE if no internal key is given.
+    ///
+    /// tr::balanced(PubKey internal_key=TR_UNSPENDABLE, Array<Policy>) -> Descriptor
+    /// tr::balanced(PubKey internal_key=TR_UNSPENDABLE, Array<Script>) -> TapInfo
+    pub fn balanced(args: Array, scope: &ScopeRef) -> Result<Value> {
+        let (internal_key, nodes, extra) = shape_args(args, scope)?;
+        ensure!(extra.is_none(), Error::InvalidArguments);
+        tr_with_shape(
+            internal_key,
+            nodes,
+            TreeShape::Balanced,
+            TAPROOT_CONTROL_MAX_NODE_COUNT,
+        )
     }
 
     /// tr::internalKey(TapInfo) -> PubKey
@@ -109,6 +163,159 @@ pub mod fns {
         Ok(Value::array(scripts_ctrls))
     }
 
+    /// tr::witness(TapInfo, Script|TapLeaf leaf_script, Array<Bytes> stack_items=[], Bytes annex=None) -> Array<Bytes>
+    ///
+    /// Get the complete script-path spend witness for the leaf script, made of the stack items followed
+    /// by the script, its control block and the annex (if provided)
+    pub fn witness(args: Array, _: &ScopeRef) -> Result<Value> {
+        let (tapinfo, leaf, stack_items, annex): (
+            TaprootSpendInfo,
+            Value,
+            Option<Value>,
+            Option<Vec<u8>>,
+        ) = args.args_into()?;
+        let (script, ver) = into_leaf(leaf)?;
+        let ctrl = tapinfo
+            .control_block(&(script.clone(), ver))
+            .ok_or_else(|| Error::TaprootLeafNotFound(script.clone()))?;
+
+        let mut witness = match stack_items {
+            Some(items) => items.map_array(Value::into_bytes)?,
+            None => vec![],
+        };
+        witness.push(script.into_bytes());
+        witness.push(ctrl.serialize());
+        if let Some(annex) = annex {
+            ensure!(
+                annex.first() == Some(&TAPROOT_ANNEX_PREFIX),
+                Error::InvalidAnnex
+            );
+            witness.push(annex);
+        }
+        Ok(Value::array(witness.into_iter().map(Value::from).collect()))
+    }
+
+    /// tr::verifyControlBlock(PubKey output_key, Script, Bytes control_block) -> Bool
+    ///
+    /// Verify that the control block proves the script's commitment in the output key
+    pub fn verifyControlBlock(args: Array, _: &ScopeRef) -> Result<Value> {
+        let (output_key, script, ctrl): (DescriptorPublicKey, ScriptBuf, Vec<u8>) =
+            args.args_into()?;
+        let ctrl = ControlBlock::decode(&ctrl)?;
+        let output_key = definite_xonly(output_key)?;
+        Ok(ctrl
+            .verify_taproot_commitment(&EC, output_key, &script)
+            .into())
+    }
+
+    /// tr::unspendable(Bytes r) -> (PubKey, Bytes r)
+    /// tr::unspendable(Xpub, Bytes r) -> (Xpub, Bytes r)
+    ///
+    /// Get a provably unspendable internal key that cannot be linked to other contracts, as H+r*G for a random
+    /// 32 bytes r (BIP341), or as an xpub with H as its key and a chain code of sha256(r||H), so that every derived
+    /// child is unspendable too. The xpub form takes the network and derivation path of the given xpub (but not its
+    /// key or chain code). Returned as a tuple with r as the proof, which can be checked using tr::verifyUnspendable()
+    /// by cosigners.
+    pub fn unspendable(args: Array, _: &ScopeRef) -> Result<Value> {
+        let (key, r): (Value, Option<Vec<u8>>) = args.args_into()?;
+        Ok(match (key, r) {
+            (Value::Bytes(r), None) => {
+                let key = unspendable_single(r.clone())?;
+                Value::array(vec![key.into(), r.into()])
+            }
+            (Value::PubKey(DescriptorPublicKey::XPub(xpub)), Some(r)) => {
+                let key = DescriptorPublicKey::XPub(unspendable_xpub(xpub, &r));
+                Value::array(vec![key.into(), r.into()])
+            }
+            _ => bail!(Error::InvalidArguments),
+        })
+    }
+
+    /// tr::verifyUnspendable(PubKey, Bytes r) -> Bool
+    ///
+    /// Verify that the key was generated by tr::unspendable() using the given r
+    pub fn verifyUnspendable(args: Array, _: &ScopeRef) -> Result<Value> {
+        let (pk, r): (DescriptorPublicKey, Vec<u8>) = args.args_into()?;
+        Ok(match pk {
+            DescriptorPublicKey::XPub(xpub) => {
+                xpub.xkey.public_key == *NUMS_H
+                    && xpub.xkey.chain_code == unspendable_chain_code(&r)
+            }
+            pk => definite_xonly(pk)? == unspendable_single(r)?,
+        }
+        .into())
+    }
+
+    /// tr::leaves(Descriptor|TapInfo) -> Array<[ "depth": Number, "script": Script, "version": Bytes, "miniscript": String ]>
+    ///
+    /// Get the leaves of the script tree with their depth, ordered by depth and then by script and version
+    /// (TapInfos don't retain the original tree order). The compiled Miniscript is available for Descriptors,
+    /// to show which leaf each Policy was compiled into.
+    pub fn leaves(args: Array, _: &ScopeRef) -> Result<Value> {
+        let mut leaves = match args.arg_into()? {
+            Value::Descriptor(desc @ Descriptor::Tr(_)) => {
+                let (Descriptor::Tr(tr_desc), miniscript::Descriptor::Tr(definite)) =
+                    (&desc, desc.at_derivation_index(0)?)
+                else {
+                    unreachable!("checked to be Tr")
+                };
+                tr_desc
+                    .iter_scripts()
+                    .zip(definite.iter_scripts())
+                    .map(|((depth, ms), (_, definite_ms))| {
+                        let script = definite_ms.encode();
+                        (
+                            depth as usize,
+                            script,
+                            LeafVersion::TapScript,
+                            Some(ms.to_string()),
+                        )
+                    })
+                    .collect::<Vec<_>>()
+            }
+            other => {
+                let tapinfo: TaprootSpendInfo = other.try_into()?;
+                tapinfo
+                    .script_map()
+                    .iter()
+                    .flat_map(|((script, ver), branches)| {
+                        branches
+                            .iter()
+                            .map(move |branch| (branch.len(), script.clone(), *ver, None))
+                    })
+                    .collect()
+            }
+        };
+        leaves.sort_by(
+            |(depth_a, script_a, ver_a, _), (depth_b, script_b, ver_b, _)| {
+                (depth_a, script_a, ver_a.to_consensus()).cmp(&(
+                    depth_b,
+                    script_b,
+                    ver_b.to_consensus(),
+                ))
+            },
+        );
+        Ok(Value::array(
+            leaves
+                .into_iter()
+                .map(|(depth, script, ver, miniscript)| {
+                    let mut tags = vec![
+                        ("depth", depth.into()),
+                        ("script", script.into()),
+                        ("version", vec![ver.to_consensus()].into()),
+                    ];
+                    if let Some(miniscript) = miniscript {
+                        tags.push(("miniscript", miniscript.into()));
+                    }
+                    let tags = tags
+                        .into_iter()
+                        .map(|(tag, val)| Value::array(vec![tag.into(), val]));
+                    Value::array(tags.collect())
+                })
+                .collect(),
+        ))
+    }
+
     /// tr::tapInfo(Descriptor|TapInfo) -> TapInfo
     ///
     /// Convert the Tr Descriptor into a TapInfo (or return TapInfo as-is)
@@ -116,19 +323,24 @@ pub mod fns {
         Ok(Value::TapInfo(args.arg_into()?))
     }
 
-    /// tr::tapLeaf(Script, version=0xc0) -> Hash
+    /// leaf(Number|Bytes version, Script) -> TapLeaf
+    ///
+    /// Tag the Script with a custom leaf version, for use as a tr() script tree leaf
+    pub fn leaf(args: Array, _: &ScopeRef) -> Result<Value> {
+        let (version, script): (Value, ScriptBuf) = args.args_into()?;
+        Ok(Value::TapLeaf(leaf_version(version)?, script))
+    }
+
+    /// tr::tapLeaf(Script|TapLeaf, version=0xc0) -> Hash
     ///
     /// Compute the leaf hash of the given script
     pub fn tapLeaf(args: Array, _: &ScopeRef) -> Result<Value> {
-        let (script, leaf_var): (ScriptBuf, Option<Value>) = args.args_into()?;
-        let leaf_ver = leaf_var.map_or(Ok(LeafVersion::TapScript), |ver| -> Result<_> {
-            Ok(LeafVersion::from_consensus(match ver {
-                Value::Number(Int(num)) => num.try_into()?,
-                Value::Bytes(bytes) if bytes.len() == 1 => bytes[0],
-                _ => bail!(Error::InvalidArguments),
-            })?)
-        })?;
-        let leaf_hash = TapLeafHash::from_script(&script, leaf_ver);
+        let (leaf, leaf_ver): (Value, Option<Value>) = args.args_into()?;
+        let (script, mut ver) = into_leaf(leaf)?;
+        if let Some(leaf_ver) = leaf_ver {
+            ver = leaf_version(leaf_ver)?;
+        }
+        let leaf_hash = TapLeafHash::from_script(&script, ver);
         Ok(Value::Bytes(leaf_hash.to_byte_array().to_vec()))
     }
 
@@ -143,7 +355,7 @@ pub mod fns {
     }
 }
 
-pub fn tr(mut a: Value, b: Option<Value>, scope: &Scope) -> Result<Value> {
+pub fn tr(mut a: Value, b: Option<Value>, scope: &ScopeRef) -> Result<Value> {
     // Convert internal keys provided as Bytes into PubKeys, for compatibility with the Miniscirpt Policy tr() syntax
     if a.is_bytes() {
         a = Value::PubKey(a.try_into()?);
@@ -154,7 +366,11 @@ pub fn tr(mut a: Value, b: Option<Value>, scope: &Scope) -> Result<Value> {
         // Single policy, compiled into a script tree
         // Extracts the internal key from the policy, or uses the TR_UNSPENDABLE key
         (Value::Policy(policy), None) => {
-            descriptor_from_policy(None, tr_unspendable(scope)?, policy)?.into()
+            let unspendable = match has_extractable_key(&policy) {
+                true => None,
+                false => tr_unspendable(scope, Value::Policy(policy.clone()))?,
+            };
+            descriptor_from_policy(None, unspendable, policy)?.into()
         }
 
         // tr(PubKey) -> Descriptor
@@ -176,7 +392,8 @@ pub fn tr(mut a: Value, b: Option<Value>, scope: &Scope) -> Result<Value> {
         // tr(Script) -> TaprootSpendInfo
         // Single Script used as the tree root, with an unspendable internal key
         (Value::Script(script), None) => {
-            let unspendable = tr_unspendable(scope)?.ok_or(Error::TaprootNoViableKey)?;
+            let unspendable = tr_unspendable(scope, Value::Script(script.clone()))?
+                .ok_or(Error::TaprootNoViableKey)?;
             tapinfo_from_tree(definite_xonly(unspendable)?, Value::Script(script))?.into()
         }
 
@@ -203,7 +420,13 @@ pub fn tr(mut a: Value, b: Option<Value>, scope: &Scope) -> Result<Value> {
         // Create a Taproot descriptor for the given Policies, extracting the internal key or using TR_UNSPENDABLE
         // tr(Array<Script>) -> TaprootSpendInfo
         // Create a TaprootSpendInfo for the given Scripts, using TR_UNSPEDABLE as the internal key
-        (Value::Array(nodes), None) => tr_from_array(None, tr_unspendable(scope)?, nodes.0)?.into(),
+        (Value::Array(nodes), None) => {
+            let unspendable = match needs_unspendable(&nodes) {
+                true => tr_unspendable(scope, Value::Array(nodes.clone()))?,
+                false => None,
+            };
+            tr_from_array(None, unspendable, nodes.0)?.into()
+        }
 
         _ => bail!(Error::TaprootInvalidTrUse),
     })
@@ -214,24 +437,6 @@ fn tr_from_array(
     unspendable: Option<DescriptorPublicKey>,
     nodes: Vec<Value>,
 ) -> Result<Value> {
-    // Determine the type of node based on the first Script/Policy. The other nodes are
-    // expected to be of the same type.
-    fn peek_node_type(node: &Value) -> Result<NodeType> {
-        Ok(match node {
-            Value::Script(_) => NodeType::Script,
-            // PubKeys are coercible into Policy
-            Value::Policy(_) | Value::PubKey(_) => NodeType::Policy,
-            Value::WithProb(_, inner) if inner.is_script() => NodeType::Script,
-            Value::WithProb(_, inner) if inner.is_policy_coercible() => NodeType::Policy,
-            Value::Array(array) if array.len() > 0 => peek_node_type(&array[0])?,
-            _ => bail!(Error::TaprootInvalidScript),
-        })
-    }
-    enum NodeType {
-        Script,
-        Policy,
-    }
-
     Ok(if nodes.len() == 0 {
         // Key-path only
         Descriptor::new_tr(internal_key.ok_or(Error::TaprootNoViableKey)?, None)?.into()
@@ -243,6 +448,195 @@ fn tr_from_array(
     })
 }
 
+// Whether tr_from_array() needs TR_UNSPENDABLE when no internal key is given. It doesn't for key-path only
+// (empty) arrays, and for flat arrays of Policies combined with OR when a key can be extracted from one of them.
+fn needs_unspendable(nodes: &[Value]) -> bool {
+    if nodes.is_empty() {
+        return false;
+    }
+    let is_or_policy = matches!(peek_node_type(&nodes[0]), Ok(NodeType::Policy))
+        && !(nodes.len() == 2 && (nodes[0].is_array() || nodes[1].is_array()))
+        && !nodes.iter().any(|node| matches!(node, Value::WithProb(..)));
+    let has_key = || {
+        nodes.iter().any(|node| match node {
+            Value::PubKey(_) => true,
+            Value::Policy(policy) => has_extractable_key(policy),
+            _ => false,
+        })
+    };
+    !(is_or_policy && has_key())
+}
+
+// Whether compile_tr() can extract the internal key from the policy, in which case the unspendable key it is
+// given goes unused. Mirrors rust-miniscript's check for a key that satisfies the policy on its own.
+fn has_extractable_key(policy: &Policy) -> bool {
+    let semantic = match policy.lift() {
+        Ok(semantic) => semantic,
+        Err(_) => return false,
+    };
+    policy.keys().into_iter().any(|key| {
+        let key = SemanticPolicy::Key(key.clone());
+        key.entails(semantic.clone()).unwrap_or(false)
+    })
+}
+
+// Determine the type of node based on the first Script/Policy. The other nodes are
+// expected to be of the same type.
+fn peek_node_type(node: &Value) -> Result<NodeType> {
+    Ok(match node {
+        Value::Script(_) | Value::TapLeaf(..) => NodeType::Script,
+        // PubKeys are coercible into Policy
+        Value::Policy(_) | Value::PubKey(_) => NodeType::Policy,
+        Value::WithProb(_, inner) if inner.is_tapleaf_like() => NodeType::Script,
+        Value::WithProb(_, inner) if inner.is_policy_coercible() => NodeType::Policy,
+        Value::Array(array) if array.len() > 0 => peek_node_type(&array[0])?,
+        _ => bail!(Error::TaprootInvalidScript),
+    })
+}
+enum NodeType {
+    Script,
+    Policy,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum TreeShape {
+    Huffman,
+    Balanced,
+}
+
+// Parse the ([PubKey internal_key,] Array nodes, [extra]) arguments of tr::huffman()/tr::balanced(),
+// using TR_UNSPENDABLE when no internal key is provided
+fn shape_args(
+    args: Array,
+    scope: &ScopeRef,
+) -> Result<(DescriptorPublicKey, Vec<Value>, Option<Value>)> {
+    let (a, b, c): (Value, Option<Value>, Option<Value>) = args.args_into()?;
+    let (internal_key, nodes, extra) = match (a, b, c) {
+        (Value::Array(nodes), extra, None) => (None, nodes, extra),
+        (pk, Some(Value::Array(nodes)), extra) => (Some(pk.try_into()?), nodes, extra),
+        _ => bail!(Error::InvalidArguments),
+    };
+    let internal_key = match internal_key {
+        Some(pk) => pk,
+        None => {
+            tr_unspendable(scope, Value::Array(nodes.clone()))?.ok_or(Error::TaprootNoViableKey)?
+        }
+    };
+    Ok((internal_key, nodes.0, extra))
+}
+
+// Construct a script tree of the given shape out of a flat array of Policies (each compiled into its own leaf)
+// or Scripts. Weights are used for Huffman trees and disallowed for balanced trees.
+fn tr_with_shape(
+    internal_key: DescriptorPublicKey,
+    nodes: Vec<Value>,
+    shape: TreeShape,
+    max_depth: usize,
+) -> Result<Value> {
+    ensure!(!nodes.is_empty(), TaprootBuilderError::EmptyTree);
+    let weighted_nodes = nodes
+        .into_iter()
+        .map(|node| {
+            Ok(match node {
+                Value::WithProb(_, _) if shape == TreeShape::Balanced => {
+                    bail!(Error::InvalidScriptProb)
+                }
+                Value::WithProb(prob, node) => (prob as u32, *node),
+                Value::Array(_) => bail!(Error::TaprootInvalidScript),
+                node => (1, node),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let (depth, tr) = match peek_node_type(&weighted_nodes[0].1)? {
+        NodeType::Policy => {
+            let leaves = weighted_nodes
+                .into_iter()
+                .map(|(weight, policy)| {
+                    Ok((
+                        weight,
+                        TapTree::Leaf(Arc::new(policy.into_policy()?.compile()?)),
+                    ))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            let combine = |a, b| Ok(TapTree::combine(a, b));
+            let tree = match shape {
+                TreeShape::Huffman => huffman_tree(leaves, combine)?,
+                TreeShape::Balanced => balanced_tree(leaves, &combine)?,
+            };
+            let depth = tree.iter().map(|(depth, _)| depth as usize).max();
+            let desc = Descriptor::new_tr(internal_key, Some(tree))?;
+            (depth, desc.into())
+        }
+        NodeType::Script => {
+            let leaves = weighted_nodes
+                .into_iter()
+                .map(|(weight, leaf)| {
+                    let (script, ver) = into_leaf(leaf)?;
+                    Ok((weight, NodeInfo::new_leaf_with_ver(script, ver)))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            let combine = |a, b| Ok(NodeInfo::combine(a, b)?);
+            let root = match shape {
+                TreeShape::Huffman => script_huffman_tree(leaves)?,
+                TreeShape::Balanced => balanced_tree(leaves, &combine)?,
+            };
+            let tapinfo =
+                TaprootSpendInfo::from_node_info(&EC, definite_xonly(internal_key)?, root);
+            let depth = tapinfo
+                .script_map()
+                .values()
+                .flatten()
+                .map(|b| b.len())
+                .max();
+            (depth, tapinfo.into())
+        }
+    };
+    let depth = depth.unwrap_or(0);
+    ensure!(
+        depth <= max_depth,
+        Error::TaprootMaxDepthExceeded(depth, max_depth)
+    );
+    Ok(tr)
+}
+
+// Build a Huffman tree by repeatedly combining the two least likely nodes, with ties broken by their order.
+// Used for Policy trees, script trees use script_huffman_tree() for compatibility with rust-bitcoin.
+fn huffman_tree<T>(nodes: Vec<(u32, T)>, combine: impl Fn(T, T) -> Result<T>) -> Result<T> {
+    let mut slots = Vec::with_capacity(nodes.len() * 2);
+    let mut heap = BinaryHeap::with_capacity(nodes.len());
+    for (weight, node) in nodes {
+        heap.push((Reverse(weight), Reverse(slots.len())));
+        slots.push(Some(node));
+    }
+    while heap.len() > 1 {
+        let (Reverse(weight1), Reverse(slot1)) = heap.pop().expect("len must be at least two");
+        let (Reverse(weight2), Reverse(slot2)) = heap.pop().expect("len must be at least two");
+        let node1 = slots[slot1].take().expect("used once");
+        let node2 = slots[slot2].take().expect("used once");
+        heap.push((
+            Reverse(weight1.saturating_add(weight2)),
+            Reverse(slots.len()),
+        ));
+        slots.push(Some(combine(node1, node2)?));
+    }
+    let (_, Reverse(root_slot)) = heap.pop().ok_or(TaprootBuilderError::EmptyTree)?;
+    Ok(slots[root_slot].take().expect("used once"))
+}
+
+// Build a balanced tree by recursively splitting the nodes into two halves. Weights are ignored.
+fn balanced_tree<T>(mut nodes: Vec<(u32, T)>, combine: &impl Fn(T, T) -> Result<T>) -> Result<T> {
+    ensure!(!nodes.is_empty(), TaprootBuilderError::EmptyTree);
+    if nodes.len() == 1 {
+        return Ok(nodes.remove(0).1);
+    }
+    let right = nodes.split_off(nodes.len().div_ceil(2));
+    combine(
+        balanced_tree(nodes, combine)?,
+        balanced_tree(right, combine)?,
+    )
+}
+
 // Functions for TaprootSpendInfo construction
 
 fn tapinfo_from_array(
@@ -266,6 +660,7 @@ fn tapinfo_from_tree(dpk: XOnlyPublicKey, node: Value) -> Result<TaprootSpendInf
     fn process_node(node: Value) -> Result<NodeInfo> {
         Ok(match node {
             Value::Script(script) => NodeInfo::new_leaf_with_ver(script, LeafVersion::TapScript),
+            Value::TapLeaf(ver, script) => NodeInfo::new_leaf_with_ver(script, ver),
             Value::Array(mut nodes) if nodes.len() == 2 => {
                 let a = process_node(nodes.remove(0))?;
                 let b = process_node(nodes.remove(0))?;
@@ -282,22 +677,56 @@ fn tapinfo_from_tree(dpk: XOnlyPublicKey, node: Value) -> Result<TaprootSpendInf
     ))
 }
 
+// Build a Huffman tree of the (optionally weighted) scripts, with support for leaf versions other than TapScript
 fn tapinfo_huffman(internal_key: XOnlyPublicKey, scripts: Vec<Value>) -> Result<TaprootSpendInfo> {
-    let script_weights = scripts
+    let leaves = scripts
         .into_iter()
         .map(|v| {
-            Ok(match v {
-                Value::WithProb(prob, value) => (prob as u32, value.into_script()?),
-                other => (1, other.into_script()?),
-            })
+            let (weight, leaf) = match v {
+                Value::WithProb(prob, value) => (prob as u32, *value),
+                other => (1, other),
+            };
+            let (script, ver) = into_leaf(leaf)?;
+            Ok((weight, NodeInfo::new_leaf_with_ver(script, ver)))
         })
         .collect::<Result<Vec<_>>>()?;
+    let root = script_huffman_tree(leaves)?;
+    Ok(TaprootSpendInfo::from_node_info(&EC, internal_key, root))
+}
 
-    Ok(TaprootSpendInfo::with_huffman_tree(
-        &EC,
-        internal_key,
-        script_weights,
-    )?)
+// Mirrors TaprootBuilder::with_huffman_tree(), including its tie-breaking of equal weights by the NodeInfo
+// ordering, so that script trees (and their output keys) match those built by rust-bitcoin
+fn script_huffman_tree(leaves: Vec<(u32, NodeInfo)>) -> Result<NodeInfo> {
+    let mut node_weights = leaves
+        .into_iter()
+        .map(|(weight, node)| (Reverse(weight), node))
+        .collect::<BinaryHeap<_>>();
+    ensure!(!node_weights.is_empty(), TaprootBuilderError::EmptyTree);
+
+    while node_weights.len() > 1 {
+        let (weight1, node1) = node_weights.pop().expect("len must be at least two");
+        let (weight2, node2) = node_weights.pop().expect("len must be at least two");
+        let weight = Reverse(weight1.0.saturating_add(weight2.0));
+        node_weights.push((weight, NodeInfo::combine(node1, node2)?));
+    }
+    let (_, root) = node_weights.pop().expect("checked non-empty");
+    Ok(root)
+}
+
+// Get the Script and leaf version of a Script (using the default TapScript version) or TapLeaf
+fn into_leaf(value: Value) -> Result<(ScriptBuf, LeafVersion)> {
+    Ok(match value {
+        Value::TapLeaf(ver, script) => (script, ver),
+        other => (other.into_script()?, LeafVersion::TapScript),
+    })
+}
+
+fn leaf_version(ver: Value) -> Result<LeafVersion> {
+    Ok(LeafVersion::from_consensus(match ver {
+        Value::Number(Int(num)) => num.try_into()?,
+        Value::Bytes(bytes) if bytes.len() == 1 => bytes[0],
+        _ => bail!(Error::InvalidArguments),
+    })?)
 }
 
 fn branch_hash(a: &sha256::Hash, b: &sha256::Hash) -> sha256::Hash {
@@ -341,6 +770,11 @@ fn descriptor_from_array(
         // Nested arrays of length 2 are treated as a binary tree of policies (e.g. [ A, [ [ B, C ], D ] ])
         let internal_key = pk.or(unspendable).ok_or(Error::TaprootNoViableKey)?;
         descriptor_from_tree(internal_key, Value::array(policies))
+    } else if policies.iter().any(|p| matches!(p, Value::WithProb(..))) {
+        // Flat arrays with weights are compiled into individual leaves of a Huffman tree
+        let internal_key = pk.or(unspendable).ok_or(Error::TaprootNoViableKey)?;
+        let max_depth = TAPROOT_CONTROL_MAX_NODE_COUNT;
+        Ok(tr_with_shape(internal_key, policies, TreeShape::Huffman, max_depth)?.try_into()?)
     } else {
         // Other arrays are expected to be flat and are compiled into an OR or thresh(1, POLICIES) policy
         let policy = multi_andor(AndOr::Or, policies)?;
@@ -366,16 +800,54 @@ fn descriptor_from_tree(pk: DescriptorPublicKey, node: Value) -> Result<Descript
     Ok(Descriptor::new_tr(pk, Some(tree))?)
 }
 
-// Get the TR_UNSPENDABLE key from scope. It may be set to false to disable it.
-fn tr_unspendable(scope: &Scope) -> Result<Option<DescriptorPublicKey>> {
+// Get the TR_UNSPENDABLE key from scope. It may be set to false to disable it, or to a function
+// that gets called with the script tree (Policy, Script or Array) and returns the key.
+pub fn tr_unspendable(scope: &ScopeRef, tree: Value) -> Result<Option<DescriptorPublicKey>> {
     // Must exists in scope because its set in the stdlib
-    Ok(match scope.builtin("TR_UNSPENDABLE") {
+    let unspendable = scope.borrow().builtin("TR_UNSPENDABLE");
+    let unspendable = match unspendable {
+        Value::Function(func) => func.call(vec![tree], scope)?,
+        other => other,
+    };
+    Ok(match unspendable {
         Value::Bool(val) if val == false => None,
         Value::PubKey(val) => Some(val),
         other => bail!(Error::InvalidTrUnspendable(other.into())),
     })
 }
 
+// H+r*G, with the NUMS point H
+fn unspendable_single(r: Vec<u8>) -> Result<XOnlyPublicKey> {
+    let key = NUMS_H.add_exp_tweak(&EC, &ec::to_scalar(r)?)?;
+    Ok(key.into())
+}
+
+// An xpub with the NUMS point H as its key and a chain code derived from r, using the given xpub's network
+// and derivation path
+fn unspendable_xpub(xpub: DescriptorXKey<Xpub>, r: &[u8]) -> DescriptorXKey<Xpub> {
+    DescriptorXKey {
+        xkey: Xpub {
+            network: xpub.xkey.network,
+            depth: 0,
+            parent_fingerprint: Default::default(),
+            child_number: ChildNumber::from(0),
+            public_key: *NUMS_H,
+            chain_code: unspendable_chain_code(r),
+        },
+        origin: None,
+        derivation_path: xpub.derivation_path,
+        wildcard: xpub.wildcard,
+    }
+}
+
+// sha256(r||H), keeping the chain code unlinkable to anything but r
+fn unspendable_chain_code(r: &[u8]) -> ChainCode {
+    let mut eng = sha256::Hash::engine();
+    eng.input(r);
+    eng.input(&NUMS_H.serialize());
+    ChainCode::from(sha256::Hash::from_engine(eng).to_byte_array())
+}
+
 // Derive the DescriptorPublicKey into a definite key and transform to an x-only
 fn definite_xonly(pk: DescriptorPublicKey) -> Result<XOnlyPublicKey> {
     Ok(XOnlyPublicKey::from(
@@ -393,10 +865,12 @@ impl PrettyDisplay for TaprootSpendInfo {
         if !scripts.is_empty() {
             write!(f, ", ",)?;
             if scripts.len() > 1 {
-                fmt_list(f, scripts.keys(), indent, |f, (script, _), indent_inner| {
-                    //write!(f, "{:?}:", leaf_ver)?;
-                    write!(f, "{}", script.pretty(indent_inner))
-                })?;
+                fmt_list(
+                    f,
+                    scripts.keys(),
+                    indent,
+                    |f, (script, ver), indent_inner| fmt_leaf(f, script, *ver, indent_inner),
+                )?;
                 if scripts.len() > 2 {
                     // The Taproot script tree is displayed as a flat array, which loses the the Taproot tree structure information when there
                     // are more than two scripts. "(not tree)" is added to inform users, and to make the serialized string invalid as a
@@ -405,8 +879,8 @@ impl PrettyDisplay for TaprootSpendInfo {
                     write!(f, "(not tree)")?;
                 }
             } else {
-                let ((script, _), _) = scripts.first_key_value().expect("checked non-empty");
-                write!(f, "{}", script.pretty(indent))?;
+                let ((script, ver), _) = scripts.first_key_value().expect("checked non-empty");
+                fmt_leaf(f, script, *ver, indent)?;
             }
         }
         write!(f, ")")
@@ -416,3 +890,18 @@ impl PrettyDisplay for TaprootSpendInfo {
         self.script_map().len() > 2
     }
 }
+
+// Display scripts with a non-default leaf version using the leaf() constructor syntax
+fn fmt_leaf<W: fmt::Write>(
+    f: &mut W,
+    script: &ScriptBuf,
+    ver: LeafVersion,
+    indent: Option<usize>,
+) -> fmt::Result {
+    if ver == LeafVersion::TapScript {
+        write!(f, "{}", script.pretty(indent))
+    } else {
+        let ver = ver.to_consensus();
+        write!(f, "leaf(0x{:02x}, {})", ver, script.pretty(indent))
+    }
+}
@@ -1,4 +1,6 @@
-use minsc::eval;
+use std::convert::TryFrom;
+
+use minsc::{eval, Value};
 
 fn test(minsc: &str, expected_policy: &str) {
     let res = eval(replace_dummy(minsc).as_str()).unwrap();
@@ -6,6 +8,19 @@ fn test(minsc: &str, expected_policy: &str) {
     assert_eq!(policy, replace_dummy(expected_policy));
 }
 
+/// Evaluate each of the checks following the setup code, expecting them to return true
+fn test_checks(setup: &str, checks: &[&str]) {
+    for check in checks {
+        let res = eval(format!("{}\n{}", setup, check).as_str());
+        assert!(
+            matches!(res, Ok(Value::Bool(true))),
+            "check failed: {}\n{:?}",
+            check,
+            res
+        );
+    }
+}
+
 #[test]
 fn test_policy_is_valid_minsc() {
     test("pk(A)", "pk(A)");
@@ -74,6 +89,743 @@ fn test_functions() {
     );
 }
 
+#[test]
+fn test_ctv_tree() {
+    test_checks(
+        r"
+        $pk = pubkey(0x0381e3019c5861c2e0bd33604ec5c3e37cbb67dbbd7fadf9567232a30acfde204c);
+        $outs = fillArray(10, |$i| $pk: 1000 + $i);
+        $prevout = 0x01ba4719c80b6fe911b091a7c05124b64eeece964e09c058ef8f9805daca546b:0;
+        $tree = ctv::tree($outs, 3, 100);
+        ",
+        &[
+            "len($tree.1) == 8",
+            "$tree.0 == `ctvHash($tree.1.0) OP_CHECKTEMPLATEVERIFY`",
+            "ctv::tree($outs, 3, 100, $prevout, |$s| wsh($s)) == ctv::tree($outs, 3, 100, $prevout)",
+            "ctv::tree($outs, 3, 100, $prevout, |$s| tr(NUMS, $s)).0 != $tree.0",
+        ],
+    );
+}
+
+#[test]
+fn test_tx_fields() {
+    test_checks(
+        r#"
+        $pk = pubkey(0x0381e3019c5861c2e0bd33604ec5c3e37cbb67dbbd7fadf9567232a30acfde204c);
+        $prev = tx([ "outputs": [ $pk: 10000 ] ]);
+        $prev_outpoint = tx::txid($prev):0;
+        $tx = tx([ "inputs": [ $prev:0 ], "outputs": [ $pk: 9000 ] ]);
+        "#,
+        &[
+            r#"$tx == tx([ "inputs": [ $prev_outpoint ], "outputs": [ $pk: 9000 ] ])"#,
+            "tx::vsize($tx) == 82",
+            "tx::fee($tx, [ 10000 ]) == 1000",
+        ],
+    );
+}
+
+#[test]
+fn test_tx_fee_planning() {
+    test_checks(
+        r#"
+        $pk = pubkey(0x0381e3019c5861c2e0bd33604ec5c3e37cbb67dbbd7fadf9567232a30acfde204c);
+        $tx = tx([ "inputs": [ 0x01ba4719c80b6fe911b091a7c05124b64eeece964e09c058ef8f9805daca546b:0 ], "outputs": [ $pk: 9000 ] ]);
+        "#,
+        &[
+            "tx::estimateVsize($tx, [ wpkh($pk) ]) == 110",
+            "tx::change($tx, [ $pk: 20000 ], 2, $pk) == 10718",
+        ],
+    );
+}
+
+#[test]
+fn test_address_network() {
+    test_checks(
+        "$info = address::info(bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4);",
+        &[
+            r#"t($info, "type") == "p2wpkh""#,
+            r#"t($info, "witness_version") == 0"#,
+            r#"t($info, "witness_program") == 0x751e76e8199196d454941c45d1b3a323f1433bd6"#,
+            r#"t($info, "network") == _$$_RECKLESSLY_RISK_MY_BITCOINS_$$_"#,
+            "!address::isValidFor(bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4, signet)",
+            r#"address::isValidFor("tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx", signet)"#,
+        ],
+    );
+    assert!(eval("address(bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4, signet)").is_err());
+}
+
+#[test]
+fn test_witness_program() {
+    test_checks(
+        // Identifiers that look like short bech32 addresses, without a valid checksum
+        "tb1qqqqqqqqqqqqq = 2; fn bc1qqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqq() = 3;",
+        &[
+            "tb1qqqqqqqqqqqqq == 2",
+            "bc1qqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqq() == 3",
+            "typeof(bc1pfeessrawgf) == \"address\"",
+            "address(witnessProgram(1, 0x4e73), _$$_RECKLESSLY_RISK_MY_BITCOINS_$$_) == bc1pfeessrawgf",
+            "scriptPubKey(bc1sqypqxpq9qcrsszg2pvxq6rs0zqg3yyc5z5tpwxqergd3c8g7ruszzg3rysjjvfcc2qw0y)
+              == witnessProgram(16, 0x0102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f2021222324252627)",
+            r#"t(address::info(bc1zqypqxpq9qcrsszg2pvxq6rs0zqg3yyc5z5tpwxqergd3c8g7ruszz57rnqw), "witness_version") == 2"#,
+            // 0x-less hex that happens to look like a bech32 address
+            "bc1acdef02345acdef02345acdef02345acdef02 == 0xbc1acdef02345acdef02345acdef02345acdef02",
+            "bc1acdef02345acdef02345acdef02345acdef02345acdef02345acdef02345a == 0xbc1acdef02345acdef02345acdef02345acdef02345acdef02345acdef02345a",
+        ],
+    );
+}
+
+#[test]
+fn test_truc() {
+    let parent = r#"
+        $pk = pubkey(0x0381e3019c5861c2e0bd33604ec5c3e37cbb67dbbd7fadf9567232a30acfde204c);
+        $parent = tx([ "version": 3, "inputs": [ 0x01ba4719c80b6fe911b091a7c05124b64eeece964e09c058ef8f9805daca546b:0 ], "outputs": [ $pk: 9000, tx::anchor() ] ]);
+        $v2_parent = tx([ "version": 2, "inputs": [ 0x01ba4719c80b6fe911b091a7c05124b64eeece964e09c058ef8f9805daca546b:1 ], "outputs": [ $pk: 9000 ] ]);
+    "#;
+    test_checks(
+        parent,
+        &[
+            r#"tx::checkTruc(tx([ "version": 3, "inputs": [ $parent:1, [ "prevout": $parent:0 ] ], "outputs": [ $pk: 8000 ] ]), [ $parent ])"#,
+            "scriptPubKey(P2A) == `1 0x4e73`",
+        ],
+    );
+    for invalid in [
+        r#"tx([ "version": 3, "inputs": [ $parent:1, $v2_parent:0 ], "outputs": [ $pk: 8000 ] ])"#,
+        r#"tx([ "version": 3, "inputs": [ $v2_parent:0 ], "outputs": [ $pk: 8000 ] ])"#,
+        r#"tx([ "version": 2, "inputs": [ $parent:1 ], "outputs": [ $pk: 8000 ] ])"#,
+        r#"tx([ "version": 3, "inputs": [ $parent:0 ], "outputs": fillArray(40, |$n| $pk:100) ])"#,
+    ] {
+        assert!(
+            eval(format!("{}{}", parent, invalid).as_str()).is_err(),
+            "{}",
+            invalid
+        );
+    }
+}
+
+#[test]
+fn test_policy_checks() {
+    test_checks(
+        r#"
+        $pk = pubkey(0x0381e3019c5861c2e0bd33604ec5c3e37cbb67dbbd7fadf9567232a30acfde204c);
+        $tx = tx([ "version": 5, "inputs": [ 0x01ba4719c80b6fe911b091a7c05124b64eeece964e09c058ef8f9805daca546b:0 ], "outputs": [ $pk: 100, $pk: 1000 ] ]);
+        $ops = `OP_DUP` * 199;
+        "#,
+        &[
+            "policy::checkScript(`<$pk> OP_CHECKSIG`) == []",
+            // Multisig counts towards the opcode limit with its number of keys, or 20 if not a constant
+            "policy::checkScript(`$ops 1 <$pk> 1 OP_CHECKMULTISIG`) == []",
+            r#"policy::checkScript(`$ops OP_DUP 1 <$pk> 1 OP_CHECKMULTISIG`)
+              == [ "Script has an opcode count of 202, exceeding the limit of 201" ]"#,
+            r#"policy::checkScript(`$ops OP_CHECKMULTISIGVERIFY`)
+              == [ "Script has an opcode count of 220, exceeding the limit of 201" ]"#,
+            r#"policy::checkScript(script(0x0105)) == [ "Non-minimal push of 1 bytes at position 0" ]"#,
+            r#"policy::checkScript(script(0x7e), "tapscript")
+              == [ "OP_CAT at position 0 is an OP_SUCCESSx opcode, reserved for future upgrades" ]"#,
+            r#"policy::checkTx($tx) == [ "Non-standard transaction version 5",
+              "Transaction has an ephemeral dust output, which requires zero fees (unverifiable without the prevouts)" ]"#,
+            r#"policy::checkTx($tx, [ $pk: 1100 ]) == [ "Non-standard transaction version 5" ]"#,
+            r#"policy::checkTx($tx, [ $pk: 1500 ]).1
+              == "Transaction has an ephemeral dust output, but pays fees (0.000015 BTC in, 0.000011 BTC out) rather than zero""#,
+            r#"policy::checkTx(tx([ "outputs": [ $pk: 100, $pk: 200 ] ]))
+              == [ "Output #0 amount of 0.000001 BTC is below the dust threshold of 0.00000294 BTC",
+                   "Output #1 amount of 0.000002 BTC is below the dust threshold of 0.00000294 BTC" ]"#,
+        ],
+    );
+}
+
+#[test]
+fn test_stack_effect() {
+    test_checks(
+        r#"
+        $p2pkh = script::stackEffect(`OP_DUP OP_HASH160 <0x0000000000000000000000000000000000000000> OP_EQUALVERIFY OP_CHECKSIG`);
+        $unbalanced = script::stackEffect(`OP_IF 1 2 OP_ELSE 1 OP_ENDIF`);
+        $pk = pubkey(0x0381e3019c5861c2e0bd33604ec5c3e37cbb67dbbd7fadf9567232a30acfde204c);
+        $multi_a = script::stackEffect(`<$pk> OP_CHECKSIG <$pk> OP_CHECKSIGADD 2 OP_NUMEQUAL`, "tapscript");
+        "#,
+        &[
+            r#"t($p2pkh, "consumed") == 2"#,
+            r#"t($p2pkh, "produced") == 1"#,
+            r#"t($p2pkh, "max_depth") == 4"#,
+            r#"len(t($p2pkh, "issues")) == 0"#,
+            r#"len(t($unbalanced, "branches")) == 1"#,
+            r#"len(t($unbalanced, "issues")) == 1"#,
+            r#"t(t(script::stackEffect(nToAlt(3)), "altstack"), "produced") == 4"#,
+            // multi_a-style tapscript
+            r#"t($multi_a, "consumed") == 2"#,
+            r#"t($multi_a, "produced") == 1"#,
+            r#"t($multi_a, "always_fails") == null"#,
+            r#"t(script::stackEffect(`OP_DEPTH 2 OP_CHECKMULTISIG 1`), "unknown")"#,
+            // Huge constant depths/counts can never succeed, and are reported without being tracked
+            r#"t(script::stackEffect(`100000000 OP_PICK`), "always_fails")"#,
+            r#"t(script::stackEffect(`1 2 OP_ROLL 100000000 OP_ROLL`), "always_fails")"#,
+            r#"len(t(script::stackEffect(`100000000 OP_PICK`), "issues")) == 1"#,
+            r#"t(script::stackEffect(`0 100000000 OP_CHECKMULTISIG`), "always_fails")"#,
+            r#"t(script::stackEffect(`3 0x01 0x02 2 OP_CHECKMULTISIG`), "always_fails")"#,
+            r#"t(script::stackEffect(`1 0x01 0x02 2 OP_CHECKMULTISIG`), "consumed") == 2"#,
+            r#"t(script::stackEffect(`999 OP_PICK`), "consumed") == 1000"#,
+        ],
+    );
+}
+
+#[test]
+fn test_script_optimize() {
+    test_checks(
+        r#"
+        $script = `OP_EQUAL OP_VERIFY 1 0 OP_ADD OP_ADD mark::comment("hi") 5 OP_DROP OP_DROP OP_DROP`;
+        $optimized = script::optimize($script);
+        $assume_valid = script::optimize($script, true);
+        $max = fold(fillArray(520, |_| 0x00), 0x, |$acc, $b| $acc + $b);
+        $oversized = $max + 0x00;
+        "#,
+        &[
+            r#"$optimized.0 == `OP_EQUALVERIFY 1 0 OP_ADD OP_ADD mark::comment("hi") OP_2DROP`"#,
+            "$optimized.1 == 4",
+            r#"$assume_valid.0 == `OP_EQUALVERIFY OP_1ADD mark::comment("hi") OP_2DROP`"#,
+            "$assume_valid.1 == 7",
+            // Non-minimal pushes are kept byte-for-byte and aren't rewritten
+            "script::optimize(script(0x0105)) == script(0x0105):0",
+            "bytes(script::optimize(`script(0x010575) 1 OP_DROP`).0) == 0x010575",
+            "bytes(script::optimize(`script(0x4c0412345678) OP_EQUAL OP_VERIFY`).0) == 0x4c0412345678 + bytes(`OP_EQUALVERIFY`)",
+            // Pushes over 520 bytes fail the script and aren't dropped
+            "script::optimize(`<$oversized> OP_DROP 1`).1 == 0",
+            "script::optimize(`<$max> OP_DROP 1`).1 == 524",
+            "script::optimize(`OP_DUP <0x1234> OP_EQUALVERIFY OP_DROP <0x1234> OP_CHECKSIG`).0 == `OP_DUP <0x1234> OP_EQUALVERIFY OP_CHECKSIG`",
+            "script::optimize(`OP_DROP OP_CODESEPARATOR OP_DROP`, true).1 == 0",
+        ],
+    );
+}
+
+#[test]
+fn test_script_decompile() {
+    // Decompiled scripts evaluate back into the original script
+    let script = r#"`<5> OP_ADD OP_IF <0x0102> OP_ELSE 0 OP_ENDIF mark::label("hi")
+        ctvVerify(0x0000000000000000000000000000000000000000000000000000000000000001) script(0xb9)`"#;
+    let decompiled = eval(format!("script::decompile({})", script).as_str()).unwrap();
+    let decompiled = String::try_from(decompiled).unwrap();
+    assert!(decompiled.contains("ctvVerify(0x"), "{}", decompiled);
+    assert_eq!(eval(decompiled.as_str()).unwrap(), eval(script).unwrap());
+
+    // Non-minimal pushes are kept as-is
+    for raw in ["0x0105", "0x4c0105", "0x4d0300aabbcc0100"] {
+        let decompiled = eval(format!("script::decompile({})", raw).as_str()).unwrap();
+        let decompiled = String::try_from(decompiled).unwrap();
+        assert_eq!(
+            eval(format!("bytes({})", decompiled).as_str()).unwrap(),
+            eval(raw).unwrap(),
+            "{}",
+            decompiled
+        );
+    }
+
+    // Miniscript can be lifted into a policy
+    let lifted = eval(
+        "script::decompile(explicitScript(wsh(pk(0x0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798) && older(10))), true)",
+    )
+    .unwrap();
+    assert_eq!(
+        String::try_from(lifted).unwrap(),
+        "and(pk(0x0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798), older(10))"
+    );
+}
+
+#[test]
+fn test_execution_limits() {
+    use minsc::runtime::limits::{with_limits, Limit};
+    use minsc::{error::RuntimeError, eval_limited, Error, Limits};
+    use std::time::Duration;
+
+    let limit_err = |code: &str, limits: Limits| match eval_limited(code, limits) {
+        Err(Error::Runtime(RuntimeError::LimitExceeded(limit))) => limit,
+        other => panic!("expected a limit error for {}, got {:?}", code, other),
+    };
+    let recurse = "fn f($n) = if $n == 0 then 0 else 1 + f($n - 1); f(1000)";
+
+    let limits = Limits {
+        max_call_depth: Some(10),
+        ..Default::default()
+    };
+    assert_eq!(limit_err(recurse, limits.clone()), Limit::CallDepth(10));
+    assert!(eval_limited(
+        "fn f($n) = if $n == 0 then 0 else 1 + f($n - 1); f(5)",
+        limits
+    )
+    .is_ok());
+
+    let limits = Limits {
+        max_steps: Some(100),
+        ..Default::default()
+    };
+    let tail_recurse = "fn f($n) = if $n == 0 then 0 else f($n - 1); f(1000)";
+    assert_eq!(limit_err(tail_recurse, limits), Limit::Steps(100));
+
+    let limits = Limits {
+        max_array_len: Some(1000),
+        max_script_len: Some(1000),
+        ..Default::default()
+    };
+    assert_eq!(
+        limit_err("fillArray(1001, 0)", limits.clone()),
+        Limit::ArrayLen(1000)
+    );
+    assert_eq!(
+        limit_err("`OP_DUP` * 1001", limits.clone()),
+        Limit::ScriptLen(1000)
+    );
+    let many_parts = r#"str::split(str::format("{}", fillArray(1000, 0)), "0")"#;
+    assert_eq!(limit_err(many_parts, limits.clone()), Limit::ArrayLen(1000));
+
+    // Doubling through concatenation
+    let limits = Limits {
+        max_bytes_len: Some(1000),
+        max_script_len: Some(1000),
+        ..Default::default()
+    };
+    let double_script = "fold(range(0, 64), `OP_DUP`, |$s, $n| `$s $s`)";
+    assert_eq!(
+        limit_err(double_script, limits.clone()),
+        Limit::ScriptLen(1000)
+    );
+    let double_str = r#"fold(range(0, 64), "a", |$s, $n| $s + $s)"#;
+    assert_eq!(limit_err(double_str, limits.clone()), Limit::BytesLen(1000));
+    let double_bytes = "fold(range(0, 64), 0x00, |$s, $n| $s + $s)";
+    assert_eq!(limit_err(double_bytes, limits), Limit::BytesLen(1000));
+
+    let limits = Limits {
+        max_duration: Some(Duration::from_millis(1)),
+        ..Default::default()
+    };
+    let slow = "fold(range(0, 1000000), 0, |$acc, $n| $acc + $n)";
+    assert_eq!(
+        limit_err(slow, limits),
+        Limit::Duration(Duration::from_millis(1))
+    );
+
+    // Not enforced outside of eval_limited()
+    assert!(eval("fillArray(1001, 0)").is_ok());
+
+    // Nor left behind by a panic within it
+    let limits = Limits {
+        max_array_len: Some(1000),
+        ..Default::default()
+    };
+    let panicked = std::panic::catch_unwind(|| with_limits(limits, || panic!("oops")));
+    assert!(panicked.is_err());
+    assert!(eval("fillArray(1001, 0)").is_ok());
+}
+
+#[test]
+fn test_tail_calls() {
+    // Tail calls (including mutual recursion and through blocks) don't grow the native stack
+    test_checks(
+        r#"
+        fn countdown($n) = if $n == 0 then true else countdown($n - 1);
+        fn even($n) = if $n == 0 then true else odd($n - 1);
+        fn odd($n) = if $n == 0 then false else { $m = $n - 1; even($m) };
+        "#,
+        &[
+            "countdown(20000)",
+            "even(20000)",
+            "!odd(20000)",
+            "countdown(100000)",
+        ],
+    );
+
+    // Deep non-tail recursion results in an error rather than a native stack overflow
+    let assert_too_deep = |code: &str| {
+        let res = eval(code);
+        assert!(
+            matches!(
+                res,
+                Err(minsc::Error::Runtime(
+                    minsc::RuntimeError::StackDepthExceeded
+                ))
+            ),
+            "{:?}",
+            res
+        );
+    };
+    assert_too_deep("fn sum($n) = if $n == 0 then 0 else $n + sum($n - 1); sum(20000)");
+
+    // Moderately deep non-tail recursion still works, given a main-thread-sized native stack
+    std::thread::Builder::new()
+        .stack_size(8 * 1024 * 1024)
+        .spawn(move || {
+            test_checks(
+                "fn sum($n) = if $n == 0 then 0 else $n + sum($n - 1);",
+                &[
+                    "sum(50) == 1275",
+                    "len(bytes(unrollFor(50, `OP_ROT OP_TOALTSTACK`))) == 355",
+                ],
+            );
+            // But is still limited by it
+            assert_too_deep("fn sum($n) = if $n == 0 then 0 else $n + sum($n - 1); sum(100000)");
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+}
+
+#[test]
+fn test_destructuring() {
+    test_checks(
+        r#"
+        [$txid, $vout] = 0x1111111111111111111111111111111111111111111111111111111111111111:3;
+        [$a, [$b, $c], ..._] = [1, [2, 3], 4, 5];
+        [$head, ...$tail] = [1, 2, 3];
+        ["amount": $amt] = ["vout": 7, "amount": 1000];
+        fn f([$p, $q], ["k": $k]) = $p + $q + $k;
+        $g = |[$m, ...$n]| $m + len($n);
+        "#,
+        &[
+            "$vout == 3",
+            "6 == $a+$b+$c",
+            "$head == 1",
+            "$tail == [2, 3]",
+            "$amt == 1000",
+            r#"f([1, 2], ["k": 3]) == 6"#,
+            "$g([10, 1, 1]) == 12",
+            // Stdlib clauses may have extra elements beyond the condition and script
+            "ifelseif([ [`1`, `2`, \"note\"], default: `3` ]) == ifelseif([ `1`: `2`, default: `3` ])",
+            "switch([ [0, `OP_TRUE`, \"note\"], default: `OP_FALSE` ]) == switch([ 0: `OP_TRUE`, default: `OP_FALSE` ])",
+        ],
+    );
+
+    let err = |code: &str| eval(code).unwrap_err().to_string();
+    assert!(err("[$a, $b] = [1]; $a").contains("Array pattern expected 2 elements, not 1"));
+    assert!(err("[$a, ...$b] = []; $a").contains("expected at least 1 elements, not 0"));
+    assert!(err(r#"["x": $a] = ["y": 1]; $a"#).contains(r#"tag not found: "x""#));
+    assert!(err("fn f([$a]) = $a; f([1, 2])").contains("in f(): #1: Array pattern"));
+    assert!(err("[1, $a] = [1, 1]; $a").contains("Invalid destructuring pattern"));
+}
+
+#[test]
+fn test_default_named_args() {
+    test_checks(
+        r#"
+        fn f($a, $b = 10, $c = $a * 2) = [$a, $b, $c];
+        fn sum($first, ...$rest) = fold($rest, $first, |$acc, $n| $acc + $n);
+        "#,
+        &[
+            "f(1) == [1, 10, 2]",
+            "f(1, 2) == [1, 2, 2]",
+            "f(1, $c = 5) == [1, 10, 5]",
+            "f($b = 4, $a = 3) == [3, 4, 6]",
+            "sum(1, 2, 3) == 6",
+            "sum(5) == 5",
+        ],
+    );
+
+    let err = |code: &str| eval(code).unwrap_err().to_string();
+    assert!(err("fn f($a, $b) = $a; f(1)").contains("Missing argument for parameter $b"));
+    assert!(err("fn f($a) = $a; f(1, 2)").contains("Expected at most 1 arguments, not 2"));
+    assert!(err("fn f($a) = $a; f($z = 1)").contains("Unknown named argument: $z"));
+    assert!(err("fn f($a) = $a; f(1, $a = 2)").contains("both positionally and by name"));
+    assert!(err("len([1], $x = 2)").contains("only supported for user-defined functions"));
+}
+
+#[test]
+fn test_strings_encodings() {
+    test_checks(
+        "",
+        &[
+            r#"str::split("a,b", ",") == ["a", "b"]"#,
+            r#"str::slice("minsc", 1, 3) == "ins""#,
+            r#"str::format("{} is {{{1}}}", "x", 2) == "x is {2}""#,
+            r#"str::fromBytes(0x6869) == "hi""#,
+            "hex::decode(hex::encode(0xbeef)) == 0xbeef",
+            r#"base64::encode(0x666f6f62) == "Zm9vYg==""#,
+            r#"base64::decode("Zm9vYmFy") == 0x666f6f626172"#,
+            "base58check::decode(base58check::encode(0x00aa)) == 0x00aa",
+            r#"bech32::decode(bech32::encode("tb", 0xaabb, "bech32m")) == ["tb", 0xaabb]"#,
+        ],
+    );
+}
+
+#[test]
+fn test_hash_functions() {
+    test_checks(
+        "",
+        &[
+            "hash::sha256(0x) == 0xe3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+            "hash::hash160(0x) == 0xb472a266d0bd89c13706a4132ccfb16f7c3b9fcb",
+            "hash::sha1(0x) == 0xda39a3ee5e6b4b0d3255bfef95601890afd80709",
+            r#"hash::taggedHash("TapLeaf", 0x) == 0x5212c288a377d1f8164962a5a13429f9ba6a7b84e59776a52c6637df2106facb"#,
+            r#"len(hash::hmac_sha512("Bitcoin seed", 0x00)) == 64"#,
+            "hash160(hash::hash160(0x01)) == hash160(0xc51b66bced5e4491001bd702669770dccf440982)",
+        ],
+    );
+}
+
+#[test]
+fn test_ec_operations() {
+    test_checks(
+        r#"
+        $G = 0x0479be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798483ada7726a3c4655da4fbfc0e1108a8fd17b448a68554199c47d08ffb10d4b8;
+        $pk = pubkey(0x0381e3019c5861c2e0bd33604ec5c3e37cbb67dbbd7fadf9567232a30acfde204c);
+        $two = 0x0000000000000000000000000000000000000000000000000000000000000002;
+        $tr = tr($pk, `OP_TRUE`);
+        "#,
+        &[
+            "ec::nums($G) == NUMS",
+            "ec::add($pk, $pk) == ec::mulTweak($pk, $two)",
+            "!ec::hasEvenY($pk)",
+            "ec::hasEvenY(ec::negate($pk))",
+            "ec::tapTweak($pk, tr::merkleRoot($tr)) == tr::outputKey($tr)",
+        ],
+    );
+}
+
+#[test]
+fn test_tr_unspendable() {
+    test_checks(
+        r#"
+        $u = tr::unspendable(hash::sha256("random"));
+        $xpub = xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8;
+        $x = tr::unspendable($xpub/0/*, hash::sha256("secret"));
+        $pk = pubkey(0x0381e3019c5861c2e0bd33604ec5c3e37cbb67dbbd7fadf9567232a30acfde204c);
+        TR_UNSPENDABLE = |$tree| tr::unspendable(hash::sha256(str($tree))).0;
+        "#,
+        &[
+            "tr::verifyUnspendable($u.0, $u.1)",
+            r#"!tr::verifyUnspendable($u.0, hash::sha256("other"))"#,
+            "tr::verifyUnspendable($x.0, $x.1)",
+            "!tr::verifyUnspendable(NUMS, $x.1)",
+            r#"!tr::verifyUnspendable($x.0, hash::sha256("other"))"#,
+            // The chain code is derived from r rather than copied from the given xpub
+            "$x.1 == hash::sha256(\"secret\")",
+            "str($x.0) != str(tr::unspendable($xpub/0/*, hash::sha256(\"other\")).0)",
+            "tr::internalKey(tr(`$pk OP_CHECKSIG`)) == tr::unspendable(hash::sha256(str(`$pk OP_CHECKSIG`))).0",
+        ],
+    );
+
+    // TR_UNSPENDABLE is not evaluated when the internal key can be extracted from the policy
+    test_checks(
+        r#"
+        $pk = pubkey(0x0381e3019c5861c2e0bd33604ec5c3e37cbb67dbbd7fadf9567232a30acfde204c);
+        $pk2 = pubkey(0x0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798);
+        $xonly = tr::internalKey(tr($pk));
+        TR_UNSPENDABLE = |$tree| throw("unexpected TR_UNSPENDABLE call");
+        "#,
+        &[
+            "tr::internalKey(tr(pk($pk) || (pk($pk2) && older(10)))) == $xonly",
+            "tr::internalKey(tr([ $pk, pk($pk2) && older(10) ])) == $xonly",
+        ],
+    );
+    // But still is when it cannot be
+    let no_key = r#"TR_UNSPENDABLE = |$tree| throw("called");
+        tr(pk(0x0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798) && older(10))"#;
+    assert!(eval(no_key).unwrap_err().to_string().contains("called"));
+}
+
+#[test]
+fn test_tr_witness() {
+    test_checks(
+        r#"
+        $pk = pubkey(0x0381e3019c5861c2e0bd33604ec5c3e37cbb67dbbd7fadf9567232a30acfde204c);
+        $t = tr($pk, [ `$pk OP_CHECKSIG`, `OP_ADD 5 OP_EQUAL`, `OP_TRUE` ]);
+        $w = tr::witness($t, `OP_ADD 5 OP_EQUAL`, [ 0x02, 0x03 ]);
+        "#,
+        &[
+            "len($w) == 4",
+            "$w.2 == bytes(`OP_ADD 5 OP_EQUAL`)",
+            "len(tr::witness($t, `OP_TRUE`, [], 0x50aa)) == 3",
+            "tr::verifyControlBlock(tr::outputKey($t), `OP_ADD 5 OP_EQUAL`, $w.3)",
+            "!tr::verifyControlBlock(tr::outputKey($t), `OP_TRUE`, $w.3)",
+        ],
+    );
+}
+
+#[test]
+fn test_tr_leaf_versions() {
+    test_checks(
+        r#"
+        $pk = pubkey(0x0381e3019c5861c2e0bd33604ec5c3e37cbb67dbbd7fadf9567232a30acfde204c);
+        $t = tr($pk, [ `$pk OP_CHECKSIG`, 5@leaf(0xc2, `OP_TRUE`), `OP_FALSE` ]);
+        $scripts = tr::scripts(tr($pk, [ `$pk OP_CHECKSIG`, leaf(0xc2, `OP_TRUE`) ]));
+        "#,
+        &[
+            "$scripts.1.1 == 0xc2",
+            "tr::tapLeaf(leaf(0xc2, `OP_TRUE`)) == tr::tapLeaf(`OP_TRUE`, 0xc2)",
+            "tr::verifyControlBlock(tr::outputKey($t), `OP_TRUE`, tr::witness($t, leaf(0xc2, `OP_TRUE`)).1)",
+            r#"str(leaf(0xc2, `OP_TRUE`)) == "leaf(0xc2, `<1>`)""#,
+        ],
+    );
+}
+
+#[test]
+fn test_tr_tree_shapes() {
+    test_checks(
+        r#"
+        $A = pubkey(0x0381e3019c5861c2e0bd33604ec5c3e37cbb67dbbd7fadf9567232a30acfde204c);
+        $B = pubkey(0x0399e3019c5861c2e0bd33604ec5c3e37cbb67dbbd7fadf9567232a30acfde204c);
+        $leaves = tr::leaves(tr($A, [ 10@pk($B), 1@(pk($A) && older(10)), 1@(pk($A) && pk($B)) ]));
+        $depths = |$leaves| map($leaves, |$leaf| t($leaf, "depth"));
+        "#,
+        &[
+            "$depths($leaves) == [ 1, 2, 2 ]",
+            r#"str::contains(t($leaves.0, "miniscript"), "pk(0399e3")"#,
+            // Descriptors and TapInfos list their leaves in the same order
+            r#"$scripts = |$leaves| map($leaves, |$leaf| t($leaf, "script"));
+               $tr = tr::balanced([ pk($A), pk($B), pk($A) && pk($B), pk($A) && older(5) ]);
+               $scripts(tr::leaves($tr)) == $scripts(tr::leaves(tr::tapInfo($tr)))"#,
+            "$depths(tr::leaves(tr::balanced([ pk($A), pk($B), pk($A) && pk($B), pk($A) && older(5) ]))) == [ 2, 2, 2, 2 ]",
+            "tr::outputKey(tr::huffman($A, [ 5@`1`, `2`, `3` ], 2)) == tr::outputKey(tr($A, [ 5@`1`, `2`, `3` ]))",
+            // Equal-weight script trees are built exactly like rust-bitcoin's TaprootBuilder::with_huffman_tree()
+            "tr::outputKey(tr($A, [ `1`, `2`, `3`, `4`, `5` ])) == pubkey(0x70cfec7357fc95734b80a6f0be0609119b75c0244e955c9875e7c1fb5ecd362a)",
+            "tr::outputKey(tr($A, [ `1`, `2`, `3`, `4`, `5`, `6`, `7` ])) == pubkey(0x51520abf7f7591f4bc699150221759e846db062bb26707bb50af0a99f44e019a)",
+            "tr::outputKey(tr($A, [ 3@`1`, `2`, `3`, 2@`4`, `5` ])) == pubkey(0xf2aa53a145b8e2c215ef4ae43931d7ac82eede443d318738ad39c94083affc02)",
+        ],
+    );
+    assert!(eval("tr::huffman(0x0381e3019c5861c2e0bd33604ec5c3e37cbb67dbbd7fadf9567232a30acfde204c, [ 5@`1`, `2`, `3` ], 1)").is_err());
+}
+
+#[test]
+fn test_policy_compile() {
+    test_checks(
+        r#"
+        $A = pubkey(0x0381e3019c5861c2e0bd33604ec5c3e37cbb67dbbd7fadf9567232a30acfde204c);
+        $B = pubkey(0x0399e3019c5861c2e0bd33604ec5c3e37cbb67dbbd7fadf9567232a30acfde204c);
+        $policy = 9@pk($A) || (pk($B) && older(100));
+        $cmp = compile::compare($policy);
+        $wsh = t($cmp, "wsh"); $shwsh = t($cmp, "sh-wsh"); $tr = t($cmp, "tr");
+        "#,
+        &[
+            r#"t($wsh, "spk_size") == 34"#,
+            r#"t($shwsh, "spk_size") == 23"#,
+            r#"t($wsh, "script_size") == t($shwsh, "script_size")"#,
+            r#"t($shwsh, "expected_weight") > t($wsh, "expected_weight")"#,
+            r#"t($tr, "expected_weight") < t($wsh, "expected_weight")"#,
+            r#"float(t($tr, "max_weight")) >= t($tr, "expected_weight")"#,
+            r#"t(compile($policy), "descriptor") == t($wsh, "descriptor")"#,
+            r#"t(compile(pk($A) && pk($B), "sh"), "fragments") == "and_v, v:, c:, pk_k""#,
+            r#"t(compile(pk($A) && pk($B) && older(10)), "fragments") == "and_v, v:, c:, pk_k, older""#,
+            r#"t(compile(pk($A) && pk($B)), "candidate") == "as-written""#,
+            r#"$c = t(compile($policy, "wsh", [ "minimize": "size" ]), "candidate"); ($c == "as-written") || ($c == "equalized")"#,
+            r#"t(compile($policy, "wsh", [ "minimize": "size" ]), "script_size") <= t($wsh, "script_size")"#,
+        ],
+    );
+    assert!(eval("compile(TRIVIAL, \"p2pkh\")").is_err());
+}
+
+#[test]
+fn test_miniscript_values() {
+    test_checks(
+        r#"
+        $A = pubkey(0x0381e3019c5861c2e0bd33604ec5c3e37cbb67dbbd7fadf9567232a30acfde204c);
+        $B = pubkey(0x0399e3019c5861c2e0bd33604ec5c3e37cbb67dbbd7fadf9567232a30acfde204c);
+        $ms = ms::and_v(ms::v(ms::pk($A)), ms::or_d(ms::pk($B), ms::older(144)));
+        "#,
+        &[
+            r#"typeof($ms) == "miniscript""#,
+            r#"ms::type($ms) == "B/nfsmk""#,
+            r#"ms::type(ms::v(ms::pk($A))) == "V/onfsmk""#,
+            "`$ms` == segwitv0($ms)",
+            r#"wsh($ms) == wsh(miniscript("and_v(v:pk(0381e3019c5861c2e0bd33604ec5c3e37cbb67dbbd7fadf9567232a30acfde204c),or_d(pk(0399e3019c5861c2e0bd33604ec5c3e37cbb67dbbd7fadf9567232a30acfde204c),older(144)))"))"#,
+            r#"ms::type(ms::thresh(2, ms::pk($A), ms::s(ms::pk($B)))) == "B/duesmk""#,
+            r#"miniscript(pk($A) && older(10), "tapscript") == miniscript(ms::and_v(ms::v(ms::pk($A)), ms::older(10)), "tapscript")"#,
+            r#"typeof(ms::multi_a(1, $A, $B)) == "miniscript""#,
+            // Wrappers over wrapped fragments with no parenthesis
+            r#"ms::v(ms::n(ms::TRUE)) == miniscript("vn:1")"#,
+            r#"ms::a(ms::n(ms::FALSE)) == miniscript("an:0")"#,
+            r#"ms::type(ms::d(ms::v(ms::TRUE))) == ms::type(miniscript("dv:1"))"#,
+            r#"ms::l(ms::n(ms::TRUE)) == miniscript("ln:1")"#,
+            r#"ms::t(ms::v(ms::TRUE)) == miniscript("tv:1")"#,
+        ],
+    );
+    assert!(eval("ms::v(ms::v(ms::pk(0x0381e3019c5861c2e0bd33604ec5c3e37cbb67dbbd7fadf9567232a30acfde204c)))").is_err());
+    // Type errors rather than invalid miniscript strings
+    let res = eval("ms::s(ms::a(ms::FALSE))").unwrap_err().to_string();
+    assert!(res.contains("cannot wrap a fragment of type W"), "{}", res);
+    assert!(eval("ms::l(ms::FALSE)").is_err());
+}
+
+#[test]
+fn test_elements_tx() {
+    test_checks(
+        r#"
+        $A = pubkey(0x0381e3019c5861c2e0bd33604ec5c3e37cbb67dbbd7fadf9567232a30acfde204c);
+        // Issuance test vector from Elements Core (the expected ids are in the internal byte order)
+        $entropy = elements::issuanceEntropy(0x05a047c98e82a848dee94efcf32462b065198bebf2404d201ba2e06db30b28f4:0);
+        $tx = elements::tx([
+          "inputs": [ [ "prevout": 0x05a047c98e82a848dee94efcf32462b065198bebf2404d201ba2e06db30b28f4:0, "issuance": [ "amount": 1000 ] ] ],
+          "outputs": [ [ "script_pubkey": wpkh($A), "asset": elements::assetId($entropy), "value": 1000 ], [ "asset": LBTC, "value": 500 ] ]
+        ]);
+        "#,
+        &[
+            "$entropy == 0x0ab7f48d6416b3853113a2ea3aa8373d6132f946f62ead2c502313697f446f74",
+            "elements::assetId($entropy) == 0x3385cd0bdab47ddf8ac10686cc8b01f5da6fbac32b0bc426c0b563d81808d6dc",
+            "elements::reissuanceToken($entropy) == 0x30956809203e9017790a01cdd614d43d52caf9d40de99cbf337df8f414b1adc1",
+            r#"typeof($tx) == "elementstx""#,
+            r#"elements::address(wpkh($A)) == "ex1q59jkec0ev6430t073ypd0ehc0zzsj8ug24e50r""#,
+        ],
+    );
+    assert!(eval("elements::address(`OP_TRUE`)").is_err());
+}
+
+#[test]
+fn test_elements_vectors() {
+    // Segwit v0 sighash test vectors from rust-elements (generated by Elements Core's test framework)
+    test_checks(
+        r#"
+        $asset = 0x230f4f5d4b7c6fa845806ee4f67713459e1b69e8e60fcee2e4940c7a0d5de1b2;
+        $tx = elements::tx([
+          "version": 1,
+          "inputs": [ [ "prevout": 0xaff27bbc41ba6e5549470f909ce55ded9fa63f26e7fad618ff02afebccf55d71:0, "sequence": 0 ] ],
+          "outputs": [
+            [ "script_pubkey": script(0x51755175517551755175517551755175517551755175517551755175517551), "asset": $asset, "value": 4900000000 ],
+            [ "asset": $asset, "value": 100000000 ]
+          ]
+        ]);
+        $code = script(0x76a914f54a5851e9372b87810a8e60cdd2e7cfd80b6e3188ac);
+        $value = 0x0850863ad64a87ae8a2fe83c1af1a8403cb53f53e486d8511dad8a04887e5b2352;
+        "#,
+        &[
+            "elements::sighash($tx, 0, $code, $value) == 0xe201b4019129a03ca0304989731c6dccde232c854d86fce999b7411da1e90048",
+            "elements::sighash($tx, 0, $code, $value, 131) == 0x7fc34367b42bf0e2bb78d8c20f45a64b81b2d4fbb59cbff8649322f619e88a0f",
+            "elements::sighash($tx, 0, $code, 100000000, 2) == 0x00730922d0e1d55b4b5fffafd087b06aeb44c4cedb58d8e182cbb9b87382cddb",
+        ],
+    );
+    for sighash_type in ["0", "4", "128", "65"] {
+        let setup = "elements::sighash(elements::tx([ \"inputs\": [ 0xaff27bbc41ba6e5549470f909ce55ded9fa63f26e7fad618ff02afebccf55d71:0 ] ]), 0, ``, 1000";
+        assert!(eval(format!("{}, {})", setup, sighash_type).as_str()).is_err());
+    }
+
+    // Confidential address test vectors from rust-elements, using the key as its own blinding key
+    test_checks(
+        "$pk = pubkey(0x0212bf0ea45b733dfde8ecb5e896306c4165c666c99fc5d1ab887f71393a975cea);",
+        &[
+            r#"elements::address(wpkh($pk)) == "ex1qew0l0emv7449u7hqgc8utzdzryhse79ydjqgek""#,
+            r#"elements::address(wpkh($pk), $pk) == "lq1qqgft7r4ytdenml0gaj67393sd3qkt3nxex0ut5dt3plhzwf6jaww4jul7lnkeat2teawq3s0cky6yxf0pnu2gs2923tg58xcz""#,
+            r#"elements::address(wsh(``), $pk) == "lq1qqgft7r4ytdenml0gaj67393sd3qkt3nxex0ut5dt3plhzwf6jaww4casc3pf3lquzjd0haxgn9hmjfp84eq7geymjdx2f9verdu99wz47jmkmgmr9a4s""#,
+            r#"elements::address(wpkh($pk), $pk, "liquidtestnet") == "tlq1qqgft7r4ytdenml0gaj67393sd3qkt3nxex0ut5dt3plhzwf6jaww4jul7lnkeat2teawq3s0cky6yxf0pnu2gq8g2kuxfj8ft""#,
+            r#"elements::address(wpkh($pk), $pk, "elementsregtest") == "el1qqgft7r4ytdenml0gaj67393sd3qkt3nxex0ut5dt3plhzwf6jaww4jul7lnkeat2teawq3s0cky6yxf0pnu2gmz9ej9kyq5yc""#,
+        ],
+    );
+}
+
+#[test]
+fn test_elements_exec() {
+    test_checks(
+        r#"
+        $tx = elements::tx([
+          "inputs": [ [ "prevout": 0x05a047c98e82a848dee94efcf32462b065198bebf2404d201ba2e06db30b28f4:0, "sequence": 4294967293 ] ],
+          "outputs": [ [ "script_pubkey": witnessProgram(1, 0x4e73), "asset": LBTC, "value": 9000 ], [ "asset": LBTC, "value": 1000 ] ]
+        ]);
+        $ctx = [ "tx": $tx, "prevouts": [ [ "script_pubkey": witnessProgram(1, 0x4e73), "asset": LBTC, "value": 10000 ] ] ];
+        $failed = elements::exec(`checkOutput(0, LBTC, le64(9000), 1, 0x4e73) mark::comment("fee") checkOutput(1, LBTC, le64(999), 0, 0x) 1`, $ctx);
+        "#,
+        &[
+            r#"t(elements::exec(`0 INPUT_VALUE 0 OUTPUT_VALUE 1 OUTPUT_VALUE ADD64_VERIFY OP_EQUAL`, $ctx), "success")"#,
+            r#"t(elements::exec(`REQUIRE_RBF checkSameSpk(0, 0) le64(-7) le64(2) DIV64FLOOR_VERIFY le64(-4) OP_EQUAL`, $ctx), "success")"#,
+            r#"!t($failed, "success")"#,
+            r#"t($failed, "opcode") == "OP_EQUALVERIFY""#,
+            r#"t($failed, "marker") == "fee""#,
+            r#"!t(elements::exec(`1 OP_INSPECTINPUTVALUE`, $ctx), "success")"#,
+            r#"t(elements::exec(`OP_CHECKSIG`, $ctx + [ "mock_signatures": true ], [ 0x01, 0x4e73 ]), "success")"#,
+        ],
+    );
+}
+
 fn replace_dummy(s: &str) -> String {
     s.replace(
         "A",